@@ -0,0 +1,87 @@
+//! Smooth and chamfered booleans.
+//!
+//! The smooth variants blend the two fields over a band of width `k` around
+//! the places where they're equal. Their gradients are always a convex
+//! combination of the input gradients, so if both inputs are Lipschitz-1
+//! (exact or bound distance fields) then so is the result, and it's a bound
+//! on the distance to the blended surface. The chamfer variants add a flat
+//! bevel, built from the average of the two fields so that it's Lipschitz-1
+//! as well; this underestimates the distance across the bevel, but doesn't
+//! move its surface.
+use fidget::context::Tree;
+
+/// Shape of a smooth blend
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Blend {
+    /// Quadratic polynomial blend, which only differs from the sharp boolean
+    /// where the inputs are within `k` of each other
+    Polynomial,
+    /// Exponential (log-sum-exp) blend, which is smooth everywhere but never
+    /// exactly matches the sharp boolean
+    Exponential,
+}
+
+impl Blend {
+    /// Parses a blend name, "polynomial" or "exponential"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "polynomial" => Ok(Blend::Polynomial),
+            "exponential" => Ok(Blend::Exponential),
+            _ => Err(format!(
+                "unknown blend '{name}', expected 'polynomial' or 'exponential'"
+            )),
+        }
+    }
+}
+
+fn check(k: f64) -> Result<(), String> {
+    if k > 0.0 && k.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("blend radius must be positive and finite, got {k}"))
+    }
+}
+
+/// Smooth minimum of `a` and `b`
+pub fn smooth_union(a: &Tree, b: &Tree, k: f64, blend: Blend) -> Result<Tree, String> {
+    check(k)?;
+    let gap = (a.clone() - b.clone()).abs();
+    let offset = match blend {
+        // h = max(k - |a - b|, 0) / k, offset = h² k / 4
+        Blend::Polynomial => (k - gap).max(0.0).square() / (4.0 * k),
+        // -k ln(e^(-a/k) + e^(-b/k)), rewritten around min(a, b) so that the
+        // exponent is never positive
+        Blend::Exponential => ((gap / -k).exp() + 1.0).ln() * k,
+    };
+    Ok(a.min(b.clone()) - offset)
+}
+
+/// Smooth maximum of `a` and `b`
+pub fn smooth_intersection(a: &Tree, b: &Tree, k: f64, blend: Blend) -> Result<Tree, String> {
+    Ok(smooth_union(&a.neg(), &b.neg(), k, blend)?.neg())
+}
+
+/// Smoothly removes `b` from `a`
+pub fn smooth_difference(a: &Tree, b: &Tree, k: f64, blend: Blend) -> Result<Tree, String> {
+    smooth_intersection(a, &b.neg(), k, blend)
+}
+
+/// Union with a bevel where the surfaces meet, reaching `k` along each one
+pub fn chamfer_union(a: &Tree, b: &Tree, k: f64) -> Result<Tree, String> {
+    check(k)?;
+    let bevel = (a.clone() + b.clone() - k) / 2.0;
+    Ok(a.min(b.clone()).min(bevel))
+}
+
+/// Intersection with a bevel where the surfaces meet, reaching `k` along
+/// each one
+pub fn chamfer_intersection(a: &Tree, b: &Tree, k: f64) -> Result<Tree, String> {
+    check(k)?;
+    let bevel = (a.clone() + b.clone() + k) / 2.0;
+    Ok(a.max(b.clone()).max(bevel))
+}
+
+/// Removes `b` from `a`, with a bevel of size `k` along the cut
+pub fn chamfer_difference(a: &Tree, b: &Tree, k: f64) -> Result<Tree, String> {
+    chamfer_intersection(a, &b.neg(), k)
+}
@@ -0,0 +1,113 @@
+//! Shapes that are compiled once and evaluated many times.
+use fidget::{
+    context::{Context, Node},
+    eval::{BulkEvaluator, Function, MathFunction, TracingEvaluator},
+    mesh::{Mesh, Octree, Settings},
+    render::{
+        BitRenderMode, ImageRenderConfig, ImageSize, RenderHints, View2, View3, VoxelRenderConfig,
+        VoxelSize,
+    },
+    shape::{EzShape, Shape, ShapeBulkEval, ShapeTape, ShapeTracingEval},
+    types::Interval,
+    Error,
+};
+use nalgebra::{Vector2, Vector3};
+
+/// A shape, along with the tapes and evaluators used to query it
+///
+/// Building the tapes is the expensive part of compilation (for the JIT
+/// backend, it generates machine code), so they're built once up front and
+/// reused by every query.
+pub struct Compiled<F: Function> {
+    shape: Shape<F>,
+    point_tape: ShapeTape<<F::PointEval as TracingEvaluator>::Tape>,
+    point_eval: ShapeTracingEval<F::PointEval>,
+    interval_tape: ShapeTape<<F::IntervalEval as TracingEvaluator>::Tape>,
+    interval_eval: ShapeTracingEval<F::IntervalEval>,
+    float_tape: ShapeTape<<F::FloatSliceEval as BulkEvaluator>::Tape>,
+    float_eval: ShapeBulkEval<F::FloatSliceEval>,
+}
+
+impl<F: MathFunction + RenderHints + Clone> Compiled<F> {
+    pub fn new(ctx: &Context, root: Node) -> Result<Self, Error> {
+        let shape = Shape::<F>::new(ctx, root)?;
+        Ok(Compiled {
+            point_tape: shape.ez_point_tape(),
+            point_eval: Shape::<F>::new_point_eval(),
+            interval_tape: shape.ez_interval_tape(),
+            interval_eval: Shape::<F>::new_interval_eval(),
+            float_tape: shape.ez_float_slice_tape(),
+            float_eval: Shape::<F>::new_float_slice_eval(),
+            shape,
+        })
+    }
+
+    /// Evaluates the shape at a single point
+    pub fn eval(&mut self, x: f32, y: f32, z: f32) -> Result<f32, Error> {
+        let (v, _trace) = self.point_eval.eval(&self.point_tape, x, y, z)?;
+        Ok(v)
+    }
+
+    /// Evaluates the shape at many points
+    pub fn eval_many(&mut self, xs: &[f32], ys: &[f32], zs: &[f32]) -> Result<Vec<f32>, Error> {
+        let out = self.float_eval.eval(&self.float_tape, xs, ys, zs)?;
+        Ok(out.to_vec())
+    }
+
+    /// Returns bounds on the shape's value over a box
+    pub fn eval_interval(
+        &mut self,
+        x: (f32, f32),
+        y: (f32, f32),
+        z: (f32, f32),
+    ) -> Result<(f32, f32), Error> {
+        let (v, _trace) = self.interval_eval.eval(
+            &self.interval_tape,
+            Interval::new(x.0, x.1),
+            Interval::new(y.0, y.1),
+            Interval::new(z.0, z.1),
+        )?;
+        Ok((v.lower(), v.upper()))
+    }
+
+    /// Meshes the shape within a cube of half-size `scale` around `center`
+    pub fn mesh(&self, depth: u8, center: [f32; 3], scale: f32) -> Mesh {
+        let settings = Settings {
+            depth,
+            view: View3::from_center_and_scale(Vector3::from(center), scale),
+            ..Default::default()
+        };
+        let octree = Octree::build(&self.shape, settings);
+        octree.walk_dual(settings)
+    }
+
+    /// Renders a `size × size` occupancy image of the XY plane
+    ///
+    /// Returns one byte per pixel (255 inside, 0 outside), row by row from
+    /// the top of the image, or `None` if rendering was cancelled.
+    pub fn render_2d(&self, size: u32, center: [f32; 2], scale: f32) -> Option<Vec<u8>> {
+        let cfg = ImageRenderConfig {
+            image_size: ImageSize::new(size, size),
+            view: View2::from_center_and_scale(Vector2::from(center), scale),
+            tile_sizes: F::tile_sizes_2d(),
+            ..Default::default()
+        };
+        let image = cfg.run::<F, BitRenderMode>(self.shape.clone())?;
+        Some(image.iter().map(|&p| if p { 255 } else { 0 }).collect())
+    }
+
+    /// Renders a `size × size` heightmap, looking down the Z axis
+    ///
+    /// Each pixel is the depth of the first filled voxel in its column, from
+    /// 0 (empty) up to `size`, with rows ordered from the top of the image.
+    pub fn render_3d(&self, size: u32, center: [f32; 3], scale: f32) -> Option<Vec<u32>> {
+        let cfg = VoxelRenderConfig {
+            image_size: VoxelSize::new(size, size, size),
+            view: View3::from_center_and_scale(Vector3::from(center), scale),
+            tile_sizes: F::tile_sizes_3d(),
+            ..Default::default()
+        };
+        let (depth, _normals) = cfg.run(self.shape.clone())?;
+        Some(depth.iter().copied().collect())
+    }
+}
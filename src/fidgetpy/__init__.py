@@ -1,3 +1,3 @@
-from . import math, shapes, types, errors
+from . import math, shapes, types, errors, toolpath
 
-__all__ = ["math", "shapes", "types", "errors"]
+__all__ = ["math", "shapes", "types", "errors", "toolpath"]
@@ -1,8 +1,35 @@
-from typing import Self
+from typing import Iterator, Self
 
 class FidgetError(Exception):
     """Wrapper around internal fidget library errors."""
 
+BACKENDS: list[str]
+"""Evaluator backends available in this build, with the default first:
+"jit" (native code, unless built without the jit feature) and "vm"
+(fidget's interpreter)."""
+
+def mesh_multi(
+    trees: list[Tree],
+    depth: int,
+    cx: float,
+    cy: float,
+    cz: float,
+    region_size: float,
+    vars: dict[Tree | str, float] | None = None,
+    backend: str | None = None,
+) -> Mesh:
+    """Mesh several disjoint trees as materials of one part. Each point is
+    assigned to the material with the smallest value there, if any is
+    negative. As for Tree.mesh, the cube of half-size region_size around
+    (cx, cy, cz) is divided into an octree with 2**depth cells per axis,
+    which is only subdivided near surfaces; all materials are classified in
+    the same pass. Interfaces between materials are meshed once and shared,
+    so the materials meet without gaps or overlaps; interface triangles
+    appear once per material, with opposite winding. Returns a single mesh
+    with Mesh.materials set, with each material's surface closed off at the
+    faces of the region."""
+    ...
+
 class Mesh:
     """A triangle mesh, represented by a list of vertices
     (3-tuples of xyz coordinates), and a list of triangles
@@ -11,14 +38,59 @@ class Mesh:
 
     vertices: list[tuple[float, float, float]]
     triangles: list[tuple[int, int, int]]
+    attributes: dict[str, list[float]]
+    """Per-vertex values requested with Tree.mesh(attributes=...), one per
+    vertex."""
+    materials: list[int] | None
+    """Material index of each triangle for meshes built by mesh_multi, or
+    None for single-material meshes."""
+
+    def split_materials(self) -> list[Mesh]:
+        """Split into one mesh per material, in material order, each with
+        only the vertices it uses. A single-material mesh is returned as a
+        one-element list."""
+        ...
+
+    def to_3mf(self, names: list[str] | None = None) -> bytes:
+        """Convert to a 3MF package with one object per material, each
+        assigned to its own entry in a base material group. names gives the
+        material names, one per material."""
+        ...
 
     def to_stl(self) -> bytes:
         """Convert to a binary stl"""
         ...
 
+    def to_ply(self, color: str | None = None) -> bytes:
+        """Convert to a binary PLY file, with each attribute as a float
+        vertex property. If color names an attribute, its values are also
+        written as red/green/blue vertex colors, scaled from the smallest to
+        the largest value."""
+        ...
+
+    def to_obj(self, color: str | None = None) -> str:
+        """Convert to a Wavefront OBJ file. OBJ can't store attributes, but
+        one can be written as vertex colors, as in to_ply."""
+        ...
+
+    def to_glb(self, color: str | None = None) -> bytes:
+        """Convert to a binary glTF 2.0 file. Each attribute is stored as a
+        float vertex attribute named with a leading underscore in upper case
+        (e.g. "_STRESS"), and color works as in to_ply, using COLOR_0."""
+        ...
+
 class Tree:
     """A tree structure of arbitrary mathematical operations."""
 
+    op: str
+    """The operation at the root of this tree, named as in to_vm: "const",
+    "var-x", "var-<name>", "add", "atan2", etc. Trees loaded with
+    from_bytes may also contain "remap" nodes."""
+    children: list[Self]
+    """The operands of the root operation, in order."""
+    constant_value: float | None
+    """The value of a "const" tree, or None for other operations."""
+
     @staticmethod
     def x() -> Self:
         """Returns a tree representing the x axis variable."""
@@ -35,8 +107,10 @@ class Tree:
         ...
 
     @staticmethod
-    def var() -> Self:
-        """Returns a tree representing an arbitrary anonymous variable."""
+    def var(name: str | None = None) -> Self:
+        """Returns a tree representing a variable. Variables created with the
+        same name are the same variable; without a name, a new anonymous
+        variable is returned. "x", "y" and "z" are reserved for the axes."""
         ...
 
     @staticmethod
@@ -44,13 +118,251 @@ class Tree:
         """Returns a tree representing a fixed floating point value."""
         ...
 
-    def eval(self, x: float, y: float, z: float) -> float:
+    def eval(self, x: float, y: float, z: float, backend: str | None = None) -> float:
         """evaluate this tree at a single point in 3D space
-        This is not efficient, and mostly useful for basic tests."""
+        This is not efficient, and mostly useful for basic tests. Without a
+        backend, the tree is interpreted in double precision; with one, it's
+        compiled for that backend and evaluated in single precision, which
+        is useful for cross-checking backends."""
+        ...
+
+    def eval_map(
+        self, varmap: dict[Self | str, float], backend: str | None = None
+    ) -> float:
+        """Evaluate this tree using a single value for each variable, keyed
+        either by the variable's tree or by its name ("x", "y", "z" or a
+        name given to Tree.var). backend is as for eval."""
+        ...
+
+    def bind(self, vars: dict[Self | str, float]) -> Self:
+        """Replace the given variables (including x, y or z) with constants,
+        folding every subexpression that becomes constant and simplifying
+        the result. For example, binding z gives a 2D slice of a 3D tree.
+        The result is exact if this tree is."""
+        ...
+
+    def substitute(self, mapping: dict[Self | str, Self | float]) -> Self:
+        """Replace variables, or any subtree matching a key, with other trees
+        or constants. All replacements happen at once; the replacement trees
+        themselves are not substituted into."""
+        ...
+
+    def stats(self) -> dict:
+        """Report on the size and evaluation cost of this tree:
+
+        - nodes: number of distinct nodes
+        - ops: node counts keyed by operation name (as in `op`)
+        - depth: number of nodes on the longest path from root to leaf
+        - choices: number of min / max nodes
+        - tape_len: length of the SSA tape that evaluators run
+        - reg_tape_len: length of the tape after register allocation
+        - registers / memory_slots: JIT registers used and values spilled
+        - jit_bytes: rough estimate of the JIT-compiled code size
+
+        Statistics are computed once per tree object and cached."""
+        ...
+
+    def walk(self) -> Iterator[Self]:
+        """Iterate over the distinct nodes of this tree, with every node
+        coming after its children and the tree itself coming last."""
+        ...
+
+    def structurally_equal(self, other: Self) -> bool:
+        """Check whether two trees are built from the same operations on the
+        same inputs, even if they were constructed separately. `==` and
+        `hash()` use this comparison, so trees can be used as dict keys;
+        use `compare` to build a tree comparing two values."""
+        ...
+
+    def simplify(self) -> Self:
+        """Return an equivalent tree with constants folded, identities such
+        as x * 1 and double negations removed, and repeated subexpressions
+        shared. The result is exact if this tree is."""
+        ...
+
+    def simplify_report(self) -> tuple[int, int]:
+        """Returns the number of nodes in this tree before and after
+        simplifying it."""
+        ...
+
+    def compile(
+        self,
+        backend: str | None = None,
+        vars: dict[Self | str, float] | None = None,
+    ) -> CompiledShape:
+        """Compile this tree once for repeated evaluation. backend is one of
+        BACKENDS, defaulting to the first. Variables other than xyz must be
+        given values in vars."""
+        ...
+
+    def mesh(
+        self,
+        depth: int,
+        cx: float,
+        cy: float,
+        cz: float,
+        region_size: float,
+        vars: dict[Self | str, float] | None = None,
+        backend: str | None = None,
+        attributes: dict[str, Self] | None = None,
+    ) -> Mesh:
+        """Mesh this tree within a cube of half-size region_size around
+        (cx, cy, cz), subdividing it depth times. backend selects the
+        evaluator, as in compile; the same applies to the export methods.
+        Each tree in attributes is evaluated at every vertex of the mesh,
+        and stored in Mesh.attributes under its name, which must consist of
+        letters, digits and underscores."""
+        ...
+
+    def transform(self, matrix: list[list[float]]) -> Self:
+        """Apply an affine transform, given as a 4x4 matrix (with a bottom
+        row of 0 0 0 1) mapping points of this shape to points of the
+        result. The matrix is inverted once and the axes are remapped."""
+        ...
+
+    def rotate(self, axis: str | tuple[float, float, float], angle: float) -> Self:
+        """Rotate by angle radians about "x", "y", "z" or a vector through
+        the origin, following the right-hand rule."""
+        ...
+
+    def scale(
+        self, sx: float, sy: float | None = None, sz: float | None = None
+    ) -> Self:
+        """Scale about the origin; sy and sz default to sx."""
+        ...
+
+    def mirror(self, plane: str | tuple[float, float, float]) -> Self:
+        """Mirror across "xy", "yz", "xz", or the plane through the origin
+        with the given normal vector."""
+        ...
+
+    def smooth_union(self, other: Tree, k: float, blend: str = "polynomial") -> Self:
+        """Union blended over a band of width k; blend is "polynomial" or
+        "exponential". Lipschitz-1 if both inputs are."""
+        ...
+
+    def smooth_intersection(
+        self, other: Tree, k: float, blend: str = "polynomial"
+    ) -> Self:
+        """Intersection blended over a band of width k."""
+        ...
+
+    def smooth_difference(
+        self, other: Tree, k: float, blend: str = "polynomial"
+    ) -> Self:
+        """Removes other, blended over a band of width k."""
+        ...
+
+    def chamfer_union(self, other: Tree, k: float) -> Self:
+        """Union with a flat bevel reaching k along each surface.
+        Lipschitz-1 if both inputs are."""
+        ...
+
+    def chamfer_intersection(self, other: Tree, k: float) -> Self:
+        """Intersection with a flat bevel reaching k along each surface."""
+        ...
+
+    def chamfer_difference(self, other: Tree, k: float) -> Self:
+        """Removes other, with a flat bevel reaching k along each surface."""
+        ...
+
+    def repeat(
+        self,
+        spacing: tuple[float, float, float],
+        count: tuple[int, int, int] | None = None,
+    ) -> Self:
+        """Repeat every spacing along each axis (zero spacing means no
+        repetition). With a count, copies are placed at 0, s, ..., (n - 1) s;
+        without one, they go on forever. The copy across the nearest cell
+        boundary is also evaluated, so copies may overlap their neighbors."""
+        ...
+
+    def mirror_repeat(
+        self,
+        spacing: tuple[float, float, float],
+        count: tuple[int, int, int] | None = None,
+    ) -> Self:
+        """Like repeat, with every other copy mirrored about its origin."""
+        ...
+
+    def polar_repeat(self, n: int, axis: str = "z") -> Self:
+        """Repeat n times around "x", "y" or "z" through the origin."""
+        ...
+
+    @property
+    def exact(self) -> bool:
+        """Whether this is known to be an exact distance field. Primitives
+        from `shapes` are exact, similarity transforms keep the flag, and
+        any other operation (including repetition) clears it."""
+        ...
+
+    def offset(self, d: float, redistance: bool = False) -> Self:
+        """Grow the shape by d (or shrink it, if negative). The result is
+        exact if this tree is exact and d >= 0. With redistance, the field is
+        first divided by its gradient length, and the result isn't exact."""
+        ...
+
+    def shell(self, thickness: float, redistance: bool = False) -> Self:
+        """Hollow out the shape, leaving a wall of thickness inside its
+        surface. The result is never flagged as exact."""
+        ...
+
+    def thicken_surface(self, thickness: float, redistance: bool = False) -> Self:
+        """Turn the surface into a solid of thickness centered on it. The
+        result is exact if this tree is, unless it's re-distanced."""
+        ...
+
+    def variables(self) -> list[str]:
+        """Returns the sorted names of all variables used by this tree."""
         ...
 
-    def eval_map(self, varmap: dict[Self:float]) -> float:
-        """Evaluate this tree using a single value for each xyz or anonymous variable."""
+    def to_vdb(
+        self,
+        path: str,
+        voxel_size: float,
+        half_width: float,
+        bounds: tuple[float, float, float, float, float, float] | None = None,
+        vars: dict[Self | str, float] | None = None,
+        backend: str | None = None,
+    ) -> int:
+        """Write a narrow-band level set of this tree to an OpenVDB file.
+        Voxels within half_width voxels of the surface are stored, and tiles
+        far from the surface are skipped using interval arithmetic.
+        bounds is (xmin, xmax, ymin, ymax, zmin, zmax); without it, the
+        surface is found by interval culling alone, within 4096 voxels of
+        the origin along each axis. Returns the number of active voxels
+        written. Variables other than xyz are replaced with the values in
+        vars before sampling; the same applies to the other export
+        methods."""
+        ...
+
+    def to_nrrd(
+        self,
+        path: str,
+        bounds: tuple[float, float, float, float, float, float],
+        resolution: tuple[int, int, int],
+        vars: dict[Self | str, float] | None = None,
+        backend: str | None = None,
+    ) -> None:
+        """Sample this tree on a regular grid and write it to a NRRD file,
+        readable by ParaView and 3D Slicer. bounds is
+        (xmin, xmax, ymin, ymax, zmin, zmax) and resolution is the number of
+        samples along each axis, including both faces of the bounds."""
+        ...
+
+    def to_raw(
+        self,
+        path: str,
+        bounds: tuple[float, float, float, float, float, float],
+        resolution: tuple[int, int, int],
+        vars: dict[Self | str, float] | None = None,
+        backend: str | None = None,
+    ) -> str:
+        """Sample this tree like to_nrrd, but write the samples as plain
+        little-endian float32 data, x varying fastest. A detached NRRD
+        header with spacing and origin is written next to it with a .nhdr
+        extension, and its path is returned; path itself can't end in
+        .nhdr."""
         ...
 
     def remap_xyz(self, new_x: Self, new_y: Self, new_z: Self) -> Self:
@@ -63,11 +375,33 @@ class Tree:
         ...
 
     def from_vm(self, src: str) -> Self:
-        """Build a tree from a simple SSA tape text representation."""
+        """Build a tree from a simple SSA tape text representation.
+
+        Variables other than x, y and z are written as `var-<name>`; a name
+        that was previously exported in this process refers to the same
+        variable, and any other name creates a new anonymous variable."""
+        ...
+
+    @staticmethod
+    def from_bytes(data: bytes, exact: bool = False) -> Self:
+        """Build a tree from the binary format written by `to_bytes`.
+
+        The format doesn't record whether the tree is exact, so that's passed
+        separately (pickling does this automatically)."""
+        ...
+
+    def to_bytes(self) -> bytes:
+        """Serialize a tree to a compact binary format.
+
+        Shared subexpressions are stored once. This is also the format used
+        when pickling trees."""
         ...
 
     def to_vm(self) -> str:
-        """Convert a tree to a simple SSA tape text representation."""
+        """Convert a tree to a simple SSA tape text representation.
+
+        Anonymous variables are given a name the first time they're exported,
+        which is kept for the rest of the process."""
         ...
 
     def to_graphviz(self) -> str:
@@ -204,3 +538,228 @@ class Tree:
         """Returns a tree that implements the two argument arc-tangent function.
         tree1.atan2(tree2) is equivalent to Tree.atan(tree2/tree1)."""
         ...
+
+class CompiledShape:
+    """A tree compiled for evaluation, created with Tree.compile."""
+
+    backend: str
+
+    def eval(self, x: float, y: float, z: float) -> float:
+        """Evaluate at a single point."""
+        ...
+
+    def eval_many(
+        self, xs: list[float], ys: list[float], zs: list[float]
+    ) -> list[float]:
+        """Evaluate at many points at once; all lists must have the same
+        length."""
+        ...
+
+    def eval_interval(
+        self,
+        x: tuple[float, float],
+        y: tuple[float, float],
+        z: tuple[float, float],
+    ) -> tuple[float, float]:
+        """Return conservative (lower, upper) bounds on the value over a box."""
+        ...
+
+    def mesh(
+        self, depth: int, cx: float, cy: float, cz: float, region_size: float
+    ) -> Mesh:
+        """Mesh like Tree.mesh, reusing the compiled shape."""
+        ...
+
+    def render_2d(
+        self, size: int, cx: float = 0.0, cy: float = 0.0, scale: float = 1.0
+    ) -> bytes:
+        """Render a size x size image of the XY plane (at z = 0) covering
+        [cx - scale, cx + scale] x [cy - scale, cy + scale]. Returns one byte
+        per pixel, 255 inside the shape and 0 outside, starting with the top
+        row."""
+        ...
+
+    def render_3d(
+        self,
+        size: int,
+        cx: float = 0.0,
+        cy: float = 0.0,
+        cz: float = 0.0,
+        scale: float = 1.0,
+    ) -> list[int]:
+        """Render a size x size heightmap looking down the z axis, with the
+        same region as render_2d extended along z. Each pixel is the height
+        of the top surface in voxels, from 0 (empty) to size."""
+        ...
+
+class TreeWalk:
+    """Iterator returned by Tree.walk."""
+
+    def __iter__(self) -> Self: ...
+    def __next__(self) -> Tree: ...
+
+Bounds = tuple[float, float, float, float, float, float]
+
+class _ShapesModule:
+    """Primitive shapes built natively, exposed as fidgetpy._core.shapes.
+    Each function returns a tree and its bounds, as
+    (xmin, xmax, ymin, ymax, zmin, zmax); 2D shapes are unbounded along z.
+    fidgetpy.shapes wraps these as Shape objects."""
+
+    @staticmethod
+    def sphere(r: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def circle(r: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def box(lx: float, ly: float, lz: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def rectangle(lx: float, ly: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def torus(major_radius: float, minor_radius: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def cylinder(radius: float, height: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def cone(
+        radius: float, height: float, top_radius: float = 0.0
+    ) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def capsule(
+        a: tuple[float, float, float],
+        b: tuple[float, float, float],
+        radius: float,
+    ) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def ellipsoid(rx: float, ry: float, rz: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def hexagonal_prism(radius: float, height: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def wedge(lx: float, ly: float, lz: float) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def rounded_box(
+        lx: float, ly: float, lz: float, radius: float
+    ) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def polygon(points: list[tuple[float, float]]) -> tuple[Tree, Bounds]: ...
+    @staticmethod
+    def extrude(
+        profile: Tree,
+        bounds: Bounds,
+        height: float,
+        axis: str | tuple[float, float, float] | None = None,
+        twist: float = 0.0,
+        taper: float = 1.0,
+        center: bool = False,
+    ) -> tuple[Tree, Bounds]:
+        """Extrude a 2D profile in the xy plane by height along axis
+        (default "z"), turning it by twist degrees and scaling it by taper
+        from bottom to top. Twisted and tapered fields are bounds."""
+        ...
+    @staticmethod
+    def sweep(
+        profile: Tree,
+        bounds: Bounds,
+        points: list[tuple[float, float, float]],
+        closed: bool = False,
+        frame: str = "rmf",
+        curve: str = "polyline",
+        samples: int = 8,
+    ) -> tuple[Tree, Bounds]:
+        """Sweep a 2D profile in the xy plane along a polyline or spline
+        ("polyline", "catmull-rom" or "bezier") through points, oriented by
+        rotation-minimizing ("rmf") or z-up ("fixed") frames. The result is
+        a bound."""
+        ...
+
+shapes: _ShapesModule
+
+class _TransformsModule:
+    """4x4 affine matrices for Tree.transform, exposed as
+    fidgetpy._core.transforms. Matrices are lists of rows."""
+
+    @staticmethod
+    def rotation(
+        axis: str | tuple[float, float, float], angle: float
+    ) -> list[list[float]]: ...
+    @staticmethod
+    def scaling(
+        sx: float, sy: float | None = None, sz: float | None = None
+    ) -> list[list[float]]: ...
+    @staticmethod
+    def mirroring(plane: str | tuple[float, float, float]) -> list[list[float]]: ...
+    @staticmethod
+    def translation(dx: float, dy: float, dz: float) -> list[list[float]]: ...
+    @staticmethod
+    def transform_bounds(matrix: list[list[float]], bounds: Bounds) -> Bounds:
+        """Axis-aligned bounds of a box after transforming it."""
+        ...
+
+transforms: _TransformsModule
+
+class _PatternsModule:
+    """Bounds of repeated shapes, exposed as fidgetpy._core.patterns."""
+
+    @staticmethod
+    def repeat_bounds(
+        bounds: Bounds,
+        spacing: tuple[float, float, float],
+        count: tuple[int, int, int] | None = None,
+        mirror: bool = False,
+    ) -> Bounds: ...
+    @staticmethod
+    def polar_repeat_bounds(bounds: Bounds, n: int, axis: str = "z") -> Bounds: ...
+
+patterns: _PatternsModule
+
+class Move:
+    """A toolpath move: a line ("G0" or "G1") or an arc ("G2" clockwise,
+    "G3" counter-clockwise) from start to end. Arcs turn around an absolute
+    center in plane "xy", "zx" or "yz" (G17, G18 or G19). tool is the tool
+    in the spindle, if known."""
+
+    kind: str
+    start: tuple[float, float, float]
+    end: tuple[float, float, float]
+    center: tuple[float, float, float] | None
+    plane: str
+    tool: int | None
+
+    def __init__(
+        self,
+        kind: str,
+        start: tuple[float, float, float],
+        end: tuple[float, float, float],
+        center: tuple[float, float, float] | None = None,
+        plane: str = "xy",
+        tool: int | None = None,
+    ) -> None: ...
+
+class _ToolpathModule:
+    """Toolpaths and swept volumes, exposed as fidgetpy._core.toolpath."""
+
+    Move: type[Move]
+
+    @staticmethod
+    def swept_volume(
+        tool: Tree, bounds: Bounds, moves: list[Move], tolerance: float = 0.01
+    ) -> tuple[Tree, Bounds]:
+        """Sweep a tool (tip at the origin, axis along +z, with the given
+        bounds, which must have an infinite zmax) along moves. Ramps, helices
+        and arcs outside the xy plane are approximated within tolerance. The
+        result is a bound."""
+        ...
+
+toolpath: _ToolpathModule
+
+class _GcodeModule:
+    """A G-code reader, exposed as fidgetpy._core.gcode."""
+
+    @staticmethod
+    def parse(
+        text: str, units: str | None = None
+    ) -> tuple[list[Move], dict[int, dict]]:
+        """Read a program's moves (in units "mm" or "in", defaulting to the
+        program's own) and its tool table, which maps tool numbers to dicts
+        of "diameter", "length" and "comment"."""
+        ...
+
+gcode: _GcodeModule
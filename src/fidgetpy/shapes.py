@@ -1,6 +1,15 @@
+import math as _math
 from numbers import Real
-from .types import Tree, Vec2, Vec3, Shape, BoundBox
-from .math import axes, axes2d, max_, min_, inf
+from .types import Tree, Vec2, Shape, BoundBox
+from .errors import ShapeBoundsWarning
+from ._core import mesh_multi as _mesh_multi, shapes as _shapes
+from ._core import patterns as _patterns, transforms as _transforms
+from .math import axes, max_, min_
+
+
+def _native(result) -> Shape:
+    tree, bounds = result
+    return Shape(tree, BoundBox(*bounds))
 
 
 def sphere(r) -> Shape:
@@ -9,7 +18,7 @@ def sphere(r) -> Shape:
 
     Exact distance field.
     """
-    return Shape(axes().length() - r, BoundBox(-r, r, -r, r, -r, r))
+    return _native(_shapes.sphere(r))
 
 
 def circle(r) -> Shape:
@@ -18,7 +27,7 @@ def circle(r) -> Shape:
 
     Exact distance field.
     """
-    return Shape(axes2d().length() - r, BoundBox(-r, r, -r, r, -inf, inf))
+    return _native(_shapes.circle(r))
 
 
 def box(lx, ly, lz) -> Shape:
@@ -27,12 +36,7 @@ def box(lx, ly, lz) -> Shape:
 
     Exact distance field.
     """
-    eps = min(lx, ly, lz) * 1e-6
-    p = axes()
-    q = abs(p) - Vec3(lx, ly, lz) / 2
-    df = (max_(q, eps)).length() + min_(max_(q.x, max_(q.y, q.z)), eps)
-    bb = BoundBox(-lx, lx, -ly, ly, -lz, lz)
-    return Shape(df, bb)
+    return _native(_shapes.box(lx, ly, lz))
 
 
 def rectangle(lx, ly) -> Shape:
@@ -41,12 +45,7 @@ def rectangle(lx, ly) -> Shape:
 
     Exact distance field.
     """
-    eps = min(lx, ly) * 1e-6
-    p = axes()
-    q = abs(p) - Vec2(lx, ly) / 2
-    df = (max_(q, eps)).length() + min_(max_(q.x, max_(q.y, q.z)), eps)
-    bb = BoundBox(-lx, lx, -ly, ly, -inf, inf)
-    return Shape(df, bb)
+    return _native(_shapes.rectangle(lx, ly))
 
 
 def torus(major_radius, minor_radius) -> Shape:
@@ -55,26 +54,85 @@ def torus(major_radius, minor_radius) -> Shape:
 
     Exact distance field.
     """
-    p = axes()
-    df = Vec2(p.xy.length() - major_radius, p.z).length() - minor_radius
-    halfwidth = major_radius + minor_radius
-    bb = BoundBox(
-        -halfwidth, halfwidth, -halfwidth, halfwidth, -minor_radius, minor_radius
-    )
-    return Shape(df, bb)
+    return _native(_shapes.torus(major_radius, minor_radius))
 
 
 def cylinder(radius, height) -> Shape:
     """
     A cylinder, aligned to the z-axis and centered at the origin.
+    It spans -height to height along z.
 
     Exact distance field.
     """
-    p = axes()
-    d = abs(Vec2(p.xy.length(), p.z)) - Vec2(radius, height)
-    df = min_(max_(d.x, d.y), radius * 1e-6) + max_(d, height * 1e-6).length()
-    bb = BoundBox(-radius, radius, -radius, radius, -height, height)
-    return Shape(df, bb)
+    return _native(_shapes.cylinder(radius, height))
+
+
+def cone(radius, height, top_radius=0.0) -> Shape:
+    """
+    A cone aligned to the z-axis, with a base of the given radius at z = 0
+    and its tip at z = height. A non-zero top_radius cuts off the tip,
+    making a frustum.
+
+    Exact distance field.
+    """
+    return _native(_shapes.cone(radius, height, top_radius))
+
+
+def capsule(a, b, radius) -> Shape:
+    """
+    A capsule around the segment between the points a and b.
+
+    Exact distance field.
+    """
+    return _native(_shapes.capsule(tuple(a), tuple(b), radius))
+
+
+def ellipsoid(rx, ry, rz) -> Shape:
+    """
+    An ellipsoid with the given semi-axes, centered at the origin.
+
+    Approximate distance field, exact on the surface.
+    """
+    return _native(_shapes.ellipsoid(rx, ry, rz))
+
+
+def hexagonal_prism(radius, height) -> Shape:
+    """
+    A hexagonal prism aligned to the z-axis and centered at the origin,
+    spanning -height to height along z. radius is the distance from the
+    axis to the middle of each side.
+
+    Exact distance field.
+    """
+    return _native(_shapes.hexagonal_prism(radius, height))
+
+
+def wedge(lx, ly, lz) -> Shape:
+    """
+    Half of a box, centered at the origin and cut diagonally from its top
+    edge at -x to its bottom edge at +x.
+
+    Exact distance field.
+    """
+    return _native(_shapes.wedge(lx, ly, lz))
+
+
+def rounded_box(lx, ly, lz, radius) -> Shape:
+    """
+    A rectangular box with rounded edges, centered at the origin.
+
+    Exact distance field.
+    """
+    return _native(_shapes.rounded_box(lx, ly, lz, radius))
+
+
+def polygon(points) -> Shape:
+    """
+    A 2D polygon with the given (x, y) vertices, which may be concave.
+
+    Exact distance field.
+    """
+    return _native(_shapes.polygon([tuple(p) for p in points]))
 
 
 def union(a: Shape, b: Shape) -> Shape:
@@ -141,21 +199,195 @@ def xor(a: Shape, b: Shape) -> Shape:
     return Shape(df, bb)
 
 
+def _union_bounds(a: Shape, b: Shape, pad) -> BoundBox:
+    return BoundBox(
+        min(a.bounds.xmin, b.bounds.xmin) - pad,
+        max(a.bounds.xmax, b.bounds.xmax) + pad,
+        min(a.bounds.ymin, b.bounds.ymin) - pad,
+        max(a.bounds.ymax, b.bounds.ymax) + pad,
+        min(a.bounds.zmin, b.bounds.zmin) - pad,
+        max(a.bounds.zmax, b.bounds.zmax) + pad,
+    )
+
+
+def _pad_bounds(bb: BoundBox, pad) -> BoundBox:
+    return BoundBox(
+        bb.xmin - pad,
+        bb.xmax + pad,
+        bb.ymin - pad,
+        bb.ymax + pad,
+        bb.zmin - pad,
+        bb.zmax + pad,
+    )
+
+
+def _intersection_bounds(a: Shape, b: Shape) -> BoundBox:
+    return BoundBox(
+        max(a.bounds.xmin, b.bounds.xmin),
+        min(a.bounds.xmax, b.bounds.xmax),
+        max(a.bounds.ymin, b.bounds.ymin),
+        min(a.bounds.ymax, b.bounds.ymax),
+        max(a.bounds.zmin, b.bounds.zmin),
+        min(a.bounds.zmax, b.bounds.zmax),
+    )
+
+
+def _smooth_pad(k, blend):
+    # how far a smooth union can bulge out past the sharp one
+    return k / 4 if blend == "polynomial" else k * _math.log(2)
+
+
+def smooth_union(a: Shape, b: Shape, k, blend="polynomial") -> Shape:
+    """
+    Union of the input shapes, with a fillet of radius about k where they
+    meet. blend is "polynomial" (only differs from the sharp union within k
+    of the seam) or "exponential" (smooth everywhere).
+
+    This operation produces an inexact (bound) distance field.
+    """
+    df = a.tree.smooth_union(b.tree, k, blend)
+    return Shape(df, _union_bounds(a, b, _smooth_pad(k, blend)))
+
+
+def smooth_intersection(a: Shape, b: Shape, k, blend="polynomial") -> Shape:
+    """
+    Intersection of the input shapes, with its edges rounded over about k.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    df = a.tree.smooth_intersection(b.tree, k, blend)
+    return Shape(df, _intersection_bounds(a, b))
+
+
+def smooth_difference(a: Shape, b: Shape, k, blend="polynomial") -> Shape:
+    """
+    Shape a with shape b removed, with the edges of the cut rounded over
+    about k.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    df = a.tree.smooth_difference(b.tree, k, blend)
+    return Shape(df, a.bounds)
+
+
+def chamfer_union(a: Shape, b: Shape, k) -> Shape:
+    """
+    Union of the input shapes, with a flat bevel where they meet that
+    reaches k along each surface.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    df = a.tree.chamfer_union(b.tree, k)
+    return Shape(df, _union_bounds(a, b, k / 2))
+
+
+def chamfer_intersection(a: Shape, b: Shape, k) -> Shape:
+    """
+    Intersection of the input shapes, with its edges beveled by k.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    df = a.tree.chamfer_intersection(b.tree, k)
+    return Shape(df, _intersection_bounds(a, b))
+
+
+def chamfer_difference(a: Shape, b: Shape, k) -> Shape:
+    """
+    Shape a with shape b removed, with the edges of the cut beveled by k.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    df = a.tree.chamfer_difference(b.tree, k)
+    return Shape(df, a.bounds)
+
+
 def move(shp: Shape, mx, my, mz) -> Shape:
     """
     Translate a shape by the given values
     """
-    mov = Vec3(mx, my, mz)
+    return transform(shp, _transforms.translation(mx, my, mz))
+
+
+def transform(shp: Shape, matrix) -> Shape:
+    """
+    Apply an affine transform, given as a 4x4 matrix mapping points of the
+    original shape to points of the transformed one.
+
+    Rotations, translations and mirrors preserve exact distance fields.
+    """
+    bb = shp.bounds
+    bounds = (bb.xmin, bb.xmax, bb.ymin, bb.ymax, bb.zmin, bb.zmax)
+    return Shape(
+        shp.tree.transform(matrix),
+        BoundBox(*_transforms.transform_bounds(matrix, bounds)),
+    )
+
+
+def rotate(shp: Shape, axis, angle) -> Shape:
+    """
+    Rotate a shape by angle radians about an axis through the origin,
+    either "x", "y", "z" or a vector, following the right-hand rule.
+    """
+    return transform(shp, _transforms.rotation(axis, angle))
+
+
+def scale(shp: Shape, sx, sy=None, sz=None) -> Shape:
+    """
+    Scale a shape about the origin. sy and sz default to sx.
+
+    Only uniform scaling preserves exact distance fields.
+    """
+    return transform(shp, _transforms.scaling(sx, sy, sz))
+
+
+def mirror(shp: Shape, plane) -> Shape:
+    """
+    Mirror a shape across a plane through the origin, either "xy", "yz",
+    "xz" or the plane's normal vector.
+    """
+    return transform(shp, _transforms.mirroring(plane))
+
+
+def _bounds_tuple(shp: Shape):
+    bb = shp.bounds
+    return (bb.xmin, bb.xmax, bb.ymin, bb.ymax, bb.zmin, bb.zmax)
+
+
+def repeat(shp: Shape, spacing, count=None) -> Shape:
+    """
+    Repeat a shape every (sx, sy, sz) along the axes; an axis with zero
+    spacing isn't repeated. With count=(nx, ny, nz), copies are placed at
+    0, s, ..., (n - 1) s along each axis, otherwise they go on forever.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    spacing, count = tuple(spacing), count and tuple(count)
+    return Shape(
+        shp.tree.repeat(spacing, count),
+        BoundBox(*_patterns.repeat_bounds(_bounds_tuple(shp), spacing, count)),
+    )
+
+
+def mirror_repeat(shp: Shape, spacing, count=None) -> Shape:
+    """
+    Like repeat, but every other copy is mirrored about its own origin, so
+    that neighboring copies meet symmetrically at the cell boundaries.
+    """
+    spacing, count = tuple(spacing), count and tuple(count)
+    bounds = _patterns.repeat_bounds(_bounds_tuple(shp), spacing, count, True)
+    return Shape(shp.tree.mirror_repeat(spacing, count), BoundBox(*bounds))
+
+
+def polar_repeat(shp: Shape, n, axis="z") -> Shape:
+    """
+    Repeat a shape n times around "x", "y" or "z" through the origin, e.g.
+    for a bolt circle.
+
+    This operation produces an inexact (bound) distance field.
+    """
     return Shape(
-        shp.tree.remap_xyz(*(axes() - mov)),
-        BoundBox(
-            shp.bounds.xmin + mx,
-            shp.bounds.xmax + mx,
-            shp.bounds.ymin + my,
-            shp.bounds.ymax + my,
-            shp.bounds.zmin + mz,
-            shp.bounds.zmax + mz,
-        ),
+        shp.tree.polar_repeat(n, axis),
+        BoundBox(*_patterns.polar_repeat_bounds(_bounds_tuple(shp), n, axis)),
     )
 
 
@@ -165,17 +397,115 @@ def expand(shp: Shape, amount: Real) -> Shape:
 
     For exact distance fields, sharp corners become rounded.
     For mitred distance fields, sharp corners are preserved.
+    This is offset with its default re-distancing; check Shape.exact to see
+    whether the result is trustworthy.
+    """
+    return offset(shp, amount)
+
+
+def _grow_redistance(shp: Shape, d, redistance) -> bool:
+    # the surface of a grown bound can move further than d, past the padded
+    # bounding box, so inexact fields are re-distanced unless asked not to be
+    if redistance is None:
+        return d > 0 and not shp.exact
+    if d > 0 and not (shp.exact or redistance):
+        raise ShapeBoundsWarning(
+            "Growing an inexact distance field without re-distancing it can "
+            "move its surface past the bounding box."
+            f" Original bounding box: {shp.bounds}"
+        )
+    return redistance
+
+
+def offset(shp: Shape, d: Real, redistance=None) -> Shape:
+    """
+    Grows a shape by d, or shrinks it if d is negative.
+
+    The surface only moves by exactly d if the field is exact: the result
+    is exact if the input is and d >= 0, and a bound otherwise. With
+    redistance, the field is first divided by its gradient length, which
+    corrects fields that aren't Lipschitz-1 (e.g. after non-uniform
+    scaling) near their surface; the result is then a bound.
+
+    By default, inexact fields are re-distanced when they're grown, since
+    their surface could otherwise move far past the grown bounding box;
+    growing one with redistance=False raises a ShapeBoundsWarning. As the
+    correction is first-order, offsets that are large compared to the
+    shape's curvature can still reach somewhat past the bounding box.
+    """
+    redistance = _grow_redistance(shp, d, redistance)
+    return Shape(shp.tree.offset(d, redistance), _pad_bounds(shp.bounds, max(d, 0)))
+
+
+def shell(shp: Shape, thickness: Real, redistance=False) -> Shape:
+    """
+    Hollows out a shape, leaving a wall of the given thickness inside its
+    surface.
+
+    This operation produces an inexact (bound) distance field.
+    """
+    return Shape(shp.tree.shell(thickness, redistance), shp.bounds)
+
+
+def thicken_surface(shp: Shape, thickness: Real, redistance=None) -> Shape:
+    """
+    Turns the surface of a shape into a solid of the given thickness,
+    centered on the surface; useful for open surfaces like gyroids.
+
+    Preserves exact distance fields, unless re-distanced. Like offset,
+    inexact fields are re-distanced by default.
     """
+    redistance = _grow_redistance(shp, thickness / 2, redistance)
     return Shape(
-        shp.tree - amount,
-        BoundBox(
-            shp.bounds.xmin - amount,
-            shp.bounds.xmax + amount,
-            shp.bounds.ymin - amount,
-            shp.bounds.ymax + amount,
-            shp.bounds.zmin - amount,
-            shp.bounds.zmax + amount,
-        ),
+        shp.tree.thicken_surface(thickness, redistance),
+        _pad_bounds(shp.bounds, thickness / 2),
+    )
+
+
+def extrude(shp: Shape, height, axis="z", twist=0, taper=1, center=False) -> Shape:
+    """
+    Extrude a 2D shape in the xy plane by height along an axis, either "x",
+    "y", "z" or a vector. Along "x" and "y", the profile's x and y axes are
+    cycled onto (y, z) and (z, x).
+
+    The profile turns by twist degrees and is scaled by taper from the
+    bottom to the top, e.g. for threads, drafted walls or twisted vases. The
+    extrusion starts at the origin, or is centered on it with center=True.
+
+    Straight extrusions of exact profiles are exact; twisted or tapered ones
+    produce an inexact (bound) distance field.
+    """
+    return _native(
+        _shapes.extrude(
+            shp.tree, _bounds_tuple(shp), height, axis, twist, taper, center
+        )
+    )
+
+
+def sweep(
+    shp: Shape, points, closed=False, frame="rmf", curve="polyline", samples=8
+) -> Shape:
+    """
+    Sweep a 2D shape in the xy plane along a path through a list of (x, y, z)
+    points, e.g. a tool profile along a toolpath or a tube along a spline.
+
+    curve is "polyline", "catmull-rom" (a spline through the points) or
+    "bezier" (cubic segments, with two control points between each pair of
+    end points); splines are sampled with samples pieces per segment. A
+    closed path joins its last point back to the first.
+
+    The profile's origin follows the path. With frame="rmf", its axes are
+    carried along by rotation-minimizing frames, which don't twist around
+    the path; with frame="fixed", its y axis stays as close to +z as
+    possible. Open ends are cut flat and joints are mitred, so the profile
+    should be small compared to the path's bends.
+
+    The result is an inexact (bound) distance field.
+    """
+    return _native(
+        _shapes.sweep(
+            shp.tree, _bounds_tuple(shp), points, closed, frame, curve, samples
+        )
     )
 
 
@@ -223,19 +553,77 @@ def revolve_z(shp: Shape) -> Tree:
     return Shape(df, bb)
 
 
+def mesh_multi(shapes: list[Shape], depth, vars=None, backend=None):
+    """
+    Mesh several disjoint shapes as separate materials of one part.
+
+    All shapes are meshed together over the union of their bounding boxes,
+    at the same resolution as Shape.mesh with the same depth, so neighboring
+    materials share their interfaces exactly. Returns a single mesh with a
+    material index per triangle, which can be split with
+    Mesh.split_materials or written with Mesh.to_3mf.
+    """
+    if not shapes:
+        raise ValueError("mesh_multi needs at least one shape")
+    boxes = [s.bounds for s in shapes]
+    bb = BoundBox(
+        min(b.xmin for b in boxes),
+        max(b.xmax for b in boxes),
+        min(b.ymin for b in boxes),
+        max(b.ymax for b in boxes),
+        min(b.zmin for b in boxes),
+        max(b.zmax for b in boxes),
+    )
+    bounds = (bb.xmin, bb.xmax, bb.ymin, bb.ymax, bb.zmin, bb.zmax)
+    if not all(_math.isfinite(v) for v in bounds):
+        raise ShapeBoundsWarning(
+            "Can't mesh shapes with a non-finite bounding box."
+            f" Combined bounding box: {bb}"
+        )
+    # the same region as Shape.mesh
+    sf = 1.01 * max(bb.xlength, bb.ylength, bb.zlength)
+    return _mesh_multi([s.tree for s in shapes], depth, *bb.center, sf, vars, backend)
+
+
 __all__ = [
     "box",
+    "capsule",
+    "chamfer_difference",
+    "chamfer_intersection",
+    "chamfer_union",
     "circle",
+    "cone",
     "cylinder",
     "difference",
+    "ellipsoid",
     "expand",
+    "extrude",
     "extrude_z",
+    "hexagonal_prism",
     "intersection",
+    "mesh_multi",
+    "mirror",
+    "mirror_repeat",
     "move",
+    "offset",
+    "polar_repeat",
+    "polygon",
     "rectangle",
+    "repeat",
     "revolve_z",
+    "rotate",
+    "rounded_box",
+    "scale",
+    "shell",
+    "smooth_difference",
+    "smooth_intersection",
+    "smooth_union",
     "sphere",
+    "sweep",
+    "thicken_surface",
     "torus",
+    "transform",
     "union",
+    "wedge",
     "xor",
 ]
@@ -0,0 +1,62 @@
+"""
+Toolpaths and the volumes that cutters sweep along them, for simulating
+3-axis machining.
+"""
+
+from ._core import gcode as _gcode, toolpath as _toolpath
+from .types import BoundBox, Shape
+
+Move = _toolpath.Move
+
+
+def swept_volume(tool: Shape, moves: list[Move], tolerance=0.01) -> Shape:
+    """
+    The volume that tool sweeps along a list of moves, i.e. the material
+    that they remove; subtract it from the stock to simulate machining.
+
+    The tool has its tip at the origin and its axis along +z, and must be a
+    solid of revolution that extends upwards forever (with bounds.zmax set
+    to math.inf) and doesn't get narrower going up, like the square, ball
+    and bullnose endmills and drill bits in examples/gcode.py; tools with a
+    finite zmax are rejected.
+    Horizontal and vertical moves are swept exactly. Ramps and helical arcs
+    are split into pieces that drop by at most tolerance, so the swept
+    volume may fall short of the true one by up to tolerance. Arcs outside
+    the xy plane are split into chords within tolerance of the arc, so the
+    error can go either way: the swept volume may also cut up to tolerance
+    too deep on the arc's concave side.
+
+    The result is an inexact (bound) distance field.
+    """
+    bb = tool.bounds
+    bounds = (bb.xmin, bb.xmax, bb.ymin, bb.ymax, bb.zmin, bb.zmax)
+    tree, bounds = _toolpath.swept_volume(tool.tree, bounds, moves, tolerance)
+    return Shape(tree, BoundBox(*bounds))
+
+
+def parse_gcode(text: str, units=None) -> tuple[list[Move], dict[int, dict]]:
+    """
+    Read a G-code program, returning its moves and its tool table.
+
+    Supports linear moves and arcs (G0 to G3, with I/J/K or R words), arc
+    planes (G17 to G19), units (G20, G21), absolute and incremental
+    distances (G90, G91, G90.1, G91.1), tool changes (T and M6) and drilling
+    cycles (G73, G81 to G83), which are expanded into their moves. Other
+    codes are skipped. Parameters (#), expressions ([...]) and checksums (*)
+    aren't supported, and raise a RuntimeError. The tool starts at the
+    origin, and the program ends at M2 or M30.
+
+    Positions are converted to units, "mm" or "in", defaulting to the units
+    that the program selects first. Each move records the tool in the
+    spindle, so the moves of one tool can be swept with swept_volume.
+
+    Tool table entries in the LinuxCNC format, such as
+    "T4 P4 Z0.0 D0.250 ; spot drill", are read from lines of their own or
+    from comments; the table maps tool numbers to dicts of "diameter",
+    "length" (the Z word) and "comment". Entries are taken to be in the
+    units that the program selects first, wherever they appear.
+    """
+    return _gcode.parse(text, units)
+
+
+__all__ = ["Move", "parse_gcode", "swept_volume"]
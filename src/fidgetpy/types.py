@@ -198,10 +198,16 @@ class Shape:
     tree: Tree
     bounds: BoundBox
 
-    def eval(self, x, y, z):
-        return self.tree.eval(x, y, z)
+    @property
+    def exact(self) -> bool:
+        """Whether the field is known to be an exact distance field, rather
+        than a bound; offsets are only trustworthy on exact fields."""
+        return self.tree.exact
+
+    def eval(self, x, y, z, backend=None):
+        return self.tree.eval(x, y, z, backend)
 
-    def mesh(self, depth):
+    def mesh(self, depth, vars=None, backend=None, attributes=None):
         # create an adjusted bounding box to compensate for infinite shapes
         bb = BoundBox(
             self.bounds.xmin if math.isfinite(self.bounds.xmin) else -1.0,
@@ -220,9 +226,37 @@ class Shape:
         # rescale the shape so that it fits inside a bounding box of [-1, 1]
         # on all axis
         sf = 1.01 * max(bb.xlength, bb.ylength, bb.zlength)
-        mesh = self.tree.mesh(depth, *bb.center, sf)
+        mesh = self.tree.mesh(depth, *bb.center, sf, vars, backend, attributes)
         return mesh
 
+    def to_nrrd(self, path, resolution, vars=None, backend=None):
+        """Sample this shape over its bounding box and write a NRRD volume."""
+        return self.tree.to_nrrd(
+            str(path), self._finite_bounds(), resolution, vars, backend
+        )
+
+    def to_vdb(self, path, voxel_size, half_width=3.0, vars=None, backend=None):
+        """Write a narrow-band level set covering this shape's bounding box
+        to an OpenVDB file."""
+        return self.tree.to_vdb(
+            str(path),
+            voxel_size,
+            half_width,
+            self._finite_bounds(),
+            vars,
+            backend,
+        )
+
+    def _finite_bounds(self):
+        bb = self.bounds
+        bounds = (bb.xmin, bb.xmax, bb.ymin, bb.ymax, bb.zmin, bb.zmax)
+        if not all(math.isfinite(v) for v in bounds):
+            raise ShapeBoundsWarning(
+                "Can't sample a shape with a non-finite bounding box."
+                f" Original bounding box: {self.bounds}"
+            )
+        return bounds
+
 
 __all__ = [
     "BoundBox",
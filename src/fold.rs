@@ -0,0 +1,127 @@
+//! Rebuilding of `Context` graphs through the context's builder functions.
+//!
+//! `Context::import` inserts binary operations without the special-casing that
+//! `Context::and`, `Context::mul`, etc. perform, so an imported graph can hold
+//! nodes (such as `and` with a constant left-hand side) that the SSA compiler
+//! refuses to lower. Replaying every node through the builders applies their
+//! constant folding and collapsing rules.
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, UnaryOpcode},
+    var::Var,
+    Error,
+};
+use std::collections::HashMap;
+
+/// Builds a unary operation with the context's folding rules
+pub fn unary(ctx: &mut Context, op: UnaryOpcode, a: Node) -> Result<Node, Error> {
+    match op {
+        UnaryOpcode::Neg => ctx.neg(a),
+        UnaryOpcode::Abs => ctx.abs(a),
+        UnaryOpcode::Recip => ctx.recip(a),
+        UnaryOpcode::Sqrt => ctx.sqrt(a),
+        UnaryOpcode::Square => ctx.square(a),
+        UnaryOpcode::Floor => ctx.floor(a),
+        UnaryOpcode::Ceil => ctx.ceil(a),
+        UnaryOpcode::Round => ctx.round(a),
+        UnaryOpcode::Sin => ctx.sin(a),
+        UnaryOpcode::Cos => ctx.cos(a),
+        UnaryOpcode::Tan => ctx.tan(a),
+        UnaryOpcode::Asin => ctx.asin(a),
+        UnaryOpcode::Acos => ctx.acos(a),
+        UnaryOpcode::Atan => ctx.atan(a),
+        UnaryOpcode::Exp => ctx.exp(a),
+        UnaryOpcode::Ln => ctx.ln(a),
+        UnaryOpcode::Not => ctx.not(a),
+    }
+}
+
+/// Builds a binary operation with the context's folding rules
+pub fn binary(ctx: &mut Context, op: BinaryOpcode, a: Node, b: Node) -> Result<Node, Error> {
+    match op {
+        BinaryOpcode::Add => ctx.add(a, b),
+        BinaryOpcode::Sub => ctx.sub(a, b),
+        BinaryOpcode::Mul => ctx.mul(a, b),
+        BinaryOpcode::Div => ctx.div(a, b),
+        BinaryOpcode::Atan => ctx.atan2(a, b),
+        BinaryOpcode::Min => ctx.min(a, b),
+        BinaryOpcode::Max => ctx.max(a, b),
+        BinaryOpcode::Compare => ctx.compare(a, b),
+        BinaryOpcode::Mod => ctx.modulo(a, b),
+        BinaryOpcode::And => ctx.and(a, b),
+        BinaryOpcode::Or => ctx.or(a, b),
+    }
+}
+
+/// Returns every node below `root`, children before parents
+pub fn nodes(ctx: &Context, root: Node) -> Result<Vec<Node>, Error> {
+    // Do recursion on the heap to avoid stack overflows for deep trees
+    let mut seen = std::collections::HashSet::new();
+    let mut out = vec![];
+    let mut todo = vec![(root, false)];
+    while let Some((node, children_done)) = todo.pop() {
+        if children_done {
+            out.push(node);
+            continue;
+        }
+        if !seen.insert(node) {
+            continue;
+        }
+        let op = ctx.get_op(node).ok_or(Error::BadNode)?;
+        todo.push((node, true));
+        todo.extend(op.iter_children().map(|c| (c, false)));
+    }
+    Ok(out)
+}
+
+/// Copies the graph below `root` from `src` into `dst`, returning the new root
+pub fn rebuild(src: &Context, root: Node, dst: &mut Context) -> Result<Node, Error> {
+    bind(src, root, dst, &HashMap::new())
+}
+
+/// Like [`rebuild`], but replaces the variables in `values` with constants
+pub fn bind(
+    src: &Context,
+    root: Node,
+    dst: &mut Context,
+    values: &HashMap<Var, f64>,
+) -> Result<Node, Error> {
+    let mut seen: HashMap<Node, Node> = HashMap::new();
+    for node in nodes(src, root)? {
+        let out = match *src.get_op(node).ok_or(Error::BadNode)? {
+            Op::Input(v) => match values.get(&v) {
+                Some(c) => dst.constant(*c),
+                None => dst.var(v),
+            },
+            Op::Const(c) => dst.constant(c.0),
+            Op::Unary(op, a) => unary(dst, op, seen[&a])?,
+            Op::Binary(op, a, b) => binary(dst, op, seen[&a], seen[&b])?,
+        };
+        seen.insert(node, out);
+    }
+    Ok(seen[&root])
+}
+
+/// Replaces nodes below `root` using the `replace` map, rebuilding everything
+/// above a replaced node in the same context
+///
+/// Replacements are inserted as-is, without substituting within them.
+pub fn substitute(
+    ctx: &mut Context,
+    root: Node,
+    replace: &HashMap<Node, Node>,
+) -> Result<Node, Error> {
+    let mut seen: HashMap<Node, Node> = HashMap::new();
+    for node in nodes(ctx, root)? {
+        let out = if let Some(r) = replace.get(&node) {
+            *r
+        } else {
+            match *ctx.get_op(node).ok_or(Error::BadNode)? {
+                Op::Input(..) | Op::Const(..) => node,
+                Op::Unary(op, a) => unary(ctx, op, seen[&a])?,
+                Op::Binary(op, a, b) => binary(ctx, op, seen[&a], seen[&b])?,
+            }
+        };
+        seen.insert(node, out);
+    }
+    Ok(seen[&root])
+}
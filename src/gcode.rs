@@ -0,0 +1,464 @@
+//! A G-code reader, turning programs into toolpath moves.
+//!
+//! This covers what's needed to simulate 3-axis milling: linear moves and
+//! arcs (`G0` to `G3`, with `I`/`J`/`K` or `R`), arc planes (`G17` to
+//! `G19`), units (`G20`, `G21`), absolute and incremental distances (`G90`,
+//! `G91`, and `G90.1`, `G91.1` for arc centers), tool changes (`T` and
+//! `M6`), and the drilling cycles (`G73`, `G81` to `G83`, with `G98` and
+//! `G99`), which are expanded into the moves they make. Other codes, such as
+//! spindle, coolant and work offset codes, don't move the tool and are
+//! skipped, as are the axis words of `G28`, `G30`, `G53` and `G92`.
+//!
+//! Tool tables in the LinuxCNC format (`T4 P4 Z0.0 D0.250 ; comment`) are
+//! read as well, whether they're on lines of their own or inside comments.
+//!
+//! Parameters (`#1 = 2`), expressions (`[1 + 2]`) and checksums (`*42`)
+//! aren't supported, and programs that use them are rejected rather than
+//! read wrongly.
+//!
+//! Positions are converted to one set of units: by default, those that the
+//! program selects first. Tool tables usually come before any units code,
+//! so their entries are in the units that the program selects first,
+//! wherever they appear. The tool starts at the origin.
+use crate::toolpath::{Kind, Move, Plane, PyMove};
+use nalgebra::Vector3;
+use pyo3::{exceptions::PyRuntimeError, prelude::*, types::PyDict};
+use std::collections::BTreeMap;
+
+type Point = Vector3<f64>;
+
+/// Units of length
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Units {
+    Millimeters,
+    Inches,
+}
+
+impl Units {
+    /// Parses a unit name, "mm" or "in"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "mm" => Ok(Units::Millimeters),
+            "in" => Ok(Units::Inches),
+            _ => Err(format!("unknown units '{name}', expected 'mm' or 'in'")),
+        }
+    }
+
+    fn millimeters(&self) -> f64 {
+        match self {
+            Units::Millimeters => 1.0,
+            Units::Inches => 25.4,
+        }
+    }
+}
+
+/// An entry of a tool table
+#[derive(Clone, Debug, Default)]
+pub struct Tool {
+    pub diameter: Option<f64>,
+    /// Length offset, from the `Z` word
+    pub length: Option<f64>,
+    pub comment: String,
+}
+
+/// The moves of a program, and the tools that it describes
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub moves: Vec<Move>,
+    pub tools: BTreeMap<u32, Tool>,
+}
+
+/// A line of G-code, split into words and comments
+struct Block {
+    words: Vec<(char, f64)>,
+    comments: Vec<String>,
+}
+
+impl Block {
+    fn get(&self, letter: char) -> Option<f64> {
+        self.words
+            .iter()
+            .rev()
+            .find(|(c, _)| *c == letter)
+            .map(|(_, v)| *v)
+    }
+
+    fn codes(&self, letter: char) -> impl Iterator<Item = f64> + '_ {
+        self.words
+            .iter()
+            .filter(move |(c, _)| *c == letter)
+            .map(|(_, v)| *v)
+    }
+
+    fn has(&self, letters: &str) -> bool {
+        self.words.iter().any(|(c, _)| letters.contains(*c))
+    }
+}
+
+fn unsupported(c: char) -> String {
+    format!("parameters, expressions and checksums aren't supported, found '{c}'")
+}
+
+fn split(line: &str) -> Result<Block, String> {
+    let mut words = vec![];
+    let mut comments = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                let text: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                comments.push(text.trim().to_owned());
+            }
+            ';' => {
+                comments.push(chars.by_ref().collect::<String>().trim().to_owned());
+            }
+            // block deletion and program delimiters are ignored
+            '/' | '%' => (),
+            c if c.is_whitespace() => (),
+            c if c.is_ascii_alphabetic() => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                if let Some(d) = chars.next_if(|d| "#[".contains(*d)) {
+                    return Err(unsupported(d));
+                }
+                let mut number = String::new();
+                while let Some(d) = chars.next_if(|d| d.is_ascii_digit() || "+-.".contains(*d)) {
+                    number.push(d);
+                }
+                let value = number
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| format!("expected a number after '{c}'"))?;
+                words.push((c.to_ascii_uppercase(), value));
+            }
+            '#' | '[' | '*' => return Err(unsupported(c)),
+            _ => return Err(format!("unexpected character '{c}'")),
+        }
+    }
+    Ok(Block { words, comments })
+}
+
+/// Reads a tool table entry, e.g. `T4 P4 Z0.0 D0.250 ; comment`, in the
+/// units it's written in
+fn tool_entry(block: &Block) -> Option<(u32, Tool)> {
+    if block.has("GMXY") || block.get('P').is_none() {
+        return None;
+    }
+    let number = block.get('T')?;
+    Some((
+        number as u32,
+        Tool {
+            diameter: block.get('D'),
+            length: block.get('Z'),
+            comment: block.comments.join(" "),
+        },
+    ))
+}
+
+/// A G code as an integer, ten times its value, so that `G90.1` is `901`
+fn code(g: f64) -> i32 {
+    (g * 10.0).round() as i32
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Motion {
+    Move(Kind),
+    /// A drilling cycle, which feeds to the bottom of the hole and retracts
+    Drill,
+}
+
+struct Reader {
+    program: Program,
+    /// Output units per program unit
+    scale: f64,
+    position: Point,
+    absolute: bool,
+    absolute_centers: bool,
+    plane: Plane,
+    motion: Option<Motion>,
+    /// Whether drilling cycles retract to where they started (`G98`), rather
+    /// than to the `R` plane (`G99`)
+    retract_to_start: bool,
+    /// Height before the current run of drilling cycles, and their sticky
+    /// `R` and `Z` values
+    drill_start: Option<f64>,
+    drill_r: Option<f64>,
+    drill_z: Option<f64>,
+    tool: Option<u32>,
+    next_tool: Option<u32>,
+}
+
+impl Reader {
+    fn push(&mut self, kind: Kind, end: Point, center: Option<Point>) {
+        if end != self.position || center.is_some() {
+            self.program.moves.push(Move {
+                kind,
+                start: self.position,
+                end,
+                center,
+                plane: self.plane,
+                tool: self.tool,
+            });
+        }
+        self.position = end;
+    }
+
+    /// The target of a move, from its axis words
+    fn target(&self, block: &Block) -> Point {
+        let mut out = self.position;
+        for (i, axis) in ['X', 'Y', 'Z'].into_iter().enumerate() {
+            if let Some(v) = block.get(axis) {
+                out[i] = if self.absolute {
+                    v * self.scale
+                } else {
+                    out[i] + v * self.scale
+                };
+            }
+        }
+        out
+    }
+
+    fn arc(&mut self, kind: Kind, block: &Block) -> Result<(), String> {
+        let end = self.target(block);
+        let [iu, iv, _] = self.plane.axes();
+        let center = if let Some(r) = block.get('R') {
+            // the center is on the perpendicular bisector of the chord, to
+            // the left of it for a short counter-clockwise arc; a negative
+            // radius asks for the long way round
+            let r = r * self.scale;
+            let (s, e) = (self.position, end);
+            let (du, dv) = (e[iu] - s[iu], e[iv] - s[iv]);
+            let chord = du.hypot(dv);
+            if chord == 0.0 {
+                return Err("arcs with R can't be full circles".to_owned());
+            }
+            if chord > 2.0 * r.abs() * (1.0 + 1e-6) {
+                return Err(format!("arc radius {} is too small", r.abs()));
+            }
+            let h = (r * r - chord * chord / 4.0).max(0.0).sqrt();
+            let left = (kind == Kind::CounterClockwise) == (r > 0.0);
+            let h = if left { h } else { -h };
+            let mut c = (s + e) / 2.0;
+            c[iu] -= dv / chord * h;
+            c[iv] += du / chord * h;
+            c
+        } else {
+            let offsets = [(iu, ['I', 'J', 'K'][iu]), (iv, ['I', 'J', 'K'][iv])];
+            if offsets.iter().all(|(_, w)| block.get(*w).is_none()) {
+                return Err("arcs need I, J or K words, or R".to_owned());
+            }
+            let mut c = self.position;
+            for (i, word) in offsets {
+                let v = block.get(word).unwrap_or(0.0) * self.scale;
+                c[i] = if self.absolute_centers {
+                    v
+                } else {
+                    self.position[i] + v
+                };
+            }
+            c
+        };
+        self.push(kind, end, Some(center));
+        Ok(())
+    }
+
+    fn drill(&mut self, block: &Block) -> Result<(), String> {
+        let start = *self.drill_start.get_or_insert(self.position.z);
+        let z0 = self.position.z;
+        // in incremental mode, R is relative to the current height and Z to
+        // the R plane
+        if let Some(r) = block.get('R') {
+            let r = r * self.scale;
+            self.drill_r = Some(if self.absolute { r } else { z0 + r });
+        }
+        let r = self.drill_r.ok_or("drilling cycles need an R word")?;
+        if let Some(z) = block.get('Z') {
+            let z = z * self.scale;
+            self.drill_z = Some(if self.absolute { z } else { r + z });
+        }
+        let bottom = self.drill_z.ok_or("drilling cycles need a Z word")?;
+        let mut hole = self.target(block);
+        if z0 < r {
+            let up = Point::new(self.position.x, self.position.y, r);
+            self.push(Kind::Rapid, up, None);
+        }
+        hole.z = self.position.z;
+        self.push(Kind::Rapid, hole, None);
+        hole.z = r;
+        self.push(Kind::Rapid, hole, None);
+        hole.z = bottom;
+        self.push(Kind::Linear, hole, None);
+        hole.z = if self.retract_to_start {
+            start.max(r)
+        } else {
+            r
+        };
+        self.push(Kind::Rapid, hole, None);
+        Ok(())
+    }
+
+    /// Handles one block, returning `false` at the end of the program
+    fn block(&mut self, block: &Block) -> Result<bool, String> {
+        if let Some((number, tool)) = tool_entry(block) {
+            self.program.tools.insert(number, tool);
+            return Ok(true);
+        }
+        for comment in &block.comments {
+            if let Ok(inner) = split(comment) {
+                if let Some((number, tool)) = tool_entry(&inner) {
+                    self.program.tools.insert(number, tool);
+                }
+            }
+        }
+
+        let mut moves_axes = true;
+        for g in block.codes('G') {
+            match code(g) {
+                0 => self.motion = Some(Motion::Move(Kind::Rapid)),
+                10 => self.motion = Some(Motion::Move(Kind::Linear)),
+                20 => self.motion = Some(Motion::Move(Kind::Clockwise)),
+                30 => self.motion = Some(Motion::Move(Kind::CounterClockwise)),
+                730 | 810 | 820 | 830 => self.motion = Some(Motion::Drill),
+                800 => self.motion = None,
+                170 => self.plane = Plane::XY,
+                180 => self.plane = Plane::ZX,
+                190 => self.plane = Plane::YZ,
+                200 | 210 => (),
+                900 => self.absolute = true,
+                910 => self.absolute = false,
+                901 => self.absolute_centers = true,
+                911 => self.absolute_centers = false,
+                980 => self.retract_to_start = true,
+                990 => self.retract_to_start = false,
+                // axis words that don't describe a cutting move
+                280 | 300 | 530 | 920 => moves_axes = false,
+                _ => (),
+            }
+        }
+        if self.motion != Some(Motion::Drill) {
+            self.drill_start = None;
+        }
+        if let Some(t) = block.get('T') {
+            self.next_tool = Some(t as u32);
+        }
+        for m in block.codes('M') {
+            match code(m) {
+                60 => self.tool = self.next_tool,
+                20 | 300 => return Ok(false),
+                _ => (),
+            }
+        }
+
+        if !moves_axes {
+            return Ok(true);
+        }
+        match self.motion {
+            // arcs can be full circles, given just their center
+            Some(Motion::Move(kind)) if kind.is_arc() && block.has("XYZIJKR") => {
+                self.arc(kind, block)?
+            }
+            Some(Motion::Move(kind)) if !kind.is_arc() && block.has("XYZ") => {
+                let end = self.target(block);
+                self.push(kind, end, None);
+            }
+            Some(Motion::Drill) if block.has("XYZR") => self.drill(block)?,
+            _ => (),
+        }
+        Ok(true)
+    }
+}
+
+/// Reads a program, in the given units or those that it selects first
+pub fn parse(text: &str, units: Option<Units>) -> Result<Program, String> {
+    let blocks = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| split(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let program_units = |block: &Block| {
+        block.codes('G').find_map(|g| match code(g) {
+            200 => Some(Units::Inches),
+            210 => Some(Units::Millimeters),
+            _ => None,
+        })
+    };
+    let first = blocks
+        .iter()
+        .find_map(program_units)
+        .unwrap_or(Units::Millimeters);
+    let output = units.unwrap_or(first);
+
+    // until the program selects its units, they're the ones it selects first
+    let scale = first.millimeters() / output.millimeters();
+    let mut reader = Reader {
+        program: Program::default(),
+        scale,
+        position: Point::zeros(),
+        absolute: true,
+        absolute_centers: false,
+        plane: Plane::XY,
+        motion: None,
+        retract_to_start: true,
+        drill_start: None,
+        drill_r: None,
+        drill_z: None,
+        tool: None,
+        next_tool: None,
+    };
+    for (i, block) in blocks.iter().enumerate() {
+        // units apply to the whole block they're in
+        if let Some(u) = program_units(block) {
+            reader.scale = u.millimeters() / output.millimeters();
+        }
+        match reader.block(block) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => return Err(format!("line {}: {e}", i + 1)),
+        }
+    }
+    for tool in reader.program.tools.values_mut() {
+        tool.diameter = tool.diameter.map(|d| d * scale);
+        tool.length = tool.length.map(|z| z * scale);
+    }
+    Ok(reader.program)
+}
+
+#[pyfunction(name = "parse")]
+#[pyo3(signature = (text, units=None))]
+fn py_parse<'py>(
+    py: Python<'py>,
+    text: &str,
+    units: Option<&str>,
+) -> PyResult<(Vec<PyMove>, Bound<'py, PyDict>)> {
+    let program = match units.map(Units::parse).transpose() {
+        Ok(units) => parse(text, units),
+        Err(e) => Err(e),
+    };
+    let program = match program {
+        Ok(p) => p,
+        Err(e) => return Err(PyRuntimeError::new_err(e)),
+    };
+    let tools = PyDict::new(py);
+    for (number, tool) in program.tools {
+        let entry = PyDict::new(py);
+        entry.set_item("diameter", tool.diameter)?;
+        entry.set_item("length", tool.length)?;
+        entry.set_item("comment", tool.comment)?;
+        tools.set_item(number, entry)?;
+    }
+    let moves = program
+        .moves
+        .into_iter()
+        .map(|m| PyMove { _val: m })
+        .collect();
+    Ok((moves, tools))
+}
+
+/// Adds the `gcode` submodule to `m`
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let sub = PyModule::new(m.py(), "gcode")?;
+    sub.add_function(wrap_pyfunction!(py_parse, &sub)?)?;
+    m.add_submodule(&sub)
+}
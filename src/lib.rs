@@ -1,6 +1,6 @@
 use fidget::{
-    compiler::{SsaOp, SsaTape},
-    context::{Context, Tree, TreeOp},
+    compiler::SsaTape,
+    context::{Context, Node, Tree, TreeOp},
     mesh::{Mesh, Settings},
     render::View3,
     var::Var,
@@ -9,19 +9,273 @@ use nalgebra::base::Vector3;
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
 use pyo3::{exceptions::PyRuntimeError, types::PyDict, IntoPyObjectExt};
-use std::{cmp::Ordering, collections::HashMap};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+mod blend;
+mod compiled;
+mod fold;
+mod gcode;
+mod meshio;
+mod multi;
+mod offset;
+mod repeat;
+mod serial;
+mod shapes;
+mod simplify;
+mod stats;
+mod structure;
+mod sweep;
+mod toolpath;
+mod transform;
+mod vars;
+mod vdb;
+mod vm;
+mod volume;
 
 pyo3::create_exception!(_core, FidgetError, PyException);
 
 #[derive(Clone)]
-#[pyclass(name = "Tree")]
+#[pyclass(name = "Tree", module = "fidgetpy._core")]
 struct PyTree {
     _val: Tree,
+    // trees are immutable, so statistics, the context size used by `len()`
+    // and the structural hash are computed at most once
+    stats: Arc<OnceLock<stats::Stats>>,
+    len: Arc<OnceLock<usize>>,
+    hash: Arc<OnceLock<u64>>,
+    // whether the tree is known to be an exact distance field; see `offset`
+    exact: bool,
+}
+
+/// Arguments for `Tree.from_bytes` when unpickling
+type Reduced<'py> = (Bound<'py, PyAny>, (Vec<u8>, bool));
+
+impl From<Tree> for PyTree {
+    fn from(t: Tree) -> Self {
+        PyTree {
+            _val: t,
+            stats: Default::default(),
+            len: Default::default(),
+            hash: Default::default(),
+            exact: false,
+        }
+    }
+}
+
+/// Iterator over the distinct nodes of a tree, children before parents
+#[pyclass(name = "TreeWalk", module = "fidgetpy._core")]
+struct PyTreeWalk {
+    nodes: std::vec::IntoIter<Tree>,
+}
+
+#[pymethods]
+impl PyTreeWalk {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self) -> Option<PyTree> {
+        self.nodes.next().map(PyTree::from)
+    }
+}
+
+/// Evaluator backends available in this build, with the default first
+const BACKENDS: &[&str] = &[
+    #[cfg(feature = "jit")]
+    "jit",
+    "vm",
+];
+
+/// Checks a backend name from Python, returning the default if it's `None`
+fn backend_name(backend: Option<&str>) -> PyResult<&'static str> {
+    let Some(name) = backend else {
+        return Ok(BACKENDS[0]);
+    };
+    match BACKENDS.iter().find(|b| **b == name) {
+        Some(b) => Ok(b),
+        None if name == "jit" => Err(PyRuntimeError::new_err(
+            "the 'jit' backend is not available in this build",
+        )),
+        None => Err(PyRuntimeError::new_err(format!(
+            "unknown backend '{name}', expected one of {BACKENDS:?}"
+        ))),
+    }
+}
+
+/// Builds a shape for a backend returned by [`backend_name`], then runs an
+/// expression with it
+macro_rules! with_shape {
+    ($backend:expr, $ctx:expr, $root:expr, $shape:ident => $e:expr) => {
+        match $backend {
+            #[cfg(feature = "jit")]
+            "jit" => match fidget::jit::JitShape::new($ctx, $root) {
+                Ok($shape) => $e,
+                Err(e) => return Err(FidgetError::new_err(e.to_string())),
+            },
+            "vm" => match fidget::vm::VmShape::new($ctx, $root) {
+                Ok($shape) => $e,
+                Err(e) => return Err(FidgetError::new_err(e.to_string())),
+            },
+            b => unreachable!("unchecked backend '{b}'"),
+        }
+    };
+}
+
+enum Backend {
+    #[cfg(feature = "jit")]
+    Jit(compiled::Compiled<fidget::jit::JitFunction>),
+    Vm(compiled::Compiled<fidget::vm::VmFunction>),
+}
+
+/// Runs the same expression on whichever backend a shape was compiled for
+macro_rules! with_backend {
+    ($backend:expr, $c:ident => $e:expr) => {
+        match $backend {
+            #[cfg(feature = "jit")]
+            Backend::Jit($c) => $e,
+            Backend::Vm($c) => $e,
+        }
+    };
+}
+
+#[pyclass(name = "CompiledShape", module = "fidgetpy._core")]
+struct PyCompiledShape {
+    backend: Backend,
+}
+
+#[pymethods]
+impl PyCompiledShape {
+    #[getter]
+    fn backend(&self) -> &'static str {
+        match self.backend {
+            #[cfg(feature = "jit")]
+            Backend::Jit(..) => "jit",
+            Backend::Vm(..) => "vm",
+        }
+    }
+    fn eval(&mut self, x: f32, y: f32, z: f32) -> PyResult<f32> {
+        match with_backend!(&mut self.backend, c => c.eval(x, y, z)) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+    fn eval_many(&mut self, xs: Vec<f32>, ys: Vec<f32>, zs: Vec<f32>) -> PyResult<Vec<f32>> {
+        match with_backend!(&mut self.backend, c => c.eval_many(&xs, &ys, &zs)) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+    fn eval_interval(
+        &mut self,
+        x: (f32, f32),
+        y: (f32, f32),
+        z: (f32, f32),
+    ) -> PyResult<(f32, f32)> {
+        if x.0 > x.1 || y.0 > y.1 || z.0 > z.1 {
+            return Err(PyRuntimeError::new_err(
+                "interval lower bounds must not exceed upper bounds",
+            ));
+        }
+        match with_backend!(&mut self.backend, c => c.eval_interval(x, y, z)) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+    fn mesh(&self, depth: u8, cx: f32, cy: f32, cz: f32, region_size: f32) -> PyMesh {
+        let mesh = with_backend!(&self.backend, c => c.mesh(depth, [cx, cy, cz], region_size));
+        PyMesh::from(mesh)
+    }
+    #[pyo3(signature = (size, cx=0.0, cy=0.0, scale=1.0))]
+    fn render_2d(&self, size: u32, cx: f32, cy: f32, scale: f32) -> PyResult<Vec<u8>> {
+        match with_backend!(&self.backend, c => c.render_2d(size, [cx, cy], scale)) {
+            Some(v) => Ok(v),
+            None => Err(FidgetError::new_err("rendering was cancelled")),
+        }
+    }
+    #[pyo3(signature = (size, cx=0.0, cy=0.0, cz=0.0, scale=1.0))]
+    fn render_3d(&self, size: u32, cx: f32, cy: f32, cz: f32, scale: f32) -> PyResult<Vec<u32>> {
+        match with_backend!(&self.backend, c => c.render_3d(size, [cx, cy, cz], scale)) {
+            Some(v) => Ok(v),
+            None => Err(FidgetError::new_err("rendering was cancelled")),
+        }
+    }
 }
 
 #[pyclass(name = "Mesh")]
 struct PyMesh {
     _val: Mesh,
+    attributes: meshio::Attributes,
+    materials: Option<multi::Materials>,
+}
+
+impl From<Mesh> for PyMesh {
+    fn from(m: Mesh) -> Self {
+        PyMesh {
+            _val: m,
+            attributes: vec![],
+            materials: None,
+        }
+    }
+}
+
+impl PyMesh {
+    /// Looks up the colors for an attribute, if one is named
+    fn colors(&self, color: Option<&str>) -> PyResult<Option<Vec<[u8; 3]>>> {
+        let Some(name) = color else {
+            return Ok(None);
+        };
+        match self.attributes.iter().find(|(n, _)| n == name) {
+            Some((_, values)) => Ok(Some(meshio::colors(values))),
+            None => Err(PyRuntimeError::new_err(format!(
+                "mesh has no attribute named '{name}'"
+            ))),
+        }
+    }
+    /// Splits the mesh into one mesh per material, keeping only the vertices
+    /// (and attribute values) used by each one
+    fn split(&self) -> Vec<PyMesh> {
+        let Some(materials) = &self.materials else {
+            let mut out = PyMesh::from(Mesh {
+                vertices: self._val.vertices.clone(),
+                triangles: self._val.triangles.clone(),
+            });
+            out.attributes = self.attributes.clone();
+            return vec![out];
+        };
+        (0..materials.count)
+            .map(|m| {
+                let mut remap = vec![usize::MAX; self._val.vertices.len()];
+                let mut used = vec![];
+                let mut triangles = vec![];
+                for (t, id) in self._val.triangles.iter().zip(&materials.ids) {
+                    if *id as usize != m {
+                        continue;
+                    }
+                    triangles.push(t.map(|v| {
+                        if remap[v] == usize::MAX {
+                            remap[v] = used.len();
+                            used.push(v);
+                        }
+                        remap[v]
+                    }));
+                }
+                let vertices = used.iter().map(|&v| self._val.vertices[v]).collect();
+                let mut out = PyMesh::from(Mesh {
+                    vertices,
+                    triangles,
+                });
+                out.attributes = self
+                    .attributes
+                    .iter()
+                    .map(|(name, values)| (name.clone(), used.iter().map(|&v| values[v]).collect()))
+                    .collect();
+                out
+            })
+            .collect()
+    }
 }
 
 #[pymethods]
@@ -44,6 +298,65 @@ impl PyMesh {
         }
         vec
     }
+    #[getter]
+    fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        // keep the order that attributes were requested in
+        let out = PyDict::new(py);
+        for (name, values) in &self.attributes {
+            out.set_item(name, values)?;
+        }
+        Ok(out)
+    }
+    #[getter]
+    fn materials(&self) -> Option<Vec<u32>> {
+        self.materials.as_ref().map(|m| m.ids.clone())
+    }
+    fn split_materials(&self) -> Vec<PyMesh> {
+        self.split()
+    }
+    #[pyo3(signature = (names=None))]
+    fn to_3mf(&self, names: Option<Vec<String>>) -> PyResult<Vec<u8>> {
+        let meshes = self.split();
+        let names = match names {
+            Some(n) if n.len() != meshes.len() => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "expected {} material names, got {}",
+                    meshes.len(),
+                    n.len()
+                )))
+            }
+            Some(n) => n,
+            None => (0..meshes.len()).map(|i| format!("material {i}")).collect(),
+        };
+        let materials: Vec<(String, Mesh)> = names
+            .into_iter()
+            .zip(meshes.into_iter().map(|m| m._val))
+            .collect();
+        Ok(meshio::to_3mf(&materials))
+    }
+    #[pyo3(signature = (color=None))]
+    fn to_ply(&self, color: Option<&str>) -> PyResult<Vec<u8>> {
+        let colors = self.colors(color)?;
+        Ok(meshio::to_ply(
+            &self._val,
+            &self.attributes,
+            colors.as_deref(),
+        ))
+    }
+    #[pyo3(signature = (color=None))]
+    fn to_obj(&self, color: Option<&str>) -> PyResult<String> {
+        let colors = self.colors(color)?;
+        Ok(meshio::to_obj(&self._val, colors.as_deref()))
+    }
+    #[pyo3(signature = (color=None))]
+    fn to_glb(&self, color: Option<&str>) -> PyResult<Vec<u8>> {
+        let colors = self.colors(color)?;
+        Ok(meshio::to_glb(
+            &self._val,
+            &self.attributes,
+            colors.as_deref(),
+        ))
+    }
     fn to_stl(&self) -> Vec<u8> {
         let mut out = std::vec::Vec::new();
         const HEADER: &[u8] = b"This is a binary STL file exported by Fidget";
@@ -72,6 +385,185 @@ impl PyMesh {
     }
 }
 
+impl PyTree {
+    fn cached_stats(&self) -> PyResult<&stats::Stats> {
+        if let Some(s) = self.stats.get() {
+            return Ok(s);
+        }
+        let s = match stats::Stats::new(&self._val) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        Ok(self.stats.get_or_init(|| s))
+    }
+    /// Returns the size of a context holding the imported tree
+    ///
+    /// This only needs an import, so it doesn't compute the full statistics
+    /// (which lower the tree to tapes), but reuses them if they're cached.
+    fn context_len(&self) -> usize {
+        if let Some(s) = self.stats.get() {
+            return s.context_len;
+        }
+        *self.len.get_or_init(|| {
+            let mut ctx = Context::new();
+            ctx.import(&self._val);
+            ctx.len()
+        })
+    }
+    /// Reads a `{variable: value}` dict, keyed by trees or variable names
+    fn var_values(vars: &Bound<PyDict>) -> PyResult<HashMap<Var, f64>> {
+        let mut varmap: HashMap<Var, f64> = HashMap::new();
+        for (key, value) in vars.iter() {
+            let this_var = if let Ok(name) = key.extract::<String>() {
+                if !vars::is_valid(&name) {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "invalid variable name '{name}'"
+                    )));
+                }
+                // a name that was never registered isn't used by any tree
+                match vars::find(&name) {
+                    Some(v) => v,
+                    None => continue,
+                }
+            } else {
+                let key_tree: PyTree = match key.extract() {
+                    Ok(k) => k,
+                    Err(..) => {
+                        return Err(FidgetError::new_err(
+                            "Could not recover useable Tree Operation from value map",
+                        ))
+                    }
+                };
+                match key_tree._val.to_owned().var() {
+                    Some(v) => v,
+                    None => {
+                        return Err(FidgetError::new_err(
+                            "Could not recover useable Tree Operation from value map",
+                        ))
+                    }
+                }
+            };
+
+            let val_float: f64 = match value.extract() {
+                Ok(v) => v,
+                Err(..) => {
+                    return Err(FidgetError::new_err(
+                        "Could not recover useable Tree Operation from value map",
+                    ))
+                }
+            };
+            varmap.insert(this_var, val_float);
+        }
+        Ok(varmap)
+    }
+    /// Imports this tree into a new context, replacing any variables given
+    /// in `vars` with constants
+    fn context(&self, vars: Option<&Bound<PyDict>>) -> PyResult<(Context, Node)> {
+        let mut ctx = Context::new();
+        let root = ctx.import(&self._val);
+        let Some(vars) = vars else {
+            return Ok((ctx, root));
+        };
+        let values = Self::var_values(vars)?;
+        let mut bound = Context::new();
+        match fold::bind(&ctx, root, &mut bound, &values) {
+            Ok(root) => Ok((bound, root)),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+    fn transformed(&self, m: &nalgebra::Matrix4<f64>) -> PyResult<PyTree> {
+        match transform::apply(&self._val, m) {
+            Ok(t) => Ok(PyTree::with_exactness(
+                t,
+                self.exact && transform::similarity_scale(m).is_some(),
+            )),
+            Err(e) => Err(PyRuntimeError::new_err(e)),
+        }
+    }
+    fn from_offset(r: Result<(Tree, bool), String>) -> PyResult<PyTree> {
+        match r {
+            Ok((t, exact)) => Ok(PyTree::with_exactness(t, exact)),
+            Err(e) => Err(PyRuntimeError::new_err(e)),
+        }
+    }
+    fn with_exactness(t: Tree, exact: bool) -> PyTree {
+        PyTree {
+            exact,
+            ..PyTree::from(t)
+        }
+    }
+    fn from_result(r: Result<Tree, String>) -> PyResult<PyTree> {
+        match r {
+            Ok(t) => Ok(PyTree::from(t)),
+            Err(e) => Err(PyRuntimeError::new_err(e)),
+        }
+    }
+    fn simplified(&self) -> PyResult<Tree> {
+        let mut ctx = Context::new();
+        let root = ctx.import(&self._val);
+        let mut simplified = Context::new();
+        let root = match simplify::simplify(&ctx, root, &mut simplified) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        match simplified.export(root) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+    /// Checks that no variable matching `check` is left in the tree
+    fn check_bound(ctx: &Context, root: Node, check: impl Fn(Var) -> bool) -> PyResult<()> {
+        let nodes = match fold::nodes(ctx, root) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        match nodes
+            .into_iter()
+            .filter_map(|n| ctx.get_var(n).ok())
+            .find(|v| check(*v))
+        {
+            Some(v) => Err(FidgetError::new_err(format!(
+                "variable '{}' has no value",
+                vars::describe(v)
+            ))),
+            None => Ok(()),
+        }
+    }
+    /// Compiles a tree for a backend returned by [`backend_name`]
+    fn compile_context(ctx: &Context, root: Node, backend: &str) -> PyResult<PyCompiledShape> {
+        let backend = match backend {
+            #[cfg(feature = "jit")]
+            "jit" => compiled::Compiled::new(ctx, root).map(Backend::Jit),
+            "vm" => compiled::Compiled::new(ctx, root).map(Backend::Vm),
+            b => unreachable!("unchecked backend '{b}'"),
+        };
+        match backend {
+            Ok(backend) => Ok(PyCompiledShape { backend }),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+    fn sample_volume(
+        &self,
+        bounds: (f32, f32, f32, f32, f32, f32),
+        resolution: (usize, usize, usize),
+        vars: Option<&Bound<PyDict>>,
+        backend: Option<&str>,
+    ) -> PyResult<volume::Volume> {
+        if let Err(e) = volume::Volume::check(bounds, resolution) {
+            return Err(PyRuntimeError::new_err(e));
+        }
+        let backend = backend_name(backend)?;
+        let (ctx, root) = self.context(vars)?;
+        let vol = with_shape!(backend, &ctx, root, shape => {
+            volume::Volume::sample(&shape, bounds, resolution)
+        });
+        match vol {
+            Ok(v) => Ok(v),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
+    }
+}
+
 #[pymethods]
 impl PyTree {
     // print to graphviz for debugging
@@ -80,7 +572,13 @@ impl PyTree {
         ctx.import(&self._val);
         ctx.dot()
     }
-    fn eval(&self, x: f64, y: f64, z: f64) -> PyResult<f64> {
+    #[pyo3(signature = (x, y, z, backend=None))]
+    fn eval(&self, x: f64, y: f64, z: f64, backend: Option<&str>) -> PyResult<f64> {
+        if backend.is_some() {
+            // compiled for one of the evaluator backends, in single precision
+            let mut shape = self.compile(backend, None)?;
+            return Ok(shape.eval(x as f32, y as f32, z as f32)? as f64);
+        }
         // slow point-wise evaluation
         // useful for debugging and testing
         let mut ctx = Context::new();
@@ -90,333 +588,459 @@ impl PyTree {
             Err(e) => Err(FidgetError::new_err(e.to_string())),
         }
     }
-    fn eval_map(&self, vars: Bound<PyDict>) -> PyResult<f64> {
+    #[pyo3(signature = (vars, backend=None))]
+    fn eval_map(&self, vars: Bound<PyDict>, backend: Option<&str>) -> PyResult<f64> {
+        if backend.is_some() {
+            // binding every variable, including the axes, leaves a constant
+            // that can be evaluated anywhere
+            let backend = backend_name(backend)?;
+            let (ctx, root) = self.context(Some(&vars))?;
+            Self::check_bound(&ctx, root, |_| true)?;
+            let mut shape = Self::compile_context(&ctx, root, backend)?;
+            return Ok(shape.eval(0.0, 0.0, 0.0)? as f64);
+        }
         let mut ctx = Context::new();
         let root = ctx.import(&self._val);
-        let mut varmap: HashMap<Var, f64> = HashMap::new();
-        for (key, value) in vars.into_iter() {
-            let key_tree: PyTree = match key.extract() {
-                Ok(k) => k,
-                Err(..) => {
-                    return Err(FidgetError::new_err(
-                        "Could not recover useable Tree Operation from value map",
-                    ))
-                }
-            };
-
-            let val_float: f64 = match value.extract() {
-                Ok(v) => v,
-                Err(..) => {
-                    return Err(FidgetError::new_err(
-                        "Could not recover useable Tree Operation from value map",
-                    ))
-                }
-            };
-
-            let this_var = match key_tree._val.to_owned().var() {
-                Some(v) => v,
-                None => {
-                    return Err(FidgetError::new_err(
-                        "Could not recover useable Tree Operation from value map",
-                    ))
-                }
-            };
-            varmap.insert(this_var, val_float);
-        }
+        let varmap = Self::var_values(&vars)?;
         match ctx.eval(root, &varmap) {
             Ok(v) => Ok(v),
             Err(e) => Err(FidgetError::new_err(e.to_string())),
         }
     }
-    #[staticmethod]
-    fn from_vm(src: &str) -> PyResult<Self> {
-        let (ctx, root) = match Context::from_text(src.as_bytes()) {
-            Ok(t) => t,
+    fn bind(&self, vars: Bound<PyDict>) -> PyResult<Self> {
+        // rebuilding through the context builders folds everything that
+        // became constant, and simplifying then cleans up after it (e.g. the
+        // `a * -1` left behind by binding a sign to -1). Neither changes the
+        // field's values, so an exact field stays exact.
+        let (ctx, root) = self.context(Some(&vars))?;
+        let mut simplified = Context::new();
+        let root = match simplify::simplify(&ctx, root, &mut simplified) {
+            Ok(v) => v,
             Err(e) => return Err(FidgetError::new_err(e.to_string())),
         };
-        Ok(PyTree {
-            _val: match ctx.export(root) {
-                Ok(v) => v,
-                Err(e) => return Err(FidgetError::new_err(e.to_string())),
-            },
-        })
+        match simplified.export(root) {
+            Ok(t) => Ok(PyTree::with_exactness(t, self.exact)),
+            Err(e) => Err(FidgetError::new_err(e.to_string())),
+        }
     }
-    fn to_vm(&self) -> PyResult<String> {
+    fn substitute(&self, mapping: Bound<PyDict>) -> PyResult<Self> {
         let mut ctx = Context::new();
         let root = ctx.import(&self._val);
-        let (ssatape, varmap) = match SsaTape::new(&ctx, &[root]) {
-            Ok(x) => x,
-            Err(e) => return Err(FidgetError::new_err(e.to_string())),
-        };
-        let mut result = String::new();
-        let mut addr: u32 = 0;
-        let mut regmap = HashMap::<u32, u32>::new();
-        let mut constmap = HashMap::<[u8; 4], u32>::new();
-        let mut axismap = HashMap::new();
-        if let Some(x) = varmap.get(&fidget::var::Var::X) {
-            axismap.insert(x, "var-x");
-        }
-        if let Some(y) = varmap.get(&fidget::var::Var::Y) {
-            axismap.insert(y, "var-y");
-        }
-        if let Some(z) = varmap.get(&fidget::var::Var::Z) {
-            axismap.insert(z, "var-z");
-        }
-        for &op in ssatape.tape.iter().rev() {
-            match op {
-                SsaOp::Output(..) => {}
-                SsaOp::Input(out, i) => {
-                    let varname = if !axismap.contains_key(&(i as usize)) {
-                        return Err(FidgetError::new_err(format!(
-                            "Error while building SSA tape {i}"
-                        )));
-                    } else {
-                        match axismap.get(&(i as usize)) {
-                            Some(x) => x,
-                            None => unreachable!(),
-                        }
-                    };
-                    result.push_str(&format!("${addr} {varname}\n"));
-                    regmap.insert(out, addr);
-                    addr += 1;
-                }
-                SsaOp::NegReg(out, arg)
-                | SsaOp::AbsReg(out, arg)
-                | SsaOp::RecipReg(out, arg)
-                | SsaOp::SqrtReg(out, arg)
-                | SsaOp::CopyReg(out, arg)
-                | SsaOp::SquareReg(out, arg)
-                | SsaOp::FloorReg(out, arg)
-                | SsaOp::CeilReg(out, arg)
-                | SsaOp::RoundReg(out, arg)
-                | SsaOp::SinReg(out, arg)
-                | SsaOp::CosReg(out, arg)
-                | SsaOp::TanReg(out, arg)
-                | SsaOp::AsinReg(out, arg)
-                | SsaOp::AcosReg(out, arg)
-                | SsaOp::AtanReg(out, arg)
-                | SsaOp::ExpReg(out, arg)
-                | SsaOp::LnReg(out, arg)
-                | SsaOp::NotReg(out, arg) => {
-                    let op = match op {
-                        SsaOp::NegReg(..) => "neg",
-                        SsaOp::AbsReg(..) => "abs",
-                        SsaOp::RecipReg(..) => "recip",
-                        SsaOp::SqrtReg(..) => "sqrt",
-                        SsaOp::SquareReg(..) => "square",
-                        SsaOp::FloorReg(..) => "floor",
-                        SsaOp::CeilReg(..) => "ceil",
-                        SsaOp::RoundReg(..) => "round",
-                        SsaOp::SinReg(..) => "sin",
-                        SsaOp::CosReg(..) => "cos",
-                        SsaOp::TanReg(..) => "tan",
-                        SsaOp::AsinReg(..) => "asin",
-                        SsaOp::AcosReg(..) => "acos",
-                        SsaOp::AtanReg(..) => "atan",
-                        SsaOp::ExpReg(..) => "exp",
-                        SsaOp::LnReg(..) => "ln",
-                        SsaOp::NotReg(..) => "not",
-                        SsaOp::CopyReg(..) => "copy",
-                        _ => unreachable!(),
-                    };
-                    let arg_addr = match regmap.get(&arg) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    result.push_str(&format!("${addr} {op} ${arg_addr}\n"));
-                    regmap.insert(out, addr);
-                    addr += 1;
+        let mut replace = HashMap::new();
+        for (key, value) in mapping.iter() {
+            let from = if let Ok(name) = key.extract::<String>() {
+                if !vars::is_valid(&name) {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "invalid variable name '{name}'"
+                    )));
                 }
-
-                SsaOp::AddRegReg(out, lhs, rhs)
-                | SsaOp::MulRegReg(out, lhs, rhs)
-                | SsaOp::DivRegReg(out, lhs, rhs)
-                | SsaOp::SubRegReg(out, lhs, rhs)
-                | SsaOp::MinRegReg(out, lhs, rhs)
-                | SsaOp::MaxRegReg(out, lhs, rhs)
-                | SsaOp::ModRegReg(out, lhs, rhs)
-                | SsaOp::AndRegReg(out, lhs, rhs)
-                | SsaOp::AtanRegReg(out, lhs, rhs)
-                | SsaOp::OrRegReg(out, lhs, rhs) => {
-                    let op = match op {
-                        SsaOp::AddRegReg(..) => "add",
-                        SsaOp::MulRegReg(..) => "mul",
-                        SsaOp::DivRegReg(..) => "div",
-                        SsaOp::AtanRegReg(..) => "atan",
-                        SsaOp::SubRegReg(..) => "sub",
-                        SsaOp::MinRegReg(..) => "min",
-                        SsaOp::MaxRegReg(..) => "max",
-                        SsaOp::ModRegReg(..) => "max",
-                        SsaOp::AndRegReg(..) => "and",
-                        SsaOp::OrRegReg(..) => "or",
-                        _ => unreachable!(),
-                    };
-                    let lhs_addr = match regmap.get(&lhs) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    let rhs_addr = match regmap.get(&rhs) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    result.push_str(&format!("${addr} {op} ${lhs_addr} ${rhs_addr}\n"));
-                    regmap.insert(out, addr);
-                    addr += 1;
+                // an unregistered name can't appear in this tree
+                match vars::find(&name) {
+                    Some(v) => ctx.var(v),
+                    None => continue,
                 }
-
-                SsaOp::AddRegImm(out, arg, imm)
-                | SsaOp::MulRegImm(out, arg, imm)
-                | SsaOp::DivRegImm(out, arg, imm)
-                | SsaOp::DivImmReg(out, arg, imm)
-                | SsaOp::SubImmReg(out, arg, imm)
-                | SsaOp::SubRegImm(out, arg, imm)
-                | SsaOp::AtanRegImm(out, arg, imm)
-                | SsaOp::AtanImmReg(out, arg, imm)
-                | SsaOp::MinRegImm(out, arg, imm)
-                | SsaOp::MaxRegImm(out, arg, imm)
-                | SsaOp::ModRegImm(out, arg, imm)
-                | SsaOp::ModImmReg(out, arg, imm)
-                | SsaOp::AndRegImm(out, arg, imm)
-                | SsaOp::OrRegImm(out, arg, imm) => {
-                    let (op, swap) = match op {
-                        SsaOp::AddRegImm(..) => ("add", false),
-                        SsaOp::MulRegImm(..) => ("mul", false),
-                        SsaOp::DivImmReg(..) => ("div", true),
-                        SsaOp::DivRegImm(..) => ("div", false),
-                        SsaOp::SubImmReg(..) => ("sub", true),
-                        SsaOp::SubRegImm(..) => ("sub", false),
-                        SsaOp::AtanImmReg(..) => ("atan", true),
-                        SsaOp::AtanRegImm(..) => ("atan", false),
-                        SsaOp::MinRegImm(..) => ("min", false),
-                        SsaOp::MaxRegImm(..) => ("max", false),
-                        SsaOp::ModRegImm(..) => ("mod", false),
-                        SsaOp::ModImmReg(..) => ("mod", true),
-                        SsaOp::AndRegImm(..) => ("and", false),
-                        SsaOp::OrRegImm(..) => ("or", false),
-                        _ => unreachable!(),
-                    };
-                    let imm_addr = if let std::collections::hash_map::Entry::Vacant(e) =
-                        constmap.entry(imm.to_ne_bytes())
-                    {
-                        e.insert(addr);
-                        result.push_str(&format!("${addr} const {imm}\n"));
-                        addr += 1;
-                        addr - 1
-                    } else {
-                        *match constmap.get(&imm.to_ne_bytes()) {
-                            Some(x) => x,
-                            None => unreachable!(),
-                        }
-                    };
-                    let arg_addr = match regmap.get(&arg) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    if swap {
-                        result.push_str(&format!("${addr} {op} ${imm_addr} ${arg_addr}\n"));
-                    } else {
-                        result.push_str(&format!("${addr} {op} ${arg_addr} ${imm_addr}\n"));
+            } else {
+                match key.extract::<PyTree>() {
+                    Ok(k) => ctx.import(&k._val),
+                    Err(..) => {
+                        return Err(PyRuntimeError::new_err(
+                            "substitution keys must be trees or variable names",
+                        ))
                     }
-                    regmap.insert(out, addr);
-                    addr += 1;
-                }
-                SsaOp::CompareRegReg(out, lhs, rhs) => {
-                    let lhs_addr = match regmap.get(&lhs) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    let rhs_addr = match regmap.get(&rhs) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    result.push_str(&format!("${addr} compare ${lhs_addr} ${rhs_addr}\n"));
-                    regmap.insert(out, addr);
-                    addr += 1;
                 }
-                SsaOp::CompareRegImm(out, arg, imm) => {
-                    let imm_addr = if let std::collections::hash_map::Entry::Vacant(e) =
-                        constmap.entry(imm.to_ne_bytes())
-                    {
-                        e.insert(addr);
-                        result.push_str(&format!("${addr} const {imm}\n"));
-                        addr += 1;
-                        addr - 1
-                    } else {
-                        *match constmap.get(&imm.to_ne_bytes()) {
-                            Some(x) => x,
-                            None => unreachable!(),
-                        }
-                    };
-                    let arg_addr = match regmap.get(&arg) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    result.push_str(&format!("${addr} compare ${arg_addr} ${imm_addr}\n"));
-                    regmap.insert(out, addr);
-                    addr += 1;
-                }
-                SsaOp::CompareImmReg(out, arg, imm) => {
-                    let imm_addr = if let std::collections::hash_map::Entry::Vacant(e) =
-                        constmap.entry(imm.to_ne_bytes())
-                    {
-                        e.insert(addr);
-                        result.push_str(&format!("${addr} const {imm}\n"));
-                        addr += 1;
-                        addr - 1
-                    } else {
-                        *match constmap.get(&imm.to_ne_bytes()) {
-                            Some(x) => x,
-                            None => unreachable!(),
-                        }
-                    };
-                    let arg_addr = match regmap.get(&arg) {
-                        Some(x) => x,
-                        None => unreachable!(),
-                    };
-                    result.push_str(&format!("${addr} compare ${imm_addr} ${arg_addr}\n"));
-                    regmap.insert(out, addr);
-                    addr += 1;
-                }
-                SsaOp::CopyImm(_out, imm) => {
-                    // result.push_str(&format!("${out} copy {imm}\n"));
-                    if let std::collections::hash_map::Entry::Vacant(e) =
-                        constmap.entry(imm.to_ne_bytes())
-                    {
-                        e.insert(addr);
-                        result.push_str(&format!("${addr} const {imm}\n"));
-                        addr += 1;
+            };
+            let to = if let Ok(t) = value.extract::<PyTree>() {
+                ctx.import(&t._val)
+            } else {
+                match value.extract::<f64>() {
+                    Ok(v) => ctx.constant(v),
+                    Err(..) => {
+                        return Err(PyRuntimeError::new_err(
+                            "substitution values must be trees or numbers",
+                        ))
                     }
                 }
-            }
+            };
+            replace.insert(from, to);
+        }
+        let root = match fold::substitute(&mut ctx, root, &replace) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        Ok(PyTree::from(match ctx.export(root) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        }))
+    }
+    fn simplify(&self) -> PyResult<Self> {
+        // every rewrite preserves the field's values, so exactness is kept
+        Ok(PyTree::with_exactness(self.simplified()?, self.exact))
+    }
+    fn simplify_report(&self) -> PyResult<(usize, usize)> {
+        let before = simplify::tree_size(&self._val);
+        let after = simplify::tree_size(&self.simplified()?);
+        Ok((before, after))
+    }
+    #[pyo3(signature = (backend=None, vars=None))]
+    fn compile(
+        &self,
+        backend: Option<&str>,
+        vars: Option<Bound<PyDict>>,
+    ) -> PyResult<PyCompiledShape> {
+        let backend = backend_name(backend)?;
+        let (ctx, root) = self.context(vars.as_ref())?;
+        // Shapes can only be evaluated at points in space, so every other
+        // variable has to be bound before compiling
+        Self::check_bound(&ctx, root, |v| matches!(v, Var::V(..)))?;
+        Self::compile_context(&ctx, root, backend)
+    }
+    fn transform(&self, matrix: Vec<Vec<f64>>) -> PyResult<PyTree> {
+        match transform::from_rows(&matrix) {
+            Ok(m) => self.transformed(&m),
+            Err(e) => Err(PyRuntimeError::new_err(e)),
         }
-        Ok(result)
     }
-    fn mesh(&self, depth: u8, cx: f32, cy: f32, cz: f32, region_size: f32) -> PyResult<PyMesh> {
+    fn rotate(&self, axis: Bound<PyAny>, angle: f64) -> PyResult<PyTree> {
+        self.transformed(&transform::rotation(&axis, angle)?)
+    }
+    #[pyo3(signature = (sx, sy=None, sz=None))]
+    fn scale(&self, sx: f64, sy: Option<f64>, sz: Option<f64>) -> PyResult<PyTree> {
+        self.transformed(&transform::scaling(sx, sy, sz))
+    }
+    fn mirror(&self, plane: Bound<PyAny>) -> PyResult<PyTree> {
+        self.transformed(&transform::mirroring(&plane)?)
+    }
+    #[pyo3(signature = (other, k, blend="polynomial"))]
+    fn smooth_union(&self, other: PyRef<PyTree>, k: f64, blend: &str) -> PyResult<PyTree> {
+        PyTree::from_result(
+            blend::Blend::parse(blend)
+                .and_then(|b| blend::smooth_union(&self._val, &other._val, k, b)),
+        )
+    }
+    #[pyo3(signature = (other, k, blend="polynomial"))]
+    fn smooth_intersection(&self, other: PyRef<PyTree>, k: f64, blend: &str) -> PyResult<PyTree> {
+        PyTree::from_result(
+            blend::Blend::parse(blend)
+                .and_then(|b| blend::smooth_intersection(&self._val, &other._val, k, b)),
+        )
+    }
+    #[pyo3(signature = (other, k, blend="polynomial"))]
+    fn smooth_difference(&self, other: PyRef<PyTree>, k: f64, blend: &str) -> PyResult<PyTree> {
+        PyTree::from_result(
+            blend::Blend::parse(blend)
+                .and_then(|b| blend::smooth_difference(&self._val, &other._val, k, b)),
+        )
+    }
+    fn chamfer_union(&self, other: PyRef<PyTree>, k: f64) -> PyResult<PyTree> {
+        PyTree::from_result(blend::chamfer_union(&self._val, &other._val, k))
+    }
+    fn chamfer_intersection(&self, other: PyRef<PyTree>, k: f64) -> PyResult<PyTree> {
+        PyTree::from_result(blend::chamfer_intersection(&self._val, &other._val, k))
+    }
+    fn chamfer_difference(&self, other: PyRef<PyTree>, k: f64) -> PyResult<PyTree> {
+        PyTree::from_result(blend::chamfer_difference(&self._val, &other._val, k))
+    }
+    #[pyo3(signature = (spacing, count=None))]
+    fn repeat(&self, spacing: (f64, f64, f64), count: Option<(u32, u32, u32)>) -> PyResult<PyTree> {
+        let spacing = [spacing.0, spacing.1, spacing.2];
+        let count = count.map(|c| [c.0, c.1, c.2]);
+        PyTree::from_result(repeat::linear(&self._val, spacing, count, false))
+    }
+    #[pyo3(signature = (spacing, count=None))]
+    fn mirror_repeat(
+        &self,
+        spacing: (f64, f64, f64),
+        count: Option<(u32, u32, u32)>,
+    ) -> PyResult<PyTree> {
+        let spacing = [spacing.0, spacing.1, spacing.2];
+        let count = count.map(|c| [c.0, c.1, c.2]);
+        PyTree::from_result(repeat::linear(&self._val, spacing, count, true))
+    }
+    #[pyo3(signature = (n, axis="z"))]
+    fn polar_repeat(&self, n: u32, axis: &str) -> PyResult<PyTree> {
+        PyTree::from_result(repeat::polar(&self._val, n, axis))
+    }
+    #[getter]
+    fn exact(&self) -> bool {
+        self.exact
+    }
+    #[pyo3(signature = (d, redistance=false))]
+    fn offset(&self, d: f64, redistance: bool) -> PyResult<PyTree> {
+        PyTree::from_offset(offset::offset(&self._val, self.exact, d, redistance))
+    }
+    #[pyo3(signature = (thickness, redistance=false))]
+    fn shell(&self, thickness: f64, redistance: bool) -> PyResult<PyTree> {
+        PyTree::from_offset(offset::shell(&self._val, thickness, redistance))
+    }
+    #[pyo3(signature = (thickness, redistance=false))]
+    fn thicken_surface(&self, thickness: f64, redistance: bool) -> PyResult<PyTree> {
+        PyTree::from_offset(offset::thicken_surface(
+            &self._val, self.exact, thickness, redistance,
+        ))
+    }
+    fn variables(&self) -> PyResult<Vec<String>> {
         let mut ctx = Context::new();
         let root = ctx.import(&self._val);
-        let shape = match fidget::jit::JitShape::new(&ctx, root) {
+        let nodes = match fold::nodes(&ctx, root) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        let mut names: Vec<String> = nodes
+            .into_iter()
+            .filter_map(|n| ctx.get_var(n).ok())
+            .map(vars::describe)
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+    #[staticmethod]
+    fn from_vm(src: &str) -> PyResult<Self> {
+        let (ctx, root) = match vm::text_to_context(src) {
+            Ok(t) => t,
+            Err(e) => return Err(FidgetError::new_err(e)),
+        };
+        Ok(PyTree::from(match ctx.export(root) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        }))
+    }
+    #[staticmethod]
+    #[pyo3(signature = (data, exact=false))]
+    fn from_bytes(data: &[u8], exact: bool) -> PyResult<Self> {
+        match serial::from_bytes(data) {
+            Ok(v) => Ok(PyTree::with_exactness(v, exact)),
+            Err(e) => Err(FidgetError::new_err(e)),
+        }
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        serial::to_bytes(&self._val)
+    }
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<Reduced<'py>> {
+        let from_bytes = slf.get_type().getattr("from_bytes")?;
+        let tree = slf.borrow();
+        Ok((from_bytes, (tree.to_bytes(), tree.exact)))
+    }
+    fn to_vm(&self) -> PyResult<String> {
+        let mut imported = Context::new();
+        let node = imported.import(&self._val);
+        // replay the graph through the context builders, which collapse nodes
+        // that the SSA compiler can't lower (e.g. `and` of a constant)
+        let mut ctx = Context::new();
+        let root = match fold::rebuild(&imported, node, &mut ctx) {
             Ok(v) => v,
             Err(e) => return Err(FidgetError::new_err(e.to_string())),
         };
+        let (ssatape, varmap) = match SsaTape::new(&ctx, &[root]) {
+            Ok(x) => x,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        let mut inputs = HashMap::new();
+        let mut constants = vec![];
+        let nodes = match fold::nodes(&ctx, root) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        for node in nodes {
+            if let Ok(c) = ctx.get_const(node) {
+                constants.push(c);
+            } else if let Ok(var) = ctx.get_var(node) {
+                if let Some(i) = varmap.get(&var) {
+                    inputs.insert(i as u32, format!("var-{}", vars::name(var)));
+                }
+            }
+        }
+        match vm::tape_to_text(&ssatape, &inputs, &constants) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(FidgetError::new_err(format!(
+                "Error while building SSA tape: {e}"
+            ))),
+        }
+    }
+    #[pyo3(signature = (depth, cx, cy, cz, region_size, vars=None, backend=None, attributes=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn mesh(
+        &self,
+        depth: u8,
+        cx: f32,
+        cy: f32,
+        cz: f32,
+        region_size: f32,
+        vars: Option<Bound<PyDict>>,
+        backend: Option<&str>,
+        attributes: Option<Bound<PyDict>>,
+    ) -> PyResult<PyMesh> {
+        let backend = backend_name(backend)?;
+        let mut fields = vec![];
+        if let Some(attributes) = &attributes {
+            for (name, tree) in attributes.iter() {
+                let name: String = name.extract()?;
+                if !meshio::is_valid_name(&name) {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "invalid attribute name '{name}'"
+                    )));
+                }
+                let tree: PyTree = tree.extract()?;
+                fields.push((name, tree.context(vars.as_ref())?));
+            }
+        }
+        let (ctx, root) = self.context(vars.as_ref())?;
         let settings = Settings {
             depth,
             view: View3::from_center_and_scale(Vector3::new(cx, cy, cz), region_size),
             ..Default::default()
         };
-        let octree = fidget::mesh::Octree::build(&shape, settings);
-        let mesh = octree.walk_dual(settings);
-        Ok(PyMesh { _val: mesh })
+        let mesh = with_shape!(backend, &ctx, root, shape => {
+            fidget::mesh::Octree::build(&shape, settings).walk_dual(settings)
+        });
+        let mut out = PyMesh::from(mesh);
+        for (name, (ctx, root)) in fields {
+            let values = with_shape!(backend, &ctx, root, shape => {
+                meshio::sample(&shape, &out._val.vertices)
+            });
+            match values {
+                Ok(v) => out.attributes.push((name, v)),
+                Err(e) => return Err(FidgetError::new_err(e.to_string())),
+            }
+        }
+        Ok(out)
+    }
+    #[pyo3(signature = (path, voxel_size, half_width, bounds=None, vars=None, backend=None))]
+    fn to_vdb(
+        &self,
+        path: &str,
+        voxel_size: f32,
+        half_width: f32,
+        bounds: Option<(f32, f32, f32, f32, f32, f32)>,
+        vars: Option<Bound<PyDict>>,
+        backend: Option<&str>,
+    ) -> PyResult<usize> {
+        if voxel_size <= 0.0 || half_width <= 0.0 {
+            return Err(PyRuntimeError::new_err(
+                "voxel size and half width must be positive",
+            ));
+        }
+        let backend = backend_name(backend)?;
+        let (ctx, root) = self.context(vars.as_ref())?;
+        let grid = with_shape!(backend, &ctx, root, shape => {
+            vdb::LevelSet::build(&shape, voxel_size, half_width, bounds)
+        });
+        let grid = match grid {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        };
+        std::fs::write(path, grid.to_bytes("surface"))?;
+        Ok(grid.active_voxel_count())
+    }
+    #[pyo3(signature = (path, bounds, resolution, vars=None, backend=None))]
+    fn to_nrrd(
+        &self,
+        path: &str,
+        bounds: (f32, f32, f32, f32, f32, f32),
+        resolution: (usize, usize, usize),
+        vars: Option<Bound<PyDict>>,
+        backend: Option<&str>,
+    ) -> PyResult<()> {
+        let vol = self.sample_volume(bounds, resolution, vars.as_ref(), backend)?;
+        let mut out = vol.nrrd_header(None).into_bytes();
+        out.extend(vol.raw_bytes());
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+    #[pyo3(signature = (path, bounds, resolution, vars=None, backend=None))]
+    fn to_raw(
+        &self,
+        path: &str,
+        bounds: (f32, f32, f32, f32, f32, f32),
+        resolution: (usize, usize, usize),
+        vars: Option<Bound<PyDict>>,
+        backend: Option<&str>,
+    ) -> PyResult<String> {
+        // raw samples, plus a detached header so that viewers know the layout
+        let raw_path = std::path::Path::new(path);
+        let data_file = match raw_path.file_name() {
+            Some(f) => f.to_string_lossy(),
+            None => return Err(PyRuntimeError::new_err("invalid output path")),
+        };
+        let header_path = raw_path.with_extension("nhdr");
+        if header_path == raw_path {
+            // the header would overwrite the samples
+            return Err(PyRuntimeError::new_err(
+                "raw output path can't have a .nhdr extension",
+            ));
+        }
+        let vol = self.sample_volume(bounds, resolution, vars.as_ref(), backend)?;
+        std::fs::write(path, vol.raw_bytes())?;
+        std::fs::write(&header_path, vol.nrrd_header(Some(&data_file)))?;
+        Ok(header_path.to_string_lossy().into_owned())
     }
     fn __repr__(&self) -> String {
-        let mut ctx = Context::new();
-        ctx.import(&self._val);
-        let thislen: String = ctx.len().to_string();
+        let thislen: String = self.context_len().to_string();
         format!("<Tree, {thislen} nodes>")
     }
+    // `==` and `hash` compare structure, so that trees can be used as dict
+    // keys; they don't build comparison trees (see `compare` for that)
+    fn __eq__(&self, py: Python<'_>, other: &Bound<PyAny>) -> PyResult<Py<PyAny>> {
+        match other.extract::<PyRef<PyTree>>() {
+            Ok(o) => self.structurally_equal(&o).into_py_any(py),
+            Err(..) => Ok(py.NotImplemented()),
+        }
+    }
+    fn __hash__(&self) -> u64 {
+        *self.hash.get_or_init(|| structure::hash(&self._val))
+    }
+    // introspection
+    #[getter]
+    fn op(&self) -> String {
+        match &*self._val {
+            TreeOp::Input(v) => format!("var-{}", vars::describe(*v)),
+            TreeOp::Const(..) => "const".to_owned(),
+            TreeOp::Unary(op, ..) => vm::unary_name(*op).to_owned(),
+            TreeOp::Binary(op, ..) => vm::binary_name(*op).to_owned(),
+            TreeOp::RemapAxes { .. } => "remap".to_owned(),
+        }
+    }
+    #[getter]
+    fn children(&self) -> Vec<PyTree> {
+        structure::children(&self._val)
+            .into_iter()
+            .map(|c| PyTree::from(structure::to_tree(c)))
+            .collect()
+    }
+    #[getter]
+    fn constant_value(&self) -> Option<f64> {
+        match &*self._val {
+            TreeOp::Const(c) => Some(*c),
+            _ => None,
+        }
+    }
+    fn walk(&self) -> PyTreeWalk {
+        let nodes: Vec<Tree> = structure::postorder(&self._val)
+            .into_iter()
+            .map(structure::to_tree)
+            .collect();
+        PyTreeWalk {
+            nodes: nodes.into_iter(),
+        }
+    }
+    fn structurally_equal(&self, other: &PyTree) -> bool {
+        structure::equal(&self._val, &other._val)
+    }
     fn __len__(&self) -> usize {
-        let mut ctx = Context::new();
-        ctx.import(&self._val);
-        ctx.len()
+        self.context_len()
+    }
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let s = self.cached_stats()?;
+        let out = PyDict::new(py);
+        out.set_item("nodes", s.nodes)?;
+        out.set_item("ops", s.ops.clone())?;
+        out.set_item("depth", s.depth)?;
+        out.set_item("choices", s.choices)?;
+        out.set_item("tape_len", s.tape_len)?;
+        out.set_item("reg_tape_len", s.reg_tape_len)?;
+        out.set_item("registers", s.registers)?;
+        out.set_item("memory_slots", s.memory_slots)?;
+        out.set_item("jit_bytes", s.jit_bytes)?;
+        Ok(out)
     }
     fn remap_xyz(&self, new_x: &PyTree, new_y: &PyTree, new_z: &PyTree) -> PyResult<Self> {
         // don't lazily evaluate remappings to prevent unexpected results due to nested remap calls
@@ -427,12 +1051,10 @@ impl PyTree {
         );
         let mut ctx = Context::new();
         let root = ctx.import(&remapped_tree);
-        Ok(PyTree {
-            _val: match ctx.export(root) {
-                Ok(v) => v,
-                Err(e) => return Err(FidgetError::new_err(e.to_string())),
-            },
-        })
+        Ok(PyTree::from(match ctx.export(root) {
+            Ok(v) => v,
+            Err(e) => return Err(FidgetError::new_err(e.to_string())),
+        }))
     }
     fn deriv(&self, v: Self, n: i32) -> PyResult<PyTree> {
         if n < 1 {
@@ -446,7 +1068,7 @@ impl PyTree {
                 for _ in 0..n {
                     derivative = derivative.deriv(val);
                 }
-                Ok(PyTree { _val: derivative })
+                Ok(PyTree::from(derivative))
             }
             None => Err(PyRuntimeError::new_err(
                 "Can only differentiate with respect to a var",
@@ -456,114 +1078,91 @@ impl PyTree {
     // axis words and constants
     #[staticmethod]
     fn x() -> Self {
-        PyTree { _val: Tree::x() }
+        PyTree::from(Tree::x())
     }
     #[staticmethod]
     fn y() -> Self {
-        PyTree { _val: Tree::y() }
+        PyTree::from(Tree::y())
     }
     #[staticmethod]
     fn z() -> Self {
-        PyTree { _val: Tree::z() }
+        PyTree::from(Tree::z())
     }
     #[staticmethod]
-    fn var() -> Self {
-        PyTree {
-            _val: Tree::from(TreeOp::Input(Var::new())),
-        }
+    #[pyo3(signature = (name=None))]
+    fn var(name: Option<&str>) -> PyResult<Self> {
+        let var = match name {
+            Some(name) if !vars::is_valid(name) => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "invalid variable name '{name}'"
+                )))
+            }
+            Some(name) if vars::is_reserved(name) => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "'{name}' is reserved for an axis; use Tree.{name}() instead"
+                )))
+            }
+            Some(name) => vars::lookup(name),
+            None => Var::new(),
+        };
+        Ok(PyTree::from(Tree::from(TreeOp::Input(var))))
     }
     #[staticmethod]
     fn constant(f: f64) -> Self {
-        PyTree {
-            _val: Tree::constant(f),
-        }
+        PyTree::from(Tree::constant(f))
     }
     // unary operations
     fn square(&self) -> Self {
-        PyTree {
-            _val: Tree::square(&self._val),
-        }
+        PyTree::from(Tree::square(&self._val))
     }
     fn floor(&self) -> Self {
-        PyTree {
-            _val: Tree::floor(&self._val),
-        }
+        PyTree::from(Tree::floor(&self._val))
     }
     fn recip(&self) -> Self {
-        PyTree {
-            _val: Tree::constant(1.0) / self._val.to_owned(),
-        }
+        PyTree::from(Tree::constant(1.0) / self._val.to_owned())
     }
     fn ceil(&self) -> Self {
-        PyTree {
-            _val: Tree::ceil(&self._val),
-        }
+        PyTree::from(Tree::ceil(&self._val))
     }
     fn round(&self) -> Self {
-        PyTree {
-            _val: Tree::round(&self._val),
-        }
+        PyTree::from(Tree::round(&self._val))
     }
     fn sqrt(&self) -> Self {
-        PyTree {
-            _val: Tree::sqrt(&self._val),
-        }
+        PyTree::from(Tree::sqrt(&self._val))
     }
     fn neg(&self) -> Self {
-        PyTree {
-            _val: Tree::neg(&self._val),
-        }
+        PyTree::from(Tree::neg(&self._val))
     }
     fn sin(&self) -> Self {
-        PyTree {
-            _val: Tree::sin(&self._val),
-        }
+        PyTree::from(Tree::sin(&self._val))
     }
     fn cos(&self) -> Self {
-        PyTree {
-            _val: Tree::cos(&self._val),
-        }
+        PyTree::from(Tree::cos(&self._val))
     }
     fn tan(&self) -> Self {
-        PyTree {
-            _val: Tree::tan(&self._val),
-        }
+        PyTree::from(Tree::tan(&self._val))
     }
     fn asin(&self) -> Self {
-        PyTree {
-            _val: Tree::asin(&self._val),
-        }
+        PyTree::from(Tree::asin(&self._val))
     }
     fn acos(&self) -> Self {
-        PyTree {
-            _val: Tree::acos(&self._val),
-        }
+        PyTree::from(Tree::acos(&self._val))
     }
     fn atan(&self) -> Self {
-        PyTree {
-            _val: Tree::atan(&self._val),
-        }
+        PyTree::from(Tree::atan(&self._val))
     }
     fn exp(&self) -> Self {
-        PyTree {
-            _val: Tree::exp(&self._val),
-        }
+        PyTree::from(Tree::exp(&self._val))
     }
     fn ln(&self) -> Self {
-        PyTree {
-            _val: Tree::ln(&self._val),
-        }
+        PyTree::from(Tree::ln(&self._val))
     }
     fn not_(&self) -> Self {
         // methods named exactly 'not', 'and', or 'or' will cause syntax errors in python code
-        PyTree {
-            _val: Tree::not(&self._val),
-        }
+        PyTree::from(Tree::not(&self._val))
     }
     fn abs(&self) -> Self {
-        PyTree {
-            _val: Tree::abs(&self._val),
-        }
+        PyTree::from(Tree::abs(&self._val))
     }
     // binary operations
     fn pow(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
@@ -581,10 +1180,7 @@ impl PyTree {
                 res = Tree::constant(1.0) / res;
             }
             Ordering::Equal => {
-                return PyTree {
-                    _val: Tree::constant(1.0),
-                }
-                .into_py_any(other.py());
+                return PyTree::from(Tree::constant(1.0)).into_py_any(other.py());
             }
             Ordering::Greater => {}
         }
@@ -602,24 +1198,20 @@ impl PyTree {
             n /= 2;
         }
         if first_y_mul {
-            PyTree { _val: res * y }.into_py_any(other.py())
+            PyTree::from(res * y).into_py_any(other.py())
         } else {
-            PyTree { _val: res }.into_py_any(other.py())
+            PyTree::from(res).into_py_any(other.py())
         }
     }
     fn add(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned() + ex._val.to_owned(),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned() + ex._val.to_owned()).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned() + Tree::constant(ex),
+                Ok(ex) => {
+                    PyTree::from(self._val.to_owned() + Tree::constant(ex)).into_py_any(other.py())
                 }
-                .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -627,16 +1219,12 @@ impl PyTree {
     fn sub(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned() - ex._val.to_owned(),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned() - ex._val.to_owned()).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned() - Tree::constant(ex),
+                Ok(ex) => {
+                    PyTree::from(self._val.to_owned() - Tree::constant(ex)).into_py_any(other.py())
                 }
-                .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -644,16 +1232,12 @@ impl PyTree {
     fn mul(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned() * ex._val.to_owned(),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned() * ex._val.to_owned()).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned() * Tree::constant(ex),
+                Ok(ex) => {
+                    PyTree::from(self._val.to_owned() * Tree::constant(ex)).into_py_any(other.py())
                 }
-                .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -661,16 +1245,12 @@ impl PyTree {
     fn div(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned() / ex._val.to_owned(),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned() / ex._val.to_owned()).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned() / Tree::constant(ex),
+                Ok(ex) => {
+                    PyTree::from(self._val.to_owned() / Tree::constant(ex)).into_py_any(other.py())
                 }
-                .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -678,16 +1258,11 @@ impl PyTree {
     fn max(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().max(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().max(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().max(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().max(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -695,16 +1270,11 @@ impl PyTree {
     fn min(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().min(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().min(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().min(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().min(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -712,16 +1282,11 @@ impl PyTree {
     fn compare(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().compare(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().compare(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().compare(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().compare(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -729,16 +1294,11 @@ impl PyTree {
     fn modulo(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().modulo(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().modulo(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().modulo(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().modulo(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -746,16 +1306,11 @@ impl PyTree {
     fn and_(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().and(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().and(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().and(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().and(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -763,16 +1318,11 @@ impl PyTree {
     fn or_(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().or(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().or(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().or(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().or(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -780,16 +1330,11 @@ impl PyTree {
     fn atan2(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: self._val.to_owned().atan2(ex._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(self._val.to_owned().atan2(ex._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: self._val.to_owned().atan2(Tree::constant(ex)),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(self._val.to_owned().atan2(Tree::constant(ex)))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -825,16 +1370,12 @@ impl PyTree {
     fn __rsub__(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: ex._val.to_owned() - self._val.to_owned(),
-            }
-            .into_py_any(other.py())
+            PyTree::from(ex._val.to_owned() - self._val.to_owned()).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: Tree::constant(ex) - self._val.to_owned(),
+                Ok(ex) => {
+                    PyTree::from(Tree::constant(ex) - self._val.to_owned()).into_py_any(other.py())
                 }
-                .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -851,16 +1392,12 @@ impl PyTree {
     fn __rtruediv__(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: ex._val.to_owned() / self._val.to_owned(),
-            }
-            .into_py_any(other.py())
+            PyTree::from(ex._val.to_owned() / self._val.to_owned()).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: Tree::constant(ex) / self._val.to_owned(),
+                Ok(ex) => {
+                    PyTree::from(Tree::constant(ex) / self._val.to_owned()).into_py_any(other.py())
                 }
-                .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -871,16 +1408,11 @@ impl PyTree {
     fn __rmod__(&self, other: Bound<PyAny>) -> Result<Py<PyAny>, PyErr> {
         if other.is_instance_of::<PyTree>() {
             let ex = PyTree::extract_bound(&other)?;
-            PyTree {
-                _val: ex._val.to_owned().modulo(self._val.to_owned()),
-            }
-            .into_py_any(other.py())
+            PyTree::from(ex._val.to_owned().modulo(self._val.to_owned())).into_py_any(other.py())
         } else {
             match other.extract() {
-                Ok(ex) => PyTree {
-                    _val: Tree::constant(ex).modulo(self._val.to_owned()),
-                }
-                .into_py_any(other.py()),
+                Ok(ex) => PyTree::from(Tree::constant(ex).modulo(self._val.to_owned()))
+                    .into_py_any(other.py()),
                 Err(..) => Ok(other.py().NotImplemented()),
             }
         }
@@ -899,6 +1431,63 @@ impl PyTree {
     }
 }
 
+/// Builds a shape for each tree on one backend, then meshes them together
+fn mesh_shapes<F: fidget::eval::MathFunction>(
+    contexts: &[(Context, Node)],
+    depth: u8,
+    center: [f32; 3],
+    scale: f32,
+) -> Result<(Mesh, multi::Materials), fidget::Error> {
+    let shapes = contexts
+        .iter()
+        .map(|(ctx, root)| fidget::shape::Shape::<F>::new(ctx, *root))
+        .collect::<Result<Vec<_>, _>>()?;
+    multi::mesh(&shapes, depth, center, scale)
+}
+
+/// Meshes several disjoint trees together, one material per tree
+#[pyfunction]
+#[pyo3(signature = (trees, depth, cx, cy, cz, region_size, vars=None, backend=None))]
+#[allow(clippy::too_many_arguments)]
+fn mesh_multi(
+    trees: Vec<PyTree>,
+    depth: u8,
+    cx: f32,
+    cy: f32,
+    cz: f32,
+    region_size: f32,
+    vars: Option<Bound<PyDict>>,
+    backend: Option<&str>,
+) -> PyResult<PyMesh> {
+    if trees.is_empty() {
+        return Err(PyRuntimeError::new_err("expected at least one tree"));
+    }
+    // grid coordinates are stored as i32
+    if depth > 30 {
+        return Err(PyRuntimeError::new_err("depth must be at most 30"));
+    }
+    let backend = backend_name(backend)?;
+    let mut contexts = vec![];
+    for tree in &trees {
+        contexts.push(tree.context(vars.as_ref())?);
+    }
+    let center = [cx, cy, cz];
+    let result = match backend {
+        #[cfg(feature = "jit")]
+        "jit" => mesh_shapes::<fidget::jit::JitFunction>(&contexts, depth, center, region_size),
+        "vm" => mesh_shapes::<fidget::vm::VmFunction>(&contexts, depth, center, region_size),
+        b => unreachable!("unchecked backend '{b}'"),
+    };
+    match result {
+        Ok((mesh, materials)) => {
+            let mut out = PyMesh::from(mesh);
+            out.materials = Some(materials);
+            Ok(out)
+        }
+        Err(e) => Err(FidgetError::new_err(e.to_string())),
+    }
+}
+
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
@@ -906,6 +1495,15 @@ impl PyTree {
 fn _core(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyTree>()?;
     m.add_class::<PyMesh>()?;
+    m.add_class::<PyTreeWalk>()?;
+    m.add_class::<PyCompiledShape>()?;
+    m.add_function(wrap_pyfunction!(mesh_multi, m)?)?;
+    shapes::register(m)?;
+    transform::register(m)?;
+    repeat::register(m)?;
+    toolpath::register(m)?;
+    gcode::register(m)?;
     m.add("FidgetError", py.get_type::<FidgetError>())?;
+    m.add("BACKENDS", BACKENDS.to_vec())?;
     Ok(())
 }
@@ -0,0 +1,417 @@
+//! Mesh export to PLY, OBJ and binary glTF, with per-vertex attributes.
+//!
+//! Attributes are named scalar fields sampled at each vertex. PLY and glTF
+//! store them as extra vertex properties (glTF requires a leading underscore
+//! for application-specific attributes, so `stress` is written as
+//! `_STRESS`). OBJ has no way to store arbitrary values, so it only carries
+//! them as vertex colors. Any of the formats can also color vertices by one
+//! attribute, mapped through a viridis-like color scale.
+//!
+//! Multi-material meshes are written to 3MF, with one object per material.
+use fidget::{
+    eval::Function,
+    mesh::Mesh,
+    shape::{EzShape, Shape},
+    Error,
+};
+use nalgebra::Vector3;
+use std::fmt::Write;
+
+/// Named per-vertex values, in the order that they were requested
+pub type Attributes = Vec<(String, Vec<f32>)>;
+
+/// Checks whether `name` can be used as an attribute name
+///
+/// Names are written as PLY property names and glTF attribute semantics, so
+/// they're limited to ASCII letters, digits and underscores.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !matches!(name, "x" | "y" | "z" | "red" | "green" | "blue")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Evaluates a shape at every vertex
+pub fn sample<F: Function>(shape: &Shape<F>, vertices: &[Vector3<f32>]) -> Result<Vec<f32>, Error> {
+    let tape = shape.ez_float_slice_tape();
+    let mut eval = Shape::<F>::new_float_slice_eval();
+    let xs: Vec<f32> = vertices.iter().map(|v| v.x).collect();
+    let ys: Vec<f32> = vertices.iter().map(|v| v.y).collect();
+    let zs: Vec<f32> = vertices.iter().map(|v| v.z).collect();
+    let out = eval.eval(&tape, &xs, &ys, &zs)?;
+    Ok(out.to_vec())
+}
+
+/// Stops of the color scale, evenly spaced from low to high values
+const COLOR_SCALE: [[f32; 3]; 5] = [
+    [68.0, 1.0, 84.0],
+    [59.0, 82.0, 139.0],
+    [33.0, 145.0, 140.0],
+    [94.0, 201.0, 98.0],
+    [253.0, 231.0, 37.0],
+];
+
+/// Maps values to RGB colors, scaling from the smallest to the largest
+/// finite value
+///
+/// Non-finite values get the lowest color.
+pub fn colors(values: &[f32]) -> Vec<[u8; 3]> {
+    let finite = values.iter().copied().filter(|v| v.is_finite());
+    let lo = finite.clone().fold(f32::INFINITY, f32::min);
+    let hi = finite.fold(f32::NEG_INFINITY, f32::max);
+    values
+        .iter()
+        .map(|&v| {
+            let t = if !v.is_finite() {
+                0.0
+            } else if hi > lo {
+                (v - lo) / (hi - lo)
+            } else {
+                0.5
+            };
+            let pos = t * (COLOR_SCALE.len() - 1) as f32;
+            let i = (pos as usize).min(COLOR_SCALE.len() - 2);
+            let frac = pos - i as f32;
+            let (a, b) = (COLOR_SCALE[i], COLOR_SCALE[i + 1]);
+            std::array::from_fn(|c| (a[c] + (b[c] - a[c]) * frac).round() as u8)
+        })
+        .collect()
+}
+
+/// Writes a binary little-endian PLY file
+///
+/// Each attribute becomes a `float` vertex property, followed by `red`,
+/// `green` and `blue` properties if `colors` is given.
+pub fn to_ply(mesh: &Mesh, attributes: &Attributes, colors: Option<&[[u8; 3]]>) -> Vec<u8> {
+    let mut header = String::new();
+    header += "ply\nformat binary_little_endian 1.0\ncomment exported by Fidget\n";
+    writeln!(header, "element vertex {}", mesh.vertices.len()).unwrap();
+    header += "property float x\nproperty float y\nproperty float z\n";
+    for (name, _) in attributes {
+        writeln!(header, "property float {name}").unwrap();
+    }
+    if colors.is_some() {
+        header += "property uchar red\nproperty uchar green\nproperty uchar blue\n";
+    }
+    writeln!(header, "element face {}", mesh.triangles.len()).unwrap();
+    header += "property list uchar uint vertex_indices\nend_header\n";
+
+    let mut out = header.into_bytes();
+    for (i, v) in mesh.vertices.iter().enumerate() {
+        for p in v.iter() {
+            out.extend(p.to_le_bytes());
+        }
+        for (_, values) in attributes {
+            out.extend(values[i].to_le_bytes());
+        }
+        if let Some(c) = colors {
+            out.extend(c[i]);
+        }
+    }
+    for t in &mesh.triangles {
+        out.push(3);
+        for i in t.iter() {
+            out.extend((*i as u32).to_le_bytes());
+        }
+    }
+    out
+}
+
+/// Writes a Wavefront OBJ file, with vertex colors if `colors` is given
+pub fn to_obj(mesh: &Mesh, colors: Option<&[[u8; 3]]>) -> String {
+    let mut out = String::from("# exported by Fidget\n");
+    for (i, v) in mesh.vertices.iter().enumerate() {
+        write!(out, "v {} {} {}", v.x, v.y, v.z).unwrap();
+        if let Some(c) = colors {
+            let [r, g, b] = c[i].map(|c| c as f32 / 255.0);
+            write!(out, " {r} {g} {b}").unwrap();
+        }
+        out.push('\n');
+    }
+    // OBJ indices start at 1
+    for t in &mesh.triangles {
+        writeln!(out, "f {} {} {}", t.x + 1, t.y + 1, t.z + 1).unwrap();
+    }
+    out
+}
+
+/// Writes a binary glTF 2.0 (GLB) file
+///
+/// Attributes are stored as `SCALAR` float accessors named `_<NAME>` in
+/// upper case, and colors as normalized `COLOR_0`.
+pub fn to_glb(mesh: &Mesh, attributes: &Attributes, colors: Option<&[[u8; 3]]>) -> Vec<u8> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_BYTE: u32 = 5121;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let n = mesh.vertices.len();
+    let mut bin: Vec<u8> = vec![];
+    let mut views: Vec<String> = vec![];
+    let mut accessors: Vec<String> = vec![];
+    // appends a buffer view, padded to a multiple of four bytes as required
+    // for accessor alignment, and returns its index
+    let mut add_view = |bin: &mut Vec<u8>, data: Vec<u8>, target: u32| {
+        let offset = bin.len();
+        let len = data.len();
+        bin.extend(data);
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{len},"target":{target}}}"#
+        ));
+        views.len() - 1
+    };
+
+    let positions = mesh
+        .vertices
+        .iter()
+        .flat_map(|v| v.iter())
+        .flat_map(|p| p.to_le_bytes());
+    let view = add_view(&mut bin, positions.collect(), ARRAY_BUFFER);
+    let (mut lo, mut hi) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for v in &mesh.vertices {
+        for i in 0..3 {
+            lo[i] = lo[i].min(v[i]);
+            hi[i] = hi[i].max(v[i]);
+        }
+    }
+    if n == 0 {
+        (lo, hi) = ([0.0; 3], [0.0; 3]);
+    }
+    accessors.push(format!(
+        r#"{{"bufferView":{view},"componentType":{FLOAT},"count":{n},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+        lo[0], lo[1], lo[2], hi[0], hi[1], hi[2]
+    ));
+    let mut attrs = vec![format!(r#""POSITION":{}"#, accessors.len() - 1)];
+
+    for (name, values) in attributes {
+        let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let view = add_view(&mut bin, data, ARRAY_BUFFER);
+        accessors.push(format!(
+            r#"{{"bufferView":{view},"componentType":{FLOAT},"count":{n},"type":"SCALAR"}}"#
+        ));
+        // names are plain ASCII, so they don't need escaping
+        attrs.push(format!(
+            r#""_{}":{}"#,
+            name.to_uppercase(),
+            accessors.len() - 1
+        ));
+    }
+    if let Some(c) = colors {
+        // RGBA, so that each element is aligned to four bytes
+        let data = c.iter().flat_map(|[r, g, b]| [*r, *g, *b, 255]).collect();
+        let view = add_view(&mut bin, data, ARRAY_BUFFER);
+        accessors.push(format!(
+            r#"{{"bufferView":{view},"componentType":{UNSIGNED_BYTE},"normalized":true,"count":{n},"type":"VEC4"}}"#
+        ));
+        attrs.push(format!(r#""COLOR_0":{}"#, accessors.len() - 1));
+    }
+
+    let indices = mesh
+        .triangles
+        .iter()
+        .flat_map(|t| t.iter())
+        .flat_map(|i| (*i as u32).to_le_bytes());
+    let view = add_view(&mut bin, indices.collect(), ELEMENT_ARRAY_BUFFER);
+    accessors.push(format!(
+        r#"{{"bufferView":{view},"componentType":{UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+        mesh.triangles.len() * 3
+    ));
+    let indices = accessors.len() - 1;
+
+    let mut json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"Fidget"}},"scene":0,"#,
+            r#""scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+            r#""meshes":[{{"primitives":[{{"attributes":{{{}}},"indices":{},"mode":4}}]}}],"#,
+            r#""accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#
+        ),
+        attrs.join(","),
+        indices,
+        accessors.join(","),
+        views.join(","),
+        bin.len()
+    )
+    .into_bytes();
+    // the JSON chunk is padded with spaces
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    let mut out = vec![];
+    out.extend(b"glTF");
+    out.extend(2u32.to_le_bytes());
+    out.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+    out.extend((json.len() as u32).to_le_bytes());
+    out.extend(b"JSON");
+    out.extend(json);
+    out.extend((bin.len() as u32).to_le_bytes());
+    out.extend(b"BIN\0");
+    out.extend(bin);
+    out
+}
+
+/// Display colors for the materials in a 3MF file, reused in order
+const MATERIAL_COLORS: [&str; 8] = [
+    "#4C72B0", "#DD8452", "#55A868", "#C44E52", "#8172B3", "#937860", "#DA8BC3", "#8C8C8C",
+];
+
+/// Escapes text for use in an XML attribute
+fn xml_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => out += "&amp;",
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '"' => out += "&quot;",
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Writes a 3MF package with one object per material
+///
+/// Each `(name, mesh)` pair becomes a separate object, assigned to its own
+/// entry of a shared base material group. Meshes without triangles are
+/// skipped, since 3MF requires every object to have some.
+pub fn to_3mf(materials: &[(String, Mesh)]) -> Vec<u8> {
+    let mut model = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<model unit="millimeter" xml:lang="en-US" "#,
+        r#"xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">"#,
+        "\n<resources>\n<basematerials id=\"1\">\n",
+    ));
+    for (i, (name, _)) in materials.iter().enumerate() {
+        let color = MATERIAL_COLORS[i % MATERIAL_COLORS.len()];
+        writeln!(
+            model,
+            r#"<base name="{}" displaycolor="{color}"/>"#,
+            xml_escape(name)
+        )
+        .unwrap();
+    }
+    model += "</basematerials>\n";
+    let mut items = vec![];
+    for (i, (name, mesh)) in materials.iter().enumerate() {
+        if mesh.triangles.is_empty() {
+            continue;
+        }
+        let id = i + 2;
+        items.push(id);
+        writeln!(
+            model,
+            r#"<object id="{id}" type="model" pid="1" pindex="{i}" name="{}">"#,
+            xml_escape(name)
+        )
+        .unwrap();
+        model += "<mesh>\n<vertices>\n";
+        for v in &mesh.vertices {
+            writeln!(model, r#"<vertex x="{}" y="{}" z="{}"/>"#, v.x, v.y, v.z).unwrap();
+        }
+        model += "</vertices>\n<triangles>\n";
+        for t in &mesh.triangles {
+            writeln!(
+                model,
+                r#"<triangle v1="{}" v2="{}" v3="{}"/>"#,
+                t.x, t.y, t.z
+            )
+            .unwrap();
+        }
+        model += "</triangles>\n</mesh>\n</object>\n";
+    }
+    model += "</resources>\n<build>\n";
+    for id in items {
+        writeln!(model, r#"<item objectid="{id}"/>"#).unwrap();
+    }
+    model += "</build>\n</model>\n";
+
+    let content_types = concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+        r#"<Default Extension="rels" "#,
+        r#"ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+        r#"<Default Extension="model" "#,
+        r#"ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>"#,
+        "</Types>\n"
+    );
+    let rels = concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        r#"<Relationship Target="/3D/3dmodel.model" Id="rel0" "#,
+        r#"Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>"#,
+        "</Relationships>\n"
+    );
+    zip(&[
+        ("[Content_Types].xml", content_types.as_bytes()),
+        ("_rels/.rels", rels.as_bytes()),
+        ("3D/3dmodel.model", model.as_bytes()),
+    ])
+}
+
+/// Computes the CRC-32 checksum used by ZIP files
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Writes an uncompressed ZIP archive
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut central = vec![];
+    for (name, data) in files {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        // fields shared by the local and central headers: version needed,
+        // flags, method (stored), time, date, crc, sizes and name length
+        let mut common = vec![];
+        common.extend(20u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0x21u16.to_le_bytes()); // 1980-01-01
+        common.extend(crc.to_le_bytes());
+        common.extend((data.len() as u32).to_le_bytes());
+        common.extend((data.len() as u32).to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes()); // extra field length
+
+        out.extend(0x0403_4b50u32.to_le_bytes());
+        out.extend(&common);
+        out.extend(name.as_bytes());
+        out.extend(*data);
+
+        central.extend(0x0201_4b50u32.to_le_bytes());
+        central.extend(20u16.to_le_bytes()); // version made by
+        central.extend(&common);
+        central.extend(0u16.to_le_bytes()); // comment length
+        central.extend(0u16.to_le_bytes()); // disk number
+        central.extend(0u16.to_le_bytes()); // internal attributes
+        central.extend(0u32.to_le_bytes()); // external attributes
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    let central_offset = out.len() as u32;
+    out.extend(&central);
+    out.extend(0x0605_4b50u32.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // disk number
+    out.extend(0u16.to_le_bytes()); // disk with central directory
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(central_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // comment length
+    out
+}
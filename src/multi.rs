@@ -0,0 +1,424 @@
+//! Meshing of several disjoint materials at once.
+//!
+//! The region is divided into an octree with `2^depth` cells along each
+//! axis, as for single-material meshing. Every cell is classified against all
+//! materials at once with interval arithmetic: it's *uniform* if it's
+//! certainly outside every material, or certainly inside one material and
+//! outside the others, and only the remaining cells are subdivided. Samples
+//! are then taken at the corners of the leaf cells that are left, and each
+//! sample is labelled with the material that has the smallest (most inside)
+//! value there, or left empty if no material contains it.
+//!
+//! A surface-nets pass places one vertex in every cell whose corners have
+//! different labels, and builds a quad across every grid edge whose ends
+//! have different labels. A quad between two materials is emitted once for
+//! each of them, with opposite winding, so both materials share exactly the
+//! same interface vertices: the per-material meshes can't have gaps or
+//! overlaps between them. Everything outside the region counts as empty, so
+//! materials that extend past it are closed off at its faces, and each
+//! material's mesh is closed.
+use fidget::{
+    eval::{Function, TracingEvaluator},
+    mesh::Mesh,
+    shape::{EzShape, Shape, ShapeBulkEval, ShapeTape, ShapeTracingEval},
+    types::Interval,
+    Error,
+};
+use nalgebra::Vector3;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Label for samples outside of every material
+const EMPTY: u32 = u32::MAX;
+
+/// Depth of the octree cells that are classified on separate threads
+const SPLIT_DEPTH: u8 = 2;
+
+/// Number of samples evaluated at once by each thread
+const CHUNK_SIZE: usize = 4096;
+
+const CORNERS: [[i32; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+type Coord = [i32; 3];
+
+/// Per-triangle material indices for a mesh built by [`mesh`]
+#[derive(Clone)]
+pub struct Materials {
+    /// Number of materials that were meshed, including any that produced no
+    /// triangles
+    pub count: usize,
+    /// Material index of each triangle
+    pub ids: Vec<u32>,
+}
+
+/// The cube being meshed, divided into `n` cells along each axis
+struct Grid {
+    n: i32,
+    center: [f32; 3],
+    scale: f32,
+}
+
+impl Grid {
+    /// Converts a (possibly fractional) grid coordinate to world units
+    fn world(&self, axis: usize, g: f32) -> f32 {
+        self.center[axis] + self.scale * (2.0 * g / self.n as f32 - 1.0)
+    }
+
+    fn contains(&self, p: Coord) -> bool {
+        p.iter().all(|c| (0..=self.n).contains(c))
+    }
+}
+
+/// Interval classification of octree cells, on one thread
+struct Classifier<'a, F: Function> {
+    grid: &'a Grid,
+    tapes: Vec<ShapeTape<<F::IntervalEval as TracingEvaluator>::Tape>>,
+    eval: ShapeTracingEval<F::IntervalEval>,
+}
+
+impl<F: Function> Classifier<'_, F> {
+    /// Collects the leaf cells below a cell whose label isn't known to be
+    /// uniform, considering only the `active` materials
+    ///
+    /// Cells on the faces of the region are also kept if they're inside a
+    /// material, since that material is closed off across them.
+    fn recurse(
+        &mut self,
+        origin: Coord,
+        size: i32,
+        active: &[usize],
+        leaves: &mut Vec<Coord>,
+    ) -> Result<(), Error> {
+        let range = |i: usize| {
+            Interval::new(
+                self.grid.world(i, origin[i] as f32),
+                self.grid.world(i, (origin[i] + size) as f32),
+            )
+        };
+        let (x, y, z) = (range(0), range(1), range(2));
+        // materials that may contain part of the cell; the others stay out
+        // of it, and of its children
+        let mut inside = vec![];
+        let mut certain = true;
+        for &m in active {
+            let (v, _trace) = self.eval.eval(&self.tapes[m], x, y, z)?;
+            // NaN fails both comparisons, so the cell is subdivided
+            if v.lower() >= 0.0 {
+                continue;
+            }
+            certain &= v.upper() < 0.0;
+            inside.push(m);
+        }
+        let uniform = inside.is_empty() || (inside.len() == 1 && certain);
+        let on_face = (0..3).any(|i| origin[i] == 0 || origin[i] + size == self.grid.n);
+        if uniform && (inside.is_empty() || !on_face) {
+            return Ok(());
+        }
+        if size == 1 {
+            leaves.push(origin);
+            return Ok(());
+        }
+        let half = size / 2;
+        for c in CORNERS {
+            let child = [0, 1, 2].map(|i| origin[i] + c[i] * half);
+            self.recurse(child, half, &inside, leaves)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sampled values of every material at grid points, with their labels
+struct Samples<'a, F: Function> {
+    grid: &'a Grid,
+    count: usize,
+    index: HashMap<Coord, usize>,
+    /// `count` values for each sample
+    values: Vec<f32>,
+    labels: Vec<u32>,
+    /// Evaluators for samples that weren't taken up front
+    tapes: Vec<ShapeTape<<F::PointEval as TracingEvaluator>::Tape>>,
+    eval: ShapeTracingEval<F::PointEval>,
+}
+
+impl<F: Function> Samples<'_, F> {
+    fn push(&mut self, p: Coord, values: &[f32]) {
+        let mut best = (EMPTY, 0.0);
+        for (m, &v) in values.iter().enumerate() {
+            // NaN fails this comparison, so it's never inside
+            if v < best.1 {
+                best = (m as u32, v);
+            }
+        }
+        self.index.insert(p, self.labels.len());
+        self.labels.push(best.0);
+        self.values.extend_from_slice(values);
+    }
+
+    /// Returns the sample at a grid point, or `None` outside the grid
+    ///
+    /// Interval arithmetic is conservative, so every point that's needed
+    /// should have been sampled already; this falls back to evaluating it
+    /// in case rounding puts a label change next to a uniform cell.
+    fn get(&mut self, p: Coord) -> Result<Option<usize>, Error> {
+        if !self.grid.contains(p) {
+            return Ok(None);
+        }
+        if let Some(&i) = self.index.get(&p) {
+            return Ok(Some(i));
+        }
+        let [x, y, z] = [0, 1, 2].map(|i| self.grid.world(i, p[i] as f32));
+        let mut values = Vec::with_capacity(self.count);
+        for tape in &self.tapes {
+            let (v, _trace) = self.eval.eval(tape, x, y, z)?;
+            values.push(v);
+        }
+        self.push(p, &values);
+        Ok(Some(self.labels.len() - 1))
+    }
+
+    fn label(&mut self, p: Coord) -> Result<u32, Error> {
+        Ok(match self.get(p)? {
+            Some(i) => self.labels[i],
+            None => EMPTY,
+        })
+    }
+
+    /// The value that separates a material from its neighbors, with empty
+    /// space at zero, so `value(a) - value(b)` crosses zero between a and b
+    fn value(&self, label: u32, i: usize) -> f32 {
+        match label {
+            EMPTY => 0.0,
+            m => self.values[i * self.count + m as usize],
+        }
+    }
+
+    /// Position of the label change along the edge from `a` to `b`, as a
+    /// fraction of the distance between them
+    ///
+    /// The region's faces are the boundary of every material, so a change
+    /// that crosses them is placed on them.
+    fn crossing(&mut self, a: Coord, b: Coord) -> Result<f32, Error> {
+        let (ia, ib) = match (self.get(a)?, self.get(b)?) {
+            (Some(ia), Some(ib)) => (ia, ib),
+            (None, _) => return Ok(1.0),
+            (_, None) => return Ok(0.0),
+        };
+        let (la, lb) = (self.labels[ia], self.labels[ib]);
+        let ga = self.value(la, ia) - self.value(lb, ia);
+        let gb = self.value(la, ib) - self.value(lb, ib);
+        let t = ga / (ga - gb);
+        Ok(if t.is_finite() {
+            t.clamp(0.0, 1.0)
+        } else {
+            0.5
+        })
+    }
+}
+
+/// Builds a multi-material mesh of `shapes` over the cube of half-size
+/// `scale` around `center`, with `2^depth` cells along each axis
+pub fn mesh<F: Function>(
+    shapes: &[Shape<F>],
+    depth: u8,
+    center: [f32; 3],
+    scale: f32,
+) -> Result<(Mesh, Materials), Error> {
+    let grid = Grid {
+        n: 1 << depth,
+        center,
+        scale,
+    };
+    let count = shapes.len();
+    let all: Vec<usize> = (0..count).collect();
+
+    // classify the cells at a shallow depth in parallel, each down to the
+    // finest level
+    let split = SPLIT_DEPTH.min(depth);
+    let size = grid.n >> split;
+    let starts: Vec<Coord> = (0..1 << (3 * split))
+        .map(|i: i32| {
+            let per_axis = 1 << split;
+            [
+                i % per_axis,
+                (i / per_axis) % per_axis,
+                i / (per_axis * per_axis),
+            ]
+            .map(|c| c * size)
+        })
+        .collect();
+    let leaves: Vec<Vec<Coord>> = starts
+        .par_iter()
+        .map_init(
+            || Classifier::<F> {
+                grid: &grid,
+                tapes: shapes.iter().map(|s| s.ez_interval_tape()).collect(),
+                eval: Shape::<F>::new_interval_eval(),
+            },
+            |c, &origin| {
+                let mut out = vec![];
+                c.recurse(origin, size, &all, &mut out)?;
+                Ok(out)
+            },
+        )
+        .collect::<Result<_, Error>>()?;
+    let mut leaves: Vec<Coord> = leaves.into_iter().flatten().collect();
+    leaves.sort();
+
+    // sample every material at the corners of the leaf cells
+    let mut points: Vec<Coord> = leaves
+        .iter()
+        .flat_map(|o| CORNERS.map(|c| [o[0] + c[0], o[1] + c[1], o[2] + c[2]]))
+        .collect();
+    points.sort();
+    points.dedup();
+    let chunks: Vec<Vec<f32>> = points
+        .par_chunks(CHUNK_SIZE)
+        .map_init(
+            || {
+                let tapes: Vec<_> = shapes.iter().map(|s| s.ez_float_slice_tape()).collect();
+                (Shape::<F>::new_float_slice_eval(), tapes)
+            },
+            |(eval, tapes): &mut (ShapeBulkEval<F::FloatSliceEval>, Vec<_>), chunk| {
+                let [xs, ys, zs] = [0, 1, 2].map(|i| {
+                    chunk
+                        .iter()
+                        .map(|p| grid.world(i, p[i] as f32))
+                        .collect::<Vec<_>>()
+                });
+                // interleave the materials, so each sample's values are
+                // contiguous
+                let mut out = vec![0.0; chunk.len() * count];
+                for (m, tape) in tapes.iter().enumerate() {
+                    let values = eval.eval(tape, &xs, &ys, &zs)?;
+                    for (j, v) in values.iter().enumerate() {
+                        out[j * count + m] = *v;
+                    }
+                }
+                Ok(out)
+            },
+        )
+        .collect::<Result<_, Error>>()?;
+    let mut samples = Samples::<F> {
+        grid: &grid,
+        count,
+        index: HashMap::with_capacity(points.len()),
+        values: Vec::with_capacity(points.len() * count),
+        labels: Vec::with_capacity(points.len()),
+        tapes: shapes.iter().map(|s| s.ez_point_tape()).collect(),
+        eval: Shape::<F>::new_point_eval(),
+    };
+    for (p, values) in points
+        .iter()
+        .zip(chunks.iter().flat_map(|c| c.chunks(count)))
+    {
+        samples.push(*p, values);
+    }
+
+    // Every label change is on an edge of a leaf cell, or on an edge leaving
+    // the region from a leaf cell's corner on its faces
+    let mut edges = vec![];
+    for o in &leaves {
+        for a in CORNERS {
+            for axis in 0..3 {
+                if a[axis] == 0 {
+                    edges.push(([o[0] + a[0], o[1] + a[1], o[2] + a[2]], axis));
+                }
+            }
+        }
+    }
+    for p in &points {
+        for axis in 0..3 {
+            let mut q = *p;
+            if p[axis] == 0 {
+                q[axis] -= 1;
+                edges.push((q, axis));
+            } else if p[axis] == grid.n {
+                edges.push((q, axis));
+            }
+        }
+    }
+    edges.sort();
+    edges.dedup();
+
+    let mut vertices = vec![];
+    let mut cell_vertex: HashMap<Coord, u32> = HashMap::new();
+    let mut vertex = |samples: &mut Samples<F>, cell: Coord| -> Result<u32, Error> {
+        if let Some(&v) = cell_vertex.get(&cell) {
+            return Ok(v);
+        }
+        let corner = |c: Coord| [cell[0] + c[0], cell[1] + c[1], cell[2] + c[2]];
+        // average the crossings on the cell's 12 edges
+        let mut sum = Vector3::zeros();
+        let mut n = 0;
+        for a in CORNERS {
+            for axis in 0..3 {
+                if a[axis] == 1 {
+                    continue;
+                }
+                let mut b = a;
+                b[axis] = 1;
+                let (pa, pb) = (corner(a), corner(b));
+                if samples.label(pa)? != samples.label(pb)? {
+                    let mut p = Vector3::new(a[0] as f32, a[1] as f32, a[2] as f32);
+                    p[axis] += samples.crossing(pa, pb)?;
+                    sum += p;
+                    n += 1;
+                }
+            }
+        }
+        let local = sum / n as f32;
+        let pos = Vector3::from_fn(|axis, _| grid.world(axis, cell[axis] as f32 + local[axis]));
+        let v = vertices.len() as u32;
+        vertices.push(pos);
+        cell_vertex.insert(cell, v);
+        Ok(v)
+    };
+
+    let mut triangles = vec![];
+    let mut ids = vec![];
+    for (p, d) in edges {
+        let mut q = p;
+        q[d] += 1;
+        let (la, lb) = (samples.label(p)?, samples.label(q)?);
+        if la == lb {
+            continue;
+        }
+        // the four cells around the edge all have corners with both labels
+        let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+        let mut quad = [0; 4];
+        for (slot, (du, dv)) in quad.iter_mut().zip([(1, 1), (0, 1), (0, 0), (1, 0)]) {
+            let mut c = p;
+            c[u] -= du;
+            c[v] -= dv;
+            *slot = vertex(&mut samples, c)? as usize;
+        }
+        // quad is counter-clockwise around +d, since u × v = d, and a is on
+        // the -d side, so its outward normal is +d
+        if la != EMPTY {
+            triangles.push(Vector3::new(quad[0], quad[1], quad[2]));
+            triangles.push(Vector3::new(quad[0], quad[2], quad[3]));
+            ids.extend([la, la]);
+        }
+        if lb != EMPTY {
+            triangles.push(Vector3::new(quad[0], quad[2], quad[1]));
+            triangles.push(Vector3::new(quad[0], quad[3], quad[2]));
+            ids.extend([lb, lb]);
+        }
+    }
+
+    let mesh = Mesh {
+        vertices,
+        triangles,
+    };
+    let materials = Materials { count, ids };
+    Ok((mesh, materials))
+}
@@ -0,0 +1,103 @@
+//! Offsets, shells and thickened surfaces, with exactness tracking.
+//!
+//! Trees carry a flag saying whether they're known to be *exact* distance
+//! fields, whose value is the signed distance to their surface; otherwise
+//! they're assumed to be *bounds*, whose magnitude may underestimate it (as
+//! after a `min` or `max`). Primitives from `shapes` are exact, similarity
+//! transforms keep the flag, and every other operation clears
+//! it. Offsetting a bound by `d` doesn't move its surface by `d`, so each
+//! function here reports whether its result is still exact.
+//!
+//! Fields that are off by more than a bound (e.g. after non-uniform scaling,
+//! whose gradient isn't unit length) can be re-distanced first, dividing the
+//! field by the length of its gradient. This is a first-order correction:
+//! exact near the surface of any smooth field, and a no-op on fields that
+//! are already Lipschitz-1 almost everywhere, so it can't turn a bound into
+//! an exact field. Re-distanced results are never flagged as exact.
+use fidget::context::Tree;
+
+/// Step used for the finite-difference gradient when re-distancing
+///
+/// Fidget's symbolic derivatives of `min`, `max` and `abs` use `or`, which
+/// the JIT can't compile when it's followed by `max` (as of fidget 0.3.5),
+/// so the gradient is estimated with central differences instead.
+const GRADIENT_STEP: f64 = 1e-3;
+
+/// Smallest gradient length used when re-distancing, which bounds the
+/// correction where the gradient vanishes (e.g. at the center of a sphere)
+const MIN_GRADIENT: f64 = 1e-3;
+
+/// Divides `tree` by the length of its gradient
+pub fn redistance(tree: &Tree) -> Tree {
+    let axes = [Tree::x(), Tree::y(), Tree::z()];
+    let shifted = |d: usize, h: f64| {
+        let mut p = axes.clone();
+        p[d] = p[d].clone() + h;
+        let [x, y, z] = p;
+        tree.remap_xyz(x, y, z)
+    };
+    let [dx, dy, dz] = [0, 1, 2]
+        .map(|d| (shifted(d, GRADIENT_STEP) - shifted(d, -GRADIENT_STEP)) / (2.0 * GRADIENT_STEP));
+    let norm = (dx.square() + dy.square() + dz.square()).sqrt();
+    tree.clone() / norm.max(MIN_GRADIENT)
+}
+
+fn field(tree: &Tree, redistanced: bool) -> Tree {
+    if redistanced {
+        redistance(tree)
+    } else {
+        tree.clone()
+    }
+}
+
+/// Grows the shape by `d`, or shrinks it if `d` is negative
+///
+/// Growing an exact field keeps it exact, unless it's re-distanced.
+/// Shrinking one leaves a bound: outside a convex corner, the shrunken
+/// corner is further away than the field says.
+pub fn offset(tree: &Tree, exact: bool, d: f64, redistanced: bool) -> Result<(Tree, bool), String> {
+    if !d.is_finite() {
+        return Err(format!("offset must be finite, got {d}"));
+    }
+    Ok((
+        field(tree, redistanced) - d,
+        exact && d >= 0.0 && !redistanced,
+    ))
+}
+
+/// Hollows out the shape, leaving a wall of `thickness` inside its surface
+///
+/// This is `|f + t/2| - t/2`, the surface of an inward offset thickened by
+/// `t`, so the result is a bound even for an exact field.
+pub fn shell(tree: &Tree, thickness: f64, redistanced: bool) -> Result<(Tree, bool), String> {
+    check_thickness(thickness)?;
+    let half = thickness / 2.0;
+    Ok(((field(tree, redistanced) + half).abs() - half, false))
+}
+
+/// Turns the surface into a solid of `thickness`, centered on it
+///
+/// `|f|` is the unsigned distance to the surface if `f` is exact, so the
+/// result is exact whenever the input is and isn't re-distanced.
+pub fn thicken_surface(
+    tree: &Tree,
+    exact: bool,
+    thickness: f64,
+    redistanced: bool,
+) -> Result<(Tree, bool), String> {
+    check_thickness(thickness)?;
+    Ok((
+        field(tree, redistanced).abs() - thickness / 2.0,
+        exact && !redistanced,
+    ))
+}
+
+fn check_thickness(thickness: f64) -> Result<(), String> {
+    if thickness > 0.0 && thickness.is_finite() {
+        Ok(())
+    } else {
+        Err(format!(
+            "thickness must be positive and finite, got {thickness}"
+        ))
+    }
+}
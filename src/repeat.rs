@@ -0,0 +1,236 @@
+//! Domain repetition: linear, mirrored and polar arrays of a shape.
+//!
+//! Each copy owns a cell of space, picked by rounding, and the field in that
+//! cell is the copy's field with the point moved into its frame. A copy can
+//! stick out of its cell, so the copy on the other side of the nearest cell
+//! boundary is evaluated as well and the two are combined with `min`. A copy
+//! that reaches past the cells next to its own, or a nearer copy across a
+//! corner, isn't seen, so the result is only ever a bound.
+use fidget::context::Tree;
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use std::f64::consts::TAU;
+
+type Bounds = (f64, f64, f64, f64, f64, f64);
+
+fn check_spacing(spacing: [f64; 3]) -> Result<(), String> {
+    if spacing.iter().all(|s| *s >= 0.0 && s.is_finite()) {
+        Ok(())
+    } else {
+        Err("spacing must be non-negative and finite".to_owned())
+    }
+}
+
+fn check_count(count: Option<[u32; 3]>) -> Result<(), String> {
+    if count.is_some_and(|c| c.contains(&0)) {
+        Err("count must be at least 1 along every axis".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses an axis name, returning the indices of the two axes spanning the
+/// plane around it (in right-handed order) and the index of the axis itself
+fn parse_axis(axis: &str) -> Result<[usize; 3], String> {
+    match axis {
+        "x" => Ok([1, 2, 0]),
+        "y" => Ok([2, 0, 1]),
+        "z" => Ok([0, 1, 2]),
+        _ => Err(format!("unknown axis '{axis}', expected 'x', 'y' or 'z'")),
+    }
+}
+
+/// Minimum of `tree` over every combination of per-axis candidates
+///
+/// Each entry of `candidates` holds the expressions that can replace that
+/// axis; the tree is remapped with each combination in turn.
+fn min_over(tree: &Tree, candidates: &[Vec<Tree>; 3]) -> Tree {
+    let mut out: Option<Tree> = None;
+    for x in &candidates[0] {
+        for y in &candidates[1] {
+            for z in &candidates[2] {
+                let t = tree.remap_xyz(x.clone(), y.clone(), z.clone());
+                out = Some(match out {
+                    Some(o) => o.min(t),
+                    None => t,
+                });
+            }
+        }
+    }
+    out.unwrap()
+}
+
+/// Repeats `tree` every `spacing` along each axis, optionally mirroring
+/// every other copy
+///
+/// An axis with zero spacing isn't repeated. With a `count`, copies are
+/// placed at `0, s, 2s, ..., (n - 1)s` along each axis; without one, the
+/// repetition is infinite in both directions.
+pub fn linear(
+    tree: &Tree,
+    spacing: [f64; 3],
+    count: Option<[u32; 3]>,
+    mirror: bool,
+) -> Result<Tree, String> {
+    check_spacing(spacing)?;
+    check_count(count)?;
+    let axes = [Tree::x(), Tree::y(), Tree::z()];
+    let candidates = std::array::from_fn(|d| {
+        let (p, s) = (&axes[d], spacing[d]);
+        if s == 0.0 {
+            return vec![p.clone()];
+        }
+        let clamp = |id: Tree| match count {
+            Some(n) => id.max(0.0).min(f64::from(n[d] - 1)),
+            None => id,
+        };
+        let id = clamp((p.clone() / s).round());
+        // the cell on the other side of the nearest boundary
+        let next = clamp(id.clone() + (p.clone() - id.clone() * s).compare(0.0));
+        [id, next]
+            .into_iter()
+            .map(|id| {
+                let local = p.clone() - id.clone() * s;
+                if mirror {
+                    // odd copies are reflected about their own origin
+                    local * (1.0 - id.modulo(2.0) * 2.0)
+                } else {
+                    local
+                }
+            })
+            .collect()
+    });
+    Ok(min_over(tree, &candidates))
+}
+
+/// Bounds of a linear array built by [`linear`]
+pub fn linear_bounds(
+    b: Bounds,
+    spacing: [f64; 3],
+    count: Option<[u32; 3]>,
+    mirror: bool,
+) -> Result<Bounds, String> {
+    check_spacing(spacing)?;
+    check_count(count)?;
+    let mut ranges = [(b.0, b.1), (b.2, b.3), (b.4, b.5)];
+    for (d, r) in ranges.iter_mut().enumerate() {
+        let s = spacing[d];
+        if s == 0.0 {
+            continue;
+        }
+        let Some(n) = count.map(|c| c[d]) else {
+            *r = (-f64::INFINITY, f64::INFINITY);
+            continue;
+        };
+        // the extremes come from the first and last copies of each parity
+        let (lo, hi) = *r;
+        let mut out = (f64::INFINITY, -f64::INFINITY);
+        for i in [0, 1, n.saturating_sub(2), n - 1] {
+            if i >= n {
+                continue;
+            }
+            let o = f64::from(i) * s;
+            let (a, b) = if mirror && i % 2 == 1 {
+                (o - hi, o - lo)
+            } else {
+                (o + lo, o + hi)
+            };
+            out = (out.0.min(a), out.1.max(b));
+        }
+        *r = out;
+    }
+    Ok((
+        ranges[0].0,
+        ranges[0].1,
+        ranges[1].0,
+        ranges[1].1,
+        ranges[2].0,
+        ranges[2].1,
+    ))
+}
+
+/// Repeats `tree` `n` times around an axis through the origin
+pub fn polar(tree: &Tree, n: u32, axis: &str) -> Result<Tree, String> {
+    let [iu, iv, _] = parse_axis(axis)?;
+    if n == 0 {
+        return Err("polar_repeat needs at least one copy".to_owned());
+    }
+    if n == 1 {
+        return Ok(tree.clone());
+    }
+    let axes = [Tree::x(), Tree::y(), Tree::z()];
+    let (u, v) = (&axes[iu], &axes[iv]);
+    let sector = TAU / f64::from(n);
+    let angle = v.atan2(u.clone());
+    let id = (angle.clone() / sector).round();
+    let next = id.clone() + (angle - id.clone() * sector).compare(0.0);
+
+    let [a, b] = [id, next].map(|id| {
+        // rotate the point back by the copy's angle; the branch cut of atan2
+        // doesn't matter, since angles a full turn apart give the same copy
+        let t = id * sector;
+        let (c, s) = (t.cos(), t.sin());
+        let mut p = axes.clone();
+        p[iu] = u.clone() * c.clone() + v.clone() * s.clone();
+        p[iv] = v.clone() * c - u.clone() * s;
+        let [x, y, z] = p;
+        tree.remap_xyz(x, y, z)
+    });
+    Ok(a.min(b))
+}
+
+/// Bounds of a polar array built by [`polar`]
+pub fn polar_bounds(b: Bounds, n: u32, axis: &str) -> Result<Bounds, String> {
+    let [iu, iv, _] = parse_axis(axis)?;
+    if n == 0 {
+        return Err("polar_repeat needs at least one copy".to_owned());
+    }
+    let mut ranges = [(b.0, b.1), (b.2, b.3), (b.4, b.5)];
+    if n > 1 {
+        // every copy lies within the circle around the original's bounds
+        let reach = |(lo, hi): (f64, f64)| lo.abs().max(hi.abs());
+        let r = reach(ranges[iu]).hypot(reach(ranges[iv]));
+        ranges[iu] = (-r, r);
+        ranges[iv] = (-r, r);
+    }
+    Ok((
+        ranges[0].0,
+        ranges[0].1,
+        ranges[1].0,
+        ranges[1].1,
+        ranges[2].0,
+        ranges[2].1,
+    ))
+}
+
+#[pyfunction(name = "repeat_bounds")]
+#[pyo3(signature = (bounds, spacing, count=None, mirror=false))]
+fn py_repeat_bounds(
+    bounds: Bounds,
+    spacing: (f64, f64, f64),
+    count: Option<(u32, u32, u32)>,
+    mirror: bool,
+) -> PyResult<Bounds> {
+    let spacing = [spacing.0, spacing.1, spacing.2];
+    let count = count.map(|c| [c.0, c.1, c.2]);
+    match linear_bounds(bounds, spacing, count, mirror) {
+        Ok(b) => Ok(b),
+        Err(e) => Err(PyRuntimeError::new_err(e)),
+    }
+}
+
+#[pyfunction(name = "polar_repeat_bounds")]
+#[pyo3(signature = (bounds, n, axis="z"))]
+fn py_polar_repeat_bounds(bounds: Bounds, n: u32, axis: &str) -> PyResult<Bounds> {
+    match polar_bounds(bounds, n, axis) {
+        Ok(b) => Ok(b),
+        Err(e) => Err(PyRuntimeError::new_err(e)),
+    }
+}
+
+/// Adds the `patterns` submodule to `m`
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let sub = PyModule::new(m.py(), "patterns")?;
+    sub.add_function(wrap_pyfunction!(py_repeat_bounds, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(py_polar_repeat_bounds, &sub)?)?;
+    m.add_submodule(&sub)
+}
@@ -0,0 +1,208 @@
+//! Compact binary serialization of trees.
+//!
+//! The format is a deduplicated DAG: every distinct `TreeOp` is written once,
+//! children before parents, and refers to its children by index. The last
+//! node is the root.
+//!
+//! ```text
+//! magic    b"FPTR"
+//! version  u32
+//! count    u32
+//! nodes    count × (tag: u8, payload)
+//! ```
+//!
+//! All integers and floats are little-endian. Variables other than x, y and
+//! z are stored by name (see [`vars`](crate::vars)).
+use crate::{structure, vars};
+use fidget::{
+    context::{BinaryOpcode, Tree, TreeOp, UnaryOpcode},
+    var::Var,
+};
+use std::{collections::HashMap, sync::Arc};
+
+const MAGIC: &[u8; 4] = b"FPTR";
+const VERSION: u32 = 1;
+
+const TAG_CONST: u8 = 0;
+const TAG_X: u8 = 1;
+const TAG_Y: u8 = 2;
+const TAG_Z: u8 = 3;
+const TAG_VAR: u8 = 4;
+const TAG_UNARY: u8 = 5;
+const TAG_BINARY: u8 = 6;
+const TAG_REMAP: u8 = 7;
+
+/// Unary opcodes, indexed by their serialized value
+const UNARY: [UnaryOpcode; 17] = [
+    UnaryOpcode::Neg,
+    UnaryOpcode::Abs,
+    UnaryOpcode::Recip,
+    UnaryOpcode::Sqrt,
+    UnaryOpcode::Square,
+    UnaryOpcode::Floor,
+    UnaryOpcode::Ceil,
+    UnaryOpcode::Round,
+    UnaryOpcode::Sin,
+    UnaryOpcode::Cos,
+    UnaryOpcode::Tan,
+    UnaryOpcode::Asin,
+    UnaryOpcode::Acos,
+    UnaryOpcode::Atan,
+    UnaryOpcode::Exp,
+    UnaryOpcode::Ln,
+    UnaryOpcode::Not,
+];
+
+/// Binary opcodes, indexed by their serialized value
+const BINARY: [BinaryOpcode; 11] = [
+    BinaryOpcode::Add,
+    BinaryOpcode::Sub,
+    BinaryOpcode::Mul,
+    BinaryOpcode::Div,
+    BinaryOpcode::Atan,
+    BinaryOpcode::Min,
+    BinaryOpcode::Max,
+    BinaryOpcode::Compare,
+    BinaryOpcode::Mod,
+    BinaryOpcode::And,
+    BinaryOpcode::Or,
+];
+
+/// Serializes a tree, writing shared subtrees only once
+pub fn to_bytes(tree: &Tree) -> Vec<u8> {
+    let mut index: HashMap<*const TreeOp, u32> = HashMap::new();
+    let mut body = vec![];
+    for op in structure::postorder(tree) {
+        let idx = |c: &Arc<TreeOp>| index[&Arc::as_ptr(c)].to_le_bytes();
+        match op {
+            TreeOp::Const(c) => {
+                body.push(TAG_CONST);
+                body.extend(c.to_le_bytes());
+            }
+            TreeOp::Input(Var::X) => body.push(TAG_X),
+            TreeOp::Input(Var::Y) => body.push(TAG_Y),
+            TreeOp::Input(Var::Z) => body.push(TAG_Z),
+            TreeOp::Input(v) => {
+                let name = vars::name(*v);
+                body.push(TAG_VAR);
+                body.extend((name.len() as u32).to_le_bytes());
+                body.extend(name.as_bytes());
+            }
+            TreeOp::Unary(o, a) => {
+                body.push(TAG_UNARY);
+                body.push(UNARY.iter().position(|u| u == o).unwrap() as u8);
+                body.extend(idx(a));
+            }
+            TreeOp::Binary(o, a, b) => {
+                body.push(TAG_BINARY);
+                body.push(BINARY.iter().position(|u| u == o).unwrap() as u8);
+                body.extend(idx(a));
+                body.extend(idx(b));
+            }
+            TreeOp::RemapAxes { target, x, y, z } => {
+                body.push(TAG_REMAP);
+                for c in [target, x, y, z] {
+                    body.extend(idx(c));
+                }
+            }
+        }
+        let n = index.len() as u32;
+        index.insert(op, n);
+    }
+    let mut out = MAGIC.to_vec();
+    out.extend(VERSION.to_le_bytes());
+    out.extend((index.len() as u32).to_le_bytes());
+    out.extend(body);
+    out
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        match self.data.get(self.pos..self.pos + n) {
+            Some(b) => {
+                self.pos += n;
+                Ok(b)
+            }
+            None => Err("unexpected end of data".to_owned()),
+        }
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// Deserializes a tree written by [`to_bytes`]
+pub fn from_bytes(data: &[u8]) -> Result<Tree, String> {
+    let mut r = Reader { data, pos: 0 };
+    if r.take(4).ok() != Some(&MAGIC[..]) {
+        return Err("not a serialized tree".to_owned());
+    }
+    let version = r.u32()?;
+    if version != VERSION {
+        return Err(format!("unsupported tree format version {version}"));
+    }
+    let count = r.u32()? as usize;
+    let mut nodes: Vec<Arc<TreeOp>> = Vec::with_capacity(count.min(data.len()));
+    for _ in 0..count {
+        let child = |r: &mut Reader| {
+            let i = r.u32()? as usize;
+            match nodes.get(i) {
+                Some(n) => Ok(n.clone()),
+                None => Err(format!("invalid node index {i}")),
+            }
+        };
+        let op = match r.u8()? {
+            TAG_CONST => TreeOp::Const(r.f64()?),
+            TAG_X => TreeOp::Input(Var::X),
+            TAG_Y => TreeOp::Input(Var::Y),
+            TAG_Z => TreeOp::Input(Var::Z),
+            TAG_VAR => {
+                let len = r.u32()? as usize;
+                match std::str::from_utf8(r.take(len)?) {
+                    Ok(name) => TreeOp::Input(vars::lookup(name)),
+                    Err(..) => return Err("invalid variable name".to_owned()),
+                }
+            }
+            TAG_UNARY => {
+                let o = r.u8()?;
+                let Some(o) = UNARY.get(o as usize) else {
+                    return Err(format!("invalid unary opcode {o}"));
+                };
+                TreeOp::Unary(*o, child(&mut r)?)
+            }
+            TAG_BINARY => {
+                let o = r.u8()?;
+                let Some(o) = BINARY.get(o as usize) else {
+                    return Err(format!("invalid binary opcode {o}"));
+                };
+                TreeOp::Binary(*o, child(&mut r)?, child(&mut r)?)
+            }
+            TAG_REMAP => TreeOp::RemapAxes {
+                target: child(&mut r)?,
+                x: child(&mut r)?,
+                y: child(&mut r)?,
+                z: child(&mut r)?,
+            },
+            t => return Err(format!("invalid node tag {t}")),
+        };
+        nodes.push(Arc::new(op));
+    }
+    if r.pos != data.len() {
+        return Err("trailing data after tree".to_owned());
+    }
+    match nodes.last() {
+        Some(root) => Ok(structure::to_tree(root)),
+        None => Err("serialized tree is empty".to_owned()),
+    }
+}
@@ -0,0 +1,546 @@
+//! Primitive shapes, built directly as trees.
+//!
+//! Every function returns a tree along with its bounding box, as
+//! `(xmin, xmax, ymin, ymax, zmin, zmax)`; 2D shapes are unbounded along z.
+//! Fields are exact distances unless noted otherwise. Branches in the
+//! reference formulas are written with `compare`, which evaluates to the sign
+//! of the difference of its arguments, so they can be evaluated (and bounded
+//! with interval arithmetic) like any other operation.
+//!
+//! A few fields clamp against a tiny `eps` rather than zero, following the
+//! Python implementations that these replace: `length(max(q, 0))` takes the
+//! square root of zero inside the shape, whose derivative is infinite and
+//! breaks the normals used when meshing.
+use crate::PyTree;
+use fidget::context::Tree;
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+
+type Bounds = (f64, f64, f64, f64, f64, f64);
+
+fn length2(a: Tree, b: Tree) -> Tree {
+    (a.square() + b.square()).sqrt()
+}
+
+fn length3(a: Tree, b: Tree, c: Tree) -> Tree {
+    (a.square() + b.square() + c.square()).sqrt()
+}
+
+fn clamp(t: Tree, lo: f64, hi: f64) -> Tree {
+    t.max(lo).min(hi)
+}
+
+/// Returns 1 if `a > b` and 0 otherwise (including when they're equal)
+fn greater(a: Tree, b: impl Into<Tree>) -> Tree {
+    a.compare(b).max(0.0)
+}
+
+/// Returns 1 if `a >= b` and 0 otherwise
+fn greater_or_equal(a: Tree, b: impl Into<Tree>) -> Tree {
+    (a.compare(b) + 1.0).min(1.0)
+}
+
+fn check(ok: bool, msg: &str) -> PyResult<()> {
+    if ok {
+        Ok(())
+    } else {
+        Err(PyRuntimeError::new_err(msg.to_owned()))
+    }
+}
+
+/// Returns an exact field, along with its bounds
+fn output(tree: Tree, bounds: Bounds) -> (PyTree, Bounds) {
+    (PyTree::with_exactness(tree, true), bounds)
+}
+
+/// Exact signed distance to a 2D polygon in the (u, v) plane
+///
+/// The distance is the minimum over all edges; the sign is flipped once for
+/// every edge crossed by a ray from the point, which works for concave and
+/// self-intersecting polygons alike (using the even-odd rule).
+fn polygon_field(u: &Tree, v: &Tree, points: &[(f64, f64)]) -> Tree {
+    let mut dist: Option<Tree> = None;
+    let mut sign = Tree::constant(1.0);
+    for (i, &(ui, vi)) in points.iter().enumerate() {
+        let (uj, vj) = points[(i + points.len() - 1) % points.len()];
+        let (eu, ev) = (uj - ui, vj - vi);
+        let (wu, wv) = (u.clone() - ui, v.clone() - vi);
+        let len2 = eu * eu + ev * ev;
+        let t = if len2 > 0.0 {
+            clamp((wu.clone() * eu + wv.clone() * ev) / len2, 0.0, 1.0)
+        } else {
+            Tree::constant(0.0)
+        };
+        let d = (wu.clone() - t.clone() * eu).square() + (wv.clone() - t * ev).square();
+        dist = Some(match dist {
+            Some(prev) => prev.min(d),
+            None => d,
+        });
+
+        // the edge crosses a ray from the point if these are all true or
+        // all false
+        let a = greater_or_equal(v.clone(), vi);
+        let b = greater(Tree::constant(vj), v.clone());
+        let c = greater(wv * eu, wu * ev);
+        let all = a.clone() * b.clone() * c.clone();
+        let none = (1.0 - a) * (1.0 - b) * (1.0 - c);
+        sign *= 1.0 - 2.0 * (all + none);
+    }
+    dist.unwrap().sqrt() * sign
+}
+
+/// Exactly extrudes a 2D field `d` along `w`, from `-half` to `half`
+fn extrude(d: Tree, w: Tree, half: f64) -> Tree {
+    let eps = half * 1e-6;
+    let wz = w.abs() - half;
+    (d.clone().max(wz.clone())).min(eps) + length2(d.max(eps), wz.max(eps))
+}
+
+/// A sphere with radius `r`, centered at the origin
+#[pyfunction]
+fn sphere(r: f64) -> PyResult<(PyTree, Bounds)> {
+    check(r > 0.0, "radius must be positive")?;
+    let (x, y, z) = Tree::axes();
+    Ok(output(length3(x, y, z) - r, (-r, r, -r, r, -r, r)))
+}
+
+/// A 2D circle with radius `r`, centered at the origin
+#[pyfunction]
+fn circle(r: f64) -> PyResult<(PyTree, Bounds)> {
+    check(r > 0.0, "radius must be positive")?;
+    let (x, y, _) = Tree::axes();
+    let inf = f64::INFINITY;
+    Ok(output(length2(x, y) - r, (-r, r, -r, r, -inf, inf)))
+}
+
+/// A box with side lengths `lx`, `ly` and `lz`, centered at the origin
+#[pyfunction]
+#[pyo3(name = "box")]
+fn box_(lx: f64, ly: f64, lz: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        lx > 0.0 && ly > 0.0 && lz > 0.0,
+        "side lengths must be positive",
+    )?;
+    let eps = lx.min(ly).min(lz) * 1e-6;
+    let (x, y, z) = Tree::axes();
+    let qx = x.abs() - lx / 2.0;
+    let qy = y.abs() - ly / 2.0;
+    let qz = z.abs() - lz / 2.0;
+    let outside = length3(
+        qx.clone().max(eps),
+        qy.clone().max(eps),
+        qz.clone().max(eps),
+    );
+    let inside = qx.max(qy).max(qz).min(eps);
+    let (hx, hy, hz) = (lx / 2.0, ly / 2.0, lz / 2.0);
+    Ok(output(outside + inside, (-hx, hx, -hy, hy, -hz, hz)))
+}
+
+/// A 2D rectangle with side lengths `lx` and `ly`, centered at the origin
+#[pyfunction]
+fn rectangle(lx: f64, ly: f64) -> PyResult<(PyTree, Bounds)> {
+    check(lx > 0.0 && ly > 0.0, "side lengths must be positive")?;
+    let eps = lx.min(ly) * 1e-6;
+    let (x, y, _) = Tree::axes();
+    let qx = x.abs() - lx / 2.0;
+    let qy = y.abs() - ly / 2.0;
+    let outside = length2(qx.clone().max(eps), qy.clone().max(eps));
+    let inside = qx.max(qy).min(eps);
+    let (hx, hy, inf) = (lx / 2.0, ly / 2.0, f64::INFINITY);
+    Ok(output(outside + inside, (-hx, hx, -hy, hy, -inf, inf)))
+}
+
+/// A torus around the z axis, centered at the origin
+#[pyfunction]
+fn torus(major_radius: f64, minor_radius: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        major_radius > 0.0 && minor_radius > 0.0,
+        "radii must be positive",
+    )?;
+    let (x, y, z) = Tree::axes();
+    let df = length2(length2(x, y) - major_radius, z) - minor_radius;
+    let r = major_radius + minor_radius;
+    let m = minor_radius;
+    Ok(output(df, (-r, r, -r, r, -m, m)))
+}
+
+/// A cylinder around the z axis, spanning `-height` to `height` along z
+#[pyfunction]
+fn cylinder(radius: f64, height: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        radius > 0.0 && height > 0.0,
+        "radius and height must be positive",
+    )?;
+    let (x, y, z) = Tree::axes();
+    let dx = length2(x, y) - radius;
+    let dz = z.abs() - height;
+    let inside = dx.clone().max(dz.clone()).min(radius * 1e-6);
+    let outside = length2(dx.max(height * 1e-6), dz.max(height * 1e-6));
+    Ok(output(
+        inside + outside,
+        (-radius, radius, -radius, radius, -height, height),
+    ))
+}
+
+/// A cone around the z axis, with its base of radius `radius` at z = 0 and
+/// its tip at z = `height`
+///
+/// If `top_radius` is non-zero, the tip is cut off by a disk of that radius.
+#[pyfunction]
+#[pyo3(signature = (radius, height, top_radius=0.0))]
+fn cone(radius: f64, height: f64, top_radius: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        radius > 0.0 && height > 0.0,
+        "radius and height must be positive",
+    )?;
+    check(top_radius >= 0.0, "top radius must not be negative")?;
+    let (x, y, z) = Tree::axes();
+    // capped cone, centered on its mid-height; see
+    // https://iquilezles.org/articles/distfunctions/
+    let h = height / 2.0;
+    let (r1, r2) = (radius, top_radius);
+    let qx = length2(x, y);
+    let qy = z - h;
+    let below = greater(Tree::constant(0.0), qy.clone());
+    let cap_radius = r2 + (r1 - r2) * below;
+    let cax = qx.clone() - qx.clone().min(cap_radius);
+    let cay = qy.clone().abs() - h;
+    let (k1x, k1y) = (r2, h);
+    let (k2x, k2y) = (r2 - r1, 2.0 * h);
+    let k2_len2 = k2x * k2x + k2y * k2y;
+    let t = clamp(
+        ((k1x - qx.clone()) * k2x + (k1y - qy.clone()) * k2y) / k2_len2,
+        0.0,
+        1.0,
+    );
+    let cbx = qx - k1x + t.clone() * k2x;
+    let cby = qy - k1y + t * k2y;
+    let inside =
+        greater(Tree::constant(0.0), cbx.clone()) * greater(Tree::constant(0.0), cay.clone());
+    let sign = 1.0 - 2.0 * inside;
+    let d2 = (cax.square() + cay.square()).min(cbx.square() + cby.square());
+    let r = r1.max(r2);
+    Ok(output(sign * d2.sqrt(), (-r, r, -r, r, 0.0, height)))
+}
+
+/// A capsule: all points within `radius` of the segment from `a` to `b`
+#[pyfunction]
+fn capsule(a: (f64, f64, f64), b: (f64, f64, f64), radius: f64) -> PyResult<(PyTree, Bounds)> {
+    check(radius > 0.0, "radius must be positive")?;
+    let (x, y, z) = Tree::axes();
+    let (bx, by, bz) = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    let (px, py, pz) = (x - a.0, y - a.1, z - a.2);
+    let len2 = bx * bx + by * by + bz * bz;
+    let t = if len2 > 0.0 {
+        clamp(
+            (px.clone() * bx + py.clone() * by + pz.clone() * bz) / len2,
+            0.0,
+            1.0,
+        )
+    } else {
+        Tree::constant(0.0)
+    };
+    let df = length3(px - t.clone() * bx, py - t.clone() * by, pz - t * bz) - radius;
+    let r = radius;
+    let bounds = (
+        a.0.min(b.0) - r,
+        a.0.max(b.0) + r,
+        a.1.min(b.1) - r,
+        a.1.max(b.1) + r,
+        a.2.min(b.2) - r,
+        a.2.max(b.2) + r,
+    );
+    Ok(output(df, bounds))
+}
+
+/// An ellipsoid with semi-axes `rx`, `ry` and `rz`, centered at the origin
+///
+/// There's no closed form for the distance to an ellipsoid, so this is an
+/// approximation which is exact on the surface and at the center.
+#[pyfunction]
+fn ellipsoid(rx: f64, ry: f64, rz: f64) -> PyResult<(PyTree, Bounds)> {
+    check(rx > 0.0 && ry > 0.0 && rz > 0.0, "radii must be positive")?;
+    let (x, y, z) = Tree::axes();
+    let (nx, ny, nz) = (x.clone() / rx, y.clone() / ry, z.clone() / rz);
+    let k0_2 = nx.square() + ny.square() + nz.square();
+    let k1_2 = (x / (rx * rx)).square() + (y / (ry * ry)).square() + (z / (rz * rz)).square();
+    // k0 * (k0 - 1) / k1, with the ratio k0 / k1 kept finite at the center
+    // (where it tends towards the smallest radius)
+    let eps = 1e-12;
+    let r_min = rx.min(ry).min(rz);
+    let ratio = ((k0_2.clone() + eps) / (k1_2 + eps / (r_min * r_min))).sqrt();
+    let df = (k0_2.sqrt() - 1.0) * ratio;
+    Ok((PyTree::from(df), (-rx, rx, -ry, ry, -rz, rz)))
+}
+
+/// A hexagonal prism around the z axis, spanning `-height` to `height`
+/// along z
+///
+/// `radius` is the distance from the axis to the middle of each side; two
+/// sides are parallel to the x axis.
+#[pyfunction]
+fn hexagonal_prism(radius: f64, height: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        radius > 0.0 && height > 0.0,
+        "radius and height must be positive",
+    )?;
+    let (x, y, z) = Tree::axes();
+    // see https://iquilezles.org/articles/distfunctions/
+    let (kx, ky, kz) = (-(3f64.sqrt()) / 2.0, 0.5, 1.0 / 3f64.sqrt());
+    let (px, py) = (x.abs(), y.abs());
+    let fold = (px.clone() * kx + py.clone() * ky).min(0.0) * 2.0;
+    let px = px - fold.clone() * kx;
+    let py = py - fold * ky;
+    let edge = kz * radius;
+    let dx = length2(px.clone() - clamp(px, -edge, edge), py.clone() - radius) * py.compare(radius);
+    let dz = z.abs() - height;
+    let inside = dx.clone().max(dz.clone()).min(radius * 1e-6);
+    let outside = length2(dx.max(radius * 1e-6), dz.max(height * 1e-6));
+    let rx = 2.0 * radius / 3f64.sqrt();
+    Ok(output(
+        inside + outside,
+        (-rx, rx, -radius, radius, -height, height),
+    ))
+}
+
+/// A wedge with side lengths `lx`, `ly` and `lz`, centered at the origin
+///
+/// This is the box of the same size cut in half diagonally, keeping the
+/// part below the plane through its top edge at -x and its bottom edge at +x.
+#[pyfunction]
+fn wedge(lx: f64, ly: f64, lz: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        lx > 0.0 && ly > 0.0 && lz > 0.0,
+        "side lengths must be positive",
+    )?;
+    let (x, y, z) = Tree::axes();
+    let (hx, hy, hz) = (lx / 2.0, ly / 2.0, lz / 2.0);
+    let profile = polygon_field(&x, &z, &[(-hx, -hz), (hx, -hz), (-hx, hz)]);
+    Ok(output(extrude(profile, y, hy), (-hx, hx, -hy, hy, -hz, hz)))
+}
+
+/// A box with side lengths `lx`, `ly` and `lz` and edges rounded with
+/// `radius`, centered at the origin
+#[pyfunction]
+fn rounded_box(lx: f64, ly: f64, lz: f64, radius: f64) -> PyResult<(PyTree, Bounds)> {
+    check(
+        lx > 0.0 && ly > 0.0 && lz > 0.0,
+        "side lengths must be positive",
+    )?;
+    check(
+        radius >= 0.0 && 2.0 * radius <= lx.min(ly).min(lz),
+        "radius must be between 0 and half the shortest side",
+    )?;
+    let eps = lx.min(ly).min(lz) * 1e-6;
+    let (x, y, z) = Tree::axes();
+    let (hx, hy, hz) = (lx / 2.0, ly / 2.0, lz / 2.0);
+    let qx = x.abs() - (hx - radius);
+    let qy = y.abs() - (hy - radius);
+    let qz = z.abs() - (hz - radius);
+    let outside = length3(
+        qx.clone().max(eps),
+        qy.clone().max(eps),
+        qz.clone().max(eps),
+    );
+    let inside = qx.max(qy).max(qz).min(eps);
+    Ok(output(
+        outside + inside - radius,
+        (-hx, hx, -hy, hy, -hz, hz),
+    ))
+}
+
+/// A 2D polygon with the given vertices, which may be concave
+#[pyfunction]
+fn polygon(points: Vec<(f64, f64)>) -> PyResult<(PyTree, Bounds)> {
+    check(points.len() >= 3, "a polygon needs at least 3 points")?;
+    check(
+        points.iter().all(|(u, v)| u.is_finite() && v.is_finite()),
+        "polygon points must be finite",
+    )?;
+    let (x, y, _) = Tree::axes();
+    let df = polygon_field(&x, &y, &points);
+    let (mut xmin, mut xmax) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut ymin, mut ymax) = (f64::INFINITY, f64::NEG_INFINITY);
+    for &(u, v) in &points {
+        (xmin, xmax) = (xmin.min(u), xmax.max(u));
+        (ymin, ymax) = (ymin.min(v), ymax.max(v));
+    }
+    let inf = f64::INFINITY;
+    Ok(output(df, (xmin, xmax, ymin, ymax, -inf, inf)))
+}
+
+/// Extrudes a 2D profile in the xy plane (with the given bounds) by
+/// `height` along `axis`, turning it by `twist` degrees and scaling it by
+/// `taper` from the bottom to the top
+///
+/// The extrusion starts at the origin, or is centered on it if `center` is
+/// set. A straight extrusion keeps an exact profile exact. Twisting and
+/// tapering stretch the field along the axis, so it's divided by a bound on
+/// its gradient, which grows with the distance `r` from the axis: with twist
+/// rate `k` and taper rate `s'`, the derivative along the axis is at most
+/// `|k| r + |s'| (2 r / s_min + r0)`, where `r0` bounds the profile. `r` is
+/// taken to be at least the profile's radius, so the bound also holds along
+/// the path to the nearest surface point.
+#[pyfunction(name = "extrude")]
+#[pyo3(signature = (profile, bounds, height, axis=None, twist=0.0, taper=1.0, center=false))]
+fn extrude_profile(
+    profile: PyRef<PyTree>,
+    bounds: Bounds,
+    height: f64,
+    axis: Option<Bound<PyAny>>,
+    twist: f64,
+    taper: f64,
+    center: bool,
+) -> PyResult<(PyTree, Bounds)> {
+    check(
+        height > 0.0 && height.is_finite(),
+        "height must be positive",
+    )?;
+    check(taper > 0.0 && taper.is_finite(), "taper must be positive")?;
+    check(twist.is_finite(), "twist must be finite")?;
+    let frame = match &axis {
+        Some(a) => crate::transform::frame(a)?,
+        None => nalgebra::Matrix4::identity(),
+    };
+    let straight = twist == 0.0 && taper == 1.0;
+    let (xmin, xmax, ymin, ymax, ..) = bounds;
+    let reach = |lo: f64, hi: f64| lo.abs().max(hi.abs());
+    let r0 = reach(xmin, xmax).hypot(reach(ymin, ymax));
+    check(
+        straight || r0.is_finite(),
+        "twisted or tapered profiles must have finite x and y bounds",
+    )?;
+
+    let (x, y, z) = Tree::axes();
+    let (w0, w1) = if center {
+        (-height / 2.0, height / 2.0)
+    } else {
+        (0.0, height)
+    };
+    let d2 = if straight {
+        profile
+            ._val
+            .remap_xyz(x.clone(), y.clone(), Tree::constant(0.0))
+    } else {
+        // fraction of the way up, held constant past the ends so that the
+        // caps see the end profiles
+        let t = clamp((z.clone() - w0) / height, 0.0, 1.0);
+        let angle = t.clone() * twist.to_radians();
+        let scale = t * (taper - 1.0) + 1.0;
+        let (c, s) = (angle.cos(), angle.sin());
+        let u = (x.clone() * c.clone() + y.clone() * s.clone()) / scale.clone();
+        let v = (y.clone() * c - x.clone() * s) / scale.clone();
+        let warped = profile._val.remap_xyz(u, v, Tree::constant(0.0)) * scale;
+
+        let k = twist.to_radians().abs() / height;
+        let ds = (taper - 1.0).abs() / height;
+        let r = length2(x, y).max(r0 * taper.max(1.0));
+        let g = r.clone() * k + (r * (2.0 / taper.min(1.0)) + r0) * ds;
+        warped / (g.square() + 1.0).sqrt()
+    };
+    let local = extrude(d2, z - (w0 + w1) / 2.0, height / 2.0);
+    let tree = crate::transform::apply(&local, &frame).map_err(PyRuntimeError::new_err)?;
+
+    let local_bounds = if twist == 0.0 {
+        let lo = |v: f64| v.min(v * taper);
+        let hi = |v: f64| v.max(v * taper);
+        (lo(xmin), hi(xmax), lo(ymin), hi(ymax), w0, w1)
+    } else {
+        // every twisted slice lies within the circle around the profile
+        let r = r0 * taper.max(1.0);
+        (-r, r, -r, r, w0, w1)
+    };
+    let exact = profile.exact && straight;
+    Ok((
+        PyTree::with_exactness(tree, exact),
+        crate::transform::bounds(&frame, local_bounds),
+    ))
+}
+
+/// Sweeps a 2D profile in the xy plane (with the given bounds) along a path
+/// through `points`
+///
+/// The path is a polyline, or a spline sampled with `samples` pieces per
+/// segment (see [`crate::sweep`]). The profile's origin follows the path,
+/// and its x and y axes are carried along by `frame`; open ends are cut flat.
+/// Joints are mitred, so the profile must be small compared to the path's
+/// bends. The result is a bound.
+#[pyfunction(name = "sweep")]
+#[pyo3(signature = (profile, bounds, points, closed=false, frame="rmf", curve="polyline", samples=8))]
+fn sweep_profile(
+    profile: PyRef<PyTree>,
+    bounds: Bounds,
+    points: Vec<(f64, f64, f64)>,
+    closed: bool,
+    frame: &str,
+    curve: &str,
+    samples: usize,
+) -> PyResult<(PyTree, Bounds)> {
+    check(
+        points
+            .iter()
+            .all(|p| p.0.is_finite() && p.1.is_finite() && p.2.is_finite()),
+        "path points must be finite",
+    )?;
+    let (xmin, xmax, ymin, ymax, ..) = bounds;
+    let reach = |lo: f64, hi: f64| lo.abs().max(hi.abs());
+    let r0 = reach(xmin, xmax).hypot(reach(ymin, ymax));
+    check(
+        r0.is_finite(),
+        "swept profiles must have finite x and y bounds",
+    )?;
+
+    let points: Vec<_> = points
+        .into_iter()
+        .map(|(x, y, z)| nalgebra::Vector3::new(x, y, z))
+        .collect();
+    let swept = crate::sweep::Frames::parse(frame).and_then(|frames| {
+        let curve = crate::sweep::Curve::parse(curve)?;
+        let path = crate::sweep::sample(&points, closed, curve, samples)?;
+        let (tree, r) = crate::sweep::sweep(&profile._val, r0, &path, closed, frames)?;
+        Ok((tree, path, r))
+    });
+    let (tree, path, r) = match swept {
+        Ok(s) => s,
+        Err(e) => return Err(PyRuntimeError::new_err(e)),
+    };
+    let mut b = (
+        f64::INFINITY,
+        -f64::INFINITY,
+        f64::INFINITY,
+        -f64::INFINITY,
+        f64::INFINITY,
+        -f64::INFINITY,
+    );
+    for p in &path {
+        b = (
+            b.0.min(p.x - r),
+            b.1.max(p.x + r),
+            b.2.min(p.y - r),
+            b.3.max(p.y + r),
+            b.4.min(p.z - r),
+            b.5.max(p.z + r),
+        );
+    }
+    Ok((PyTree::from(tree), b))
+}
+
+/// Adds the `shapes` submodule to `m`
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let sub = PyModule::new(m.py(), "shapes")?;
+    sub.add_function(wrap_pyfunction!(sphere, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(circle, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(box_, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(rectangle, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(torus, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(cylinder, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(cone, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(capsule, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(ellipsoid, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(hexagonal_prism, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(wedge, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(rounded_box, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(polygon, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(extrude_profile, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(sweep_profile, &sub)?)?;
+    m.add_submodule(&sub)
+}
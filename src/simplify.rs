@@ -0,0 +1,103 @@
+//! Algebraic simplification of trees.
+//!
+//! Most of the work is done by the context itself: importing a tree
+//! flattens `remap_xyz` layers and deduplicates identical subexpressions,
+//! and the builders used by [`fold`](crate::fold) fold constants and remove
+//! identities such as `x * 1`, `x + 0` and `min(x, x)`. The rules here add
+//! rewrites that the builders don't know about, like removing double
+//! negation. Every rule is exact for all inputs, including infinities and
+//! NaN, so a simplified tree evaluates to the same values as the original.
+use crate::fold;
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    Error,
+};
+use std::collections::HashMap;
+
+/// Builds a unary operation, applying simplification rules first
+fn unary(ctx: &mut Context, op: UnaryOpcode, a: Node) -> Result<Node, Error> {
+    let op_a = *ctx.get_op(a).ok_or(Error::BadNode)?;
+    match (op, op_a) {
+        // -(-a) = a
+        (UnaryOpcode::Neg, Op::Unary(UnaryOpcode::Neg, b)) => Ok(b),
+        // |-a| = |a|, (-a)² = a²
+        (UnaryOpcode::Abs | UnaryOpcode::Square, Op::Unary(UnaryOpcode::Neg, b)) => {
+            unary(ctx, op, b)
+        }
+        // ||a|| = |a|, |a²| = a²
+        (UnaryOpcode::Abs, Op::Unary(UnaryOpcode::Abs | UnaryOpcode::Square, _)) => Ok(a),
+        // |a|² = a²
+        (UnaryOpcode::Square, Op::Unary(UnaryOpcode::Abs, b)) => unary(ctx, op, b),
+        // rounding functions are idempotent
+        (
+            UnaryOpcode::Floor | UnaryOpcode::Ceil | UnaryOpcode::Round,
+            Op::Unary(UnaryOpcode::Floor | UnaryOpcode::Ceil | UnaryOpcode::Round, _),
+        ) => Ok(a),
+        _ => fold::unary(ctx, op, a),
+    }
+}
+
+/// Builds a binary operation, applying simplification rules first
+fn binary(ctx: &mut Context, op: BinaryOpcode, a: Node, b: Node) -> Result<Node, Error> {
+    let op_a = *ctx.get_op(a).ok_or(Error::BadNode)?;
+    let op_b = *ctx.get_op(b).ok_or(Error::BadNode)?;
+    let is_minus_one = |op: Op| matches!(op, Op::Const(c) if c.0 == -1.0);
+    match (op, op_a, op_b) {
+        // a + (-b) = a - b, (-a) + b = b - a
+        (BinaryOpcode::Add, _, Op::Unary(UnaryOpcode::Neg, c)) => {
+            binary(ctx, BinaryOpcode::Sub, a, c)
+        }
+        (BinaryOpcode::Add, Op::Unary(UnaryOpcode::Neg, c), _) => {
+            binary(ctx, BinaryOpcode::Sub, b, c)
+        }
+        // a - (-b) = a + b
+        (BinaryOpcode::Sub, _, Op::Unary(UnaryOpcode::Neg, c)) => {
+            binary(ctx, BinaryOpcode::Add, a, c)
+        }
+        // a * -1 = -a, a / -1 = -a
+        (BinaryOpcode::Mul | BinaryOpcode::Div, _, _) if is_minus_one(op_b) => {
+            unary(ctx, UnaryOpcode::Neg, a)
+        }
+        (BinaryOpcode::Mul, _, _) if is_minus_one(op_a) => unary(ctx, UnaryOpcode::Neg, b),
+        // (-a) * (-b) = a * b, (-a) / (-b) = a / b
+        (
+            BinaryOpcode::Mul | BinaryOpcode::Div,
+            Op::Unary(UnaryOpcode::Neg, c),
+            Op::Unary(UnaryOpcode::Neg, d),
+        ) => binary(ctx, op, c, d),
+        // min(a, min(a, b)) = min(a, b), and likewise for max
+        (BinaryOpcode::Min | BinaryOpcode::Max, _, Op::Binary(inner, c, d))
+            if inner == op && (c == a || d == a) =>
+        {
+            Ok(b)
+        }
+        (BinaryOpcode::Min | BinaryOpcode::Max, Op::Binary(inner, c, d), _)
+            if inner == op && (c == b || d == b) =>
+        {
+            Ok(a)
+        }
+        _ => fold::binary(ctx, op, a, b),
+    }
+}
+
+/// Copies the graph below `root` from `src` into `dst`, simplifying it along
+/// the way, and returns the new root
+pub fn simplify(src: &Context, root: Node, dst: &mut Context) -> Result<Node, Error> {
+    let mut seen: HashMap<Node, Node> = HashMap::new();
+    for node in fold::nodes(src, root)? {
+        let out = match *src.get_op(node).ok_or(Error::BadNode)? {
+            Op::Input(v) => dst.var(v),
+            Op::Const(c) => dst.constant(c.0),
+            Op::Unary(op, a) => unary(dst, op, seen[&a])?,
+            Op::Binary(op, a, b) => binary(dst, op, seen[&a], seen[&b])?,
+        };
+        seen.insert(node, out);
+    }
+    Ok(seen[&root])
+}
+
+/// Counts the distinct operations in a tree, without deduplicating
+/// structurally identical subtrees or expanding `remap_xyz`
+pub fn tree_size(tree: &Tree) -> usize {
+    crate::structure::postorder(tree).len()
+}
@@ -0,0 +1,94 @@
+//! Size and cost statistics for trees.
+use crate::{fold, vars, vm};
+use fidget::{
+    compiler::{RegTape, SsaTape},
+    context::{BinaryOpcode, Context, Op, Tree},
+    Error,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Number of registers available to the JIT before it spills to memory
+///
+/// This mirrors the (private) limit used by `fidget::jit`.
+#[cfg(target_arch = "aarch64")]
+const JIT_REGISTERS: usize = 24;
+#[cfg(not(target_arch = "aarch64"))]
+const JIT_REGISTERS: usize = 12;
+
+/// Rough size of the machine code generated per tape instruction
+///
+/// The JIT emits a point, interval, float slice and gradient slice evaluator
+/// for each tape; the interval and gradient variants dominate, at several
+/// dozen bytes per operation.
+const JIT_BYTES_PER_OP: usize = 128;
+
+pub struct Stats {
+    /// Size of a context holding the imported tree, as reported by `len()`
+    pub context_len: usize,
+    /// Number of distinct nodes reachable from the root
+    pub nodes: usize,
+    /// Node counts, keyed by operation name
+    pub ops: BTreeMap<String, usize>,
+    /// Number of nodes on the longest path from the root to a leaf
+    pub depth: usize,
+    /// Number of `min` and `max` nodes, which the evaluators can simplify
+    pub choices: usize,
+    /// Number of instructions in the SSA tape
+    pub tape_len: usize,
+    /// Number of instructions after register allocation for the JIT
+    pub reg_tape_len: usize,
+    /// Number of registers used by the JIT
+    pub registers: usize,
+    /// Number of values spilled to memory by the JIT
+    pub memory_slots: usize,
+    /// Estimated size of the JIT-compiled code, in bytes
+    pub jit_bytes: usize,
+}
+
+impl Stats {
+    pub fn new(tree: &Tree) -> Result<Self, Error> {
+        let mut ctx = Context::new();
+        let root = ctx.import(tree);
+        let nodes = fold::nodes(&ctx, root)?;
+
+        let mut ops = BTreeMap::new();
+        let mut depths = HashMap::new();
+        let mut choices = 0;
+        for &node in &nodes {
+            let op = *ctx.get_op(node).ok_or(Error::BadNode)?;
+            let (name, depth) = match op {
+                Op::Input(v) => (format!("var-{}", vars::describe(v)), 1),
+                Op::Const(..) => ("const".to_owned(), 1),
+                Op::Unary(o, a) => (vm::unary_name(o).to_owned(), depths[&a] + 1),
+                Op::Binary(o, a, b) => {
+                    if matches!(o, BinaryOpcode::Min | BinaryOpcode::Max) {
+                        choices += 1;
+                    }
+                    let d = std::cmp::max(depths[&a], depths[&b]);
+                    (vm::binary_name(o).to_owned(), d + 1)
+                }
+            };
+            *ops.entry(name).or_insert(0) += 1;
+            depths.insert(node, depth);
+        }
+
+        // lower the tree the same way that the evaluators do
+        let mut lowered = Context::new();
+        let lowered_root = fold::rebuild(&ctx, root, &mut lowered)?;
+        let (ssa, _) = SsaTape::new(&lowered, &[lowered_root])?;
+        let reg = RegTape::new::<JIT_REGISTERS>(&ssa);
+
+        Ok(Stats {
+            context_len: ctx.len(),
+            nodes: nodes.len(),
+            ops,
+            depth: depths[&root],
+            choices,
+            tape_len: ssa.len(),
+            reg_tape_len: reg.len(),
+            registers: reg.slot_count().min(JIT_REGISTERS),
+            memory_slots: reg.slot_count().saturating_sub(JIT_REGISTERS),
+            jit_bytes: reg.len() * JIT_BYTES_PER_OP,
+        })
+    }
+}
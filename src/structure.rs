@@ -0,0 +1,125 @@
+//! Traversal, structural hashing and comparison of trees.
+//!
+//! Two trees are structurally equal if they're built from the same
+//! operations on the same inputs, regardless of whether they share memory.
+//! Constants are compared by their bit patterns, so `0.0` and `-0.0` differ
+//! while identical NaNs are equal. No algebraic rewriting is done: `x + y`
+//! and `y + x` are different trees.
+use fidget::context::{Tree, TreeOp};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+/// Returns the direct children of a tree operation
+pub fn children(op: &TreeOp) -> Vec<&TreeOp> {
+    match op {
+        TreeOp::Input(..) | TreeOp::Const(..) => vec![],
+        TreeOp::Unary(_, a) => vec![a],
+        TreeOp::Binary(_, a, b) => vec![a, b],
+        TreeOp::RemapAxes { target, x, y, z } => vec![target, x, y, z],
+    }
+}
+
+/// Returns every distinct operation in a tree, children before parents
+///
+/// Operations are distinguished by address, so shared subtrees appear once.
+/// Children are visited in order, and the root is always last.
+pub fn postorder(tree: &Tree) -> Vec<&TreeOp> {
+    let mut seen: HashSet<*const TreeOp> = HashSet::new();
+    let mut out = vec![];
+    // Do recursion on the heap to avoid stack overflows for deep trees
+    let mut todo: Vec<(&TreeOp, bool)> = vec![(tree, false)];
+    while let Some((op, children_done)) = todo.pop() {
+        if children_done {
+            out.push(op);
+            continue;
+        }
+        if !seen.insert(op) {
+            continue;
+        }
+        todo.push((op, true));
+        todo.extend(children(op).into_iter().rev().map(|c| (c, false)));
+    }
+    out
+}
+
+/// Wraps an operation in a new `Tree`, sharing its children
+///
+/// `Tree` can't be built from an existing `Arc`, so the operation itself is
+/// copied by value.
+pub fn to_tree(op: &TreeOp) -> Tree {
+    Tree::from(match op {
+        TreeOp::Const(c) => TreeOp::Const(*c),
+        TreeOp::Input(v) => TreeOp::Input(*v),
+        TreeOp::Unary(o, a) => TreeOp::Unary(*o, a.clone()),
+        TreeOp::Binary(o, a, b) => TreeOp::Binary(*o, a.clone(), b.clone()),
+        TreeOp::RemapAxes { target, x, y, z } => TreeOp::RemapAxes {
+            target: target.clone(),
+            x: x.clone(),
+            y: y.clone(),
+            z: z.clone(),
+        },
+    })
+}
+
+/// Hashes everything about an operation except for its children
+fn hash_shallow(op: &TreeOp, h: &mut impl Hasher) {
+    std::mem::discriminant(op).hash(h);
+    match op {
+        TreeOp::Input(v) => v.hash(h),
+        TreeOp::Const(c) => c.to_bits().hash(h),
+        TreeOp::Unary(o, _) => o.hash(h),
+        TreeOp::Binary(o, _, _) => o.hash(h),
+        TreeOp::RemapAxes { .. } => (),
+    }
+}
+
+/// Compares everything about two operations except for their children
+fn equal_shallow(a: &TreeOp, b: &TreeOp) -> bool {
+    match (a, b) {
+        (TreeOp::Input(a), TreeOp::Input(b)) => a == b,
+        (TreeOp::Const(a), TreeOp::Const(b)) => a.to_bits() == b.to_bits(),
+        (TreeOp::Unary(a, _), TreeOp::Unary(b, _)) => a == b,
+        (TreeOp::Binary(a, _, _), TreeOp::Binary(b, _, _)) => a == b,
+        (TreeOp::RemapAxes { .. }, TreeOp::RemapAxes { .. }) => true,
+        _ => false,
+    }
+}
+
+/// Computes a structural hash of a tree
+///
+/// Shared subtrees are only hashed once, so this is linear in the number of
+/// distinct operations.
+pub fn hash(tree: &Tree) -> u64 {
+    let mut hashes: HashMap<*const TreeOp, u64> = HashMap::new();
+    for op in postorder(tree) {
+        let mut h = DefaultHasher::new();
+        hash_shallow(op, &mut h);
+        for c in children(op) {
+            hashes[&(c as *const TreeOp)].hash(&mut h);
+        }
+        hashes.insert(op, h.finish());
+    }
+    hashes[&(&**tree as *const TreeOp)]
+}
+
+/// Checks whether two trees are structurally equal
+pub fn equal(a: &Tree, b: &Tree) -> bool {
+    // pairs that are already known to be equal (or are being checked, which
+    // is fine: any mismatch below them makes the whole comparison fail)
+    let mut seen: HashSet<(*const TreeOp, *const TreeOp)> = HashSet::new();
+    let mut todo: Vec<(&TreeOp, &TreeOp)> = vec![(a, b)];
+    while let Some((a, b)) = todo.pop() {
+        if std::ptr::eq(a, b) || !seen.insert((a, b)) {
+            continue;
+        }
+        if !equal_shallow(a, b) {
+            return false;
+        }
+        let ca = children(a);
+        let cb = children(b);
+        todo.extend(ca.into_iter().zip(cb));
+    }
+    true
+}
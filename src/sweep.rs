@@ -0,0 +1,351 @@
+//! Sweeps of 2D profiles along 3D paths.
+//!
+//! Paths are polylines; splines are sampled into polylines first. Each
+//! segment of the path contributes the profile's prism along it, in the
+//! segment's frame, cut off by the mitre planes that bisect the joints with
+//! its neighbors; the sweep is the union of these pieces. With
+//! rotation-minimizing frames, the frames of neighboring segments differ by
+//! the rotation that takes one tangent to the other, so the two pieces'
+//! cross-sections on the mitre plane between them are identical and the
+//! joints are seamless. The pieces are bounds, as is their union.
+//!
+//! On a closed path that isn't planar, frames carried once around the loop
+//! come back rotated about the tangent; that angle is spread along the path
+//! as a twist, so the closing joint matches as well.
+use fidget::context::Tree;
+use nalgebra::{Rotation3, Unit, Vector3};
+
+type Point = Vector3<f64>;
+
+/// How a path is built from its points
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    /// Straight segments between the points
+    Polyline,
+    /// A uniform Catmull-Rom spline through the points
+    CatmullRom,
+    /// Cubic Bézier segments, sharing end points: the points are `P0, C, C,
+    /// P1, C, C, P2, ...`, where each `C` is a control point
+    Bezier,
+}
+
+impl Curve {
+    /// Parses a curve name, "polyline", "catmull-rom" or "bezier"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "polyline" => Ok(Curve::Polyline),
+            "catmull-rom" => Ok(Curve::CatmullRom),
+            "bezier" => Ok(Curve::Bezier),
+            _ => Err(format!(
+                "unknown curve '{name}', expected 'polyline', 'catmull-rom' or 'bezier'"
+            )),
+        }
+    }
+}
+
+/// How the profile is oriented along a path
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Frames {
+    /// Rotation-minimizing frames, which don't twist around the path
+    RotationMinimizing,
+    /// The profile's y axis is kept as close to +z as possible (or to +y,
+    /// where the path is nearly vertical); joints only line up exactly on
+    /// planar paths
+    Fixed,
+}
+
+impl Frames {
+    /// Parses a frame name, "rmf" or "fixed"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "rmf" => Ok(Frames::RotationMinimizing),
+            "fixed" => Ok(Frames::Fixed),
+            _ => Err(format!("unknown frame '{name}', expected 'rmf' or 'fixed'")),
+        }
+    }
+}
+
+/// Samples a path into polyline vertices, with `samples` pieces per spline
+/// segment
+///
+/// For a closed path, the segment from the last vertex back to the first is
+/// implied. Repeated vertices are dropped.
+pub fn sample(
+    points: &[Point],
+    closed: bool,
+    curve: Curve,
+    samples: usize,
+) -> Result<Vec<Point>, String> {
+    if samples == 0 {
+        return Err("samples must be at least 1".to_owned());
+    }
+    let n = points.len();
+    let get = |i: usize| points[i % n];
+    let mut out = vec![];
+    match curve {
+        Curve::Polyline => out.extend_from_slice(points),
+        Curve::CatmullRom => {
+            if n < 2 {
+                return Err("a spline needs at least 2 points".to_owned());
+            }
+            let segments = if closed { n } else { n - 1 };
+            for i in 0..segments {
+                let (p1, p2) = (get(i), get(i + 1));
+                // open ends are extended by reflecting their neighbors
+                let p0 = match (i, closed) {
+                    (0, false) => 2.0 * p1 - p2,
+                    _ => get(i + n - 1),
+                };
+                let p3 = match (i + 2 >= n + usize::from(closed), closed) {
+                    (true, false) => 2.0 * p2 - p1,
+                    _ => get(i + 2),
+                };
+                for k in 0..samples {
+                    let t = k as f64 / samples as f64;
+                    let (t2, t3) = (t * t, t * t * t);
+                    out.push(
+                        0.5 * (2.0 * p1
+                            + (p2 - p0) * t
+                            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3),
+                    );
+                }
+            }
+            if !closed {
+                out.push(points[n - 1]);
+            }
+        }
+        Curve::Bezier => {
+            let valid = if closed {
+                n >= 3 && n.is_multiple_of(3)
+            } else {
+                n >= 4 && n % 3 == 1
+            };
+            if !valid {
+                return Err(format!(
+                    "a {} Bézier path needs {} points, got {n}",
+                    if closed { "closed" } else { "open" },
+                    if closed { "3k" } else { "3k + 1" },
+                ));
+            }
+            let segments = if closed { n / 3 } else { (n - 1) / 3 };
+            for i in 0..segments {
+                let [p0, c0, c1, p1] = [0, 1, 2, 3].map(|j| get(3 * i + j));
+                for k in 0..samples {
+                    let t = k as f64 / samples as f64;
+                    let s = 1.0 - t;
+                    out.push(
+                        p0 * (s * s * s)
+                            + c0 * (3.0 * s * s * t)
+                            + c1 * (3.0 * s * t * t)
+                            + p1 * (t * t * t),
+                    );
+                }
+            }
+            if !closed {
+                out.push(points[n - 1]);
+            }
+        }
+    }
+    out.dedup_by(|a, b| (*a - *b).norm() <= 1e-12);
+    if closed && out.len() > 1 && (out[0] - out[out.len() - 1]).norm() <= 1e-12 {
+        out.pop();
+    }
+    if out.len() < 2 + usize::from(closed) {
+        return Err(format!(
+            "a {} path needs at least {} distinct points",
+            if closed { "closed" } else { "open" },
+            2 + usize::from(closed)
+        ));
+    }
+    Ok(out)
+}
+
+/// A straight piece of a sampled path
+struct Segment {
+    start: Point,
+    end: Point,
+    tangent: Point,
+    /// The directions of the profile's x and y axes
+    x: Point,
+    y: Point,
+    /// Rotation of the frame about the tangent at the start and end, in
+    /// radians, for spreading the twist of a closed path
+    twist: (f64, f64),
+    /// Normals of the planes at each end, pointing along the path
+    mitre: (Point, Point),
+    /// Whether each end is joined to another segment
+    joints: (bool, bool),
+}
+
+/// The profile's y axis for a tangent, when nothing else decides it
+fn up(t: &Point) -> Point {
+    let reference = if t.z.abs() < 0.9 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    (reference - t * t.dot(&reference)).normalize()
+}
+
+/// The smallest rotation taking unit vector `a` to unit vector `b`
+fn rotation_between(a: &Point, b: &Point, fallback: &Point) -> Rotation3<f64> {
+    Rotation3::rotation_between(a, b).unwrap_or_else(|| {
+        // a half turn, about an axis perpendicular to both
+        Rotation3::from_axis_angle(&Unit::new_normalize(*fallback), std::f64::consts::PI)
+    })
+}
+
+fn segments(points: &[Point], closed: bool, frames: Frames) -> Result<Vec<Segment>, String> {
+    let n = points.len();
+    let count = if closed { n } else { n - 1 };
+    let ends: Vec<(Point, Point)> = (0..count)
+        .map(|i| (points[i], points[(i + 1) % n]))
+        .collect();
+    let tangents: Vec<Point> = ends.iter().map(|(a, b)| (b - a).normalize()).collect();
+
+    let mut ys = vec![up(&tangents[0])];
+    for i in 1..count {
+        let y = match frames {
+            Frames::RotationMinimizing => {
+                let r = rotation_between(&tangents[i - 1], &tangents[i], &ys[i - 1]);
+                let y = r * ys[i - 1];
+                // keep the frame orthonormal despite rounding
+                (y - tangents[i] * tangents[i].dot(&y)).normalize()
+            }
+            Frames::Fixed => up(&tangents[i]),
+        };
+        ys.push(y);
+    }
+
+    // the twist that's needed to close the loop, spread by length
+    let mut twists = vec![0.0; count + 1];
+    if closed && frames == Frames::RotationMinimizing {
+        let (last, first) = (tangents[count - 1], tangents[0]);
+        let carried = rotation_between(&last, &first, &ys[count - 1]) * ys[count - 1];
+        let angle = ys[0].cross(&carried).dot(&first).atan2(ys[0].dot(&carried));
+        let lengths: Vec<f64> = ends.iter().map(|(a, b)| (b - a).norm()).collect();
+        let total: f64 = lengths.iter().sum();
+        let mut done = 0.0;
+        for (i, length) in lengths.iter().enumerate() {
+            done += length;
+            twists[i + 1] = -angle * done / total;
+        }
+    }
+
+    let mitre = |before: usize, after: usize| -> Result<Point, String> {
+        let m = tangents[before] + tangents[after];
+        if m.norm() < 1e-6 {
+            Err("the path turns back on itself".to_owned())
+        } else {
+            Ok(m.normalize())
+        }
+    };
+    (0..count)
+        .map(|i| {
+            let start = match (i, closed) {
+                (0, false) => tangents[0],
+                _ => mitre((i + count - 1) % count, i)?,
+            };
+            let end = match (i + 1 == count, closed) {
+                (true, false) => tangents[i],
+                _ => mitre(i, (i + 1) % count)?,
+            };
+            Ok(Segment {
+                start: ends[i].0,
+                end: ends[i].1,
+                tangent: tangents[i],
+                x: ys[i].cross(&tangents[i]),
+                y: ys[i],
+                twist: (twists[i], twists[i + 1]),
+                mitre: (start, end),
+                joints: (closed || i > 0, closed || i + 1 < count),
+            })
+        })
+        .collect()
+}
+
+/// The field that cuts a piece off at a plane, given its signed distance
+/// `c` past the plane
+///
+/// An open end is cut flat. At a joint, cutting both pieces flat would leave
+/// a field of zero across the mitre plane inside the solid, so each piece
+/// reaches past the plane instead, to depth `-d / 2` where its field is `d`.
+/// Reflecting across the mitre plane swaps the two pieces and their fields
+/// agree on it, so the neighbor's field is at most `d + 2c` there, and the
+/// overlap stays inside the neighbor. Dividing by 3 keeps it Lipschitz-1.
+fn cut(d: &Tree, c: Tree, joint: bool) -> Tree {
+    if joint {
+        (c * 2.0 + d.clone()) / 3.0
+    } else {
+        c
+    }
+}
+
+fn dot(p: &[Tree; 3], v: &Point) -> Tree {
+    p[0].clone() * v.x + p[1].clone() * v.y + p[2].clone() * v.z
+}
+
+/// Sweeps a 2D profile in the xy plane along a sampled path
+///
+/// `radius` bounds the profile's distance from its origin; it's used to
+/// keep twisted segments (on closed paths) bounded. Returns the tree and
+/// how far it reaches from the path, accounting for the stretching of the
+/// profile across mitred joints.
+pub fn sweep(
+    profile: &Tree,
+    radius: f64,
+    points: &[Point],
+    closed: bool,
+    frames: Frames,
+) -> Result<(Tree, f64), String> {
+    let segments = segments(points, closed, frames)?;
+    let mut out: Option<Tree> = None;
+    let mut stretch: f64 = 1.0;
+    for s in &segments {
+        let q = [
+            Tree::x() - s.start.x,
+            Tree::y() - s.start.y,
+            Tree::z() - s.start.z,
+        ];
+        let length = (s.end - s.start).norm();
+        let (mut u, mut v) = (dot(&q, &s.x), dot(&q, &s.y));
+        let (a, b) = s.twist;
+        let mut scale = None;
+        if a != b {
+            let along = (dot(&q, &s.tangent) / length).max(0.0).min(1.0);
+            let angle = along * (b - a) + a;
+            let (c, sn) = (angle.cos(), angle.sin());
+            (u, v) = (
+                u.clone() * c.clone() + v.clone() * sn.clone(),
+                v * c - u * sn,
+            );
+            // as for twisted extrusions, the field changes along the path
+            // by at most the twist rate times the distance from the path
+            let rate = (b - a).abs() / length;
+            let r = (u.square() + v.square()).sqrt().max(radius);
+            scale = Some(((r * rate).square() + 1.0).sqrt());
+        }
+        let mut d = profile.remap_xyz(u, v, Tree::constant(0.0));
+        if let Some(scale) = scale {
+            d /= scale;
+        }
+        let (m0, m1) = s.mitre;
+        let end = [
+            Tree::x() - s.end.x,
+            Tree::y() - s.end.y,
+            Tree::z() - s.end.z,
+        ];
+        let behind = cut(&d, dot(&q, &m0).neg(), s.joints.0);
+        let beyond = cut(&d, dot(&end, &m1), s.joints.1);
+        let piece = d.max(behind).max(beyond);
+        out = Some(match out {
+            Some(o) => o.min(piece),
+            None => piece,
+        });
+        stretch = stretch
+            .max(1.0 / m0.dot(&s.tangent))
+            .max(1.0 / m1.dot(&s.tangent));
+    }
+    Ok((out.unwrap(), radius * stretch))
+}
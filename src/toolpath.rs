@@ -0,0 +1,547 @@
+//! Toolpaths, and the volume a cutter sweeps along them.
+//!
+//! Cutters are trees with their tip at the origin and their axis along +z,
+//! like the tools in `examples/gcode.py`: solids of revolution that extend
+//! upwards forever (their bounds have an infinite `zmax`) and don't get
+//! narrower going up. Tools of finite length are rejected, since a vertical
+//! move would need them at its upper end too. For these tools, moving
+//! horizontally along a line or an arc, the point of the path nearest to
+//! `p` in the xy plane is the position of the tool that reaches furthest
+//! towards `p`, so the swept volume is exactly the tool placed there; moving
+//! vertically, it's the tool at the lower end. Ramps and helical arcs are
+//! split into pieces that drop by at most the tolerance, each of which is
+//! treated like a horizontal move (along with its lower end), so the swept
+//! volume can be short of the truth by up to the tolerance in z. Arcs
+//! outside the xy plane are split into chords that stay within the
+//! tolerance of the arc.
+//!
+//! Apart from those chords, every piece is placed at an actual position of
+//! the tool, so the result never removes material that the tool doesn't
+//! reach. A chord cuts inside its arc, so on the arc's concave side the
+//! swept volume can go past the truth by up to the tolerance.
+//!
+//! Each piece is also clipped by a cheap bound on the distance to its
+//! bounding box, and the pieces are combined with a balanced tree of `min`s,
+//! so interval evaluation can discard the pieces that are far away from a
+//! region.
+use crate::PyTree;
+use fidget::context::Tree;
+use nalgebra::Vector3;
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+type Bounds = (f64, f64, f64, f64, f64, f64);
+type Point = Vector3<f64>;
+
+/// The kind of a move, named after its G-code
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+    /// `G0`, a rapid linear move
+    Rapid,
+    /// `G1`, a linear move at the feed rate
+    Linear,
+    /// `G2`, a clockwise arc
+    Clockwise,
+    /// `G3`, a counter-clockwise arc
+    CounterClockwise,
+}
+
+impl Kind {
+    /// Parses a G-code, "G0" to "G3"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "G0" => Ok(Kind::Rapid),
+            "G1" => Ok(Kind::Linear),
+            "G2" => Ok(Kind::Clockwise),
+            "G3" => Ok(Kind::CounterClockwise),
+            _ => Err(format!(
+                "unknown move '{name}', expected 'G0', 'G1', 'G2' or 'G3'"
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Rapid => "G0",
+            Kind::Linear => "G1",
+            Kind::Clockwise => "G2",
+            Kind::CounterClockwise => "G3",
+        }
+    }
+
+    pub fn is_arc(&self) -> bool {
+        matches!(self, Kind::Clockwise | Kind::CounterClockwise)
+    }
+}
+
+/// The plane of an arc
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Plane {
+    /// `G17`, arcs around z
+    XY,
+    /// `G18`, arcs around y
+    ZX,
+    /// `G19`, arcs around x
+    YZ,
+}
+
+impl Plane {
+    /// Parses a plane name, "xy", "zx" or "yz"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "xy" => Ok(Plane::XY),
+            "zx" => Ok(Plane::ZX),
+            "yz" => Ok(Plane::YZ),
+            _ => Err(format!(
+                "unknown plane '{name}', expected 'xy', 'zx' or 'yz'"
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Plane::XY => "xy",
+            Plane::ZX => "zx",
+            Plane::YZ => "yz",
+        }
+    }
+
+    /// Indices of the two axes spanning the plane, in right-handed order,
+    /// and of the axis around which its arcs turn
+    pub fn axes(&self) -> [usize; 3] {
+        match self {
+            Plane::XY => [0, 1, 2],
+            Plane::ZX => [2, 0, 1],
+            Plane::YZ => [1, 2, 0],
+        }
+    }
+}
+
+/// A single move of a toolpath
+#[derive(Clone, Debug)]
+pub struct Move {
+    pub kind: Kind,
+    pub start: Point,
+    pub end: Point,
+    /// Center of an arc; along the arc's axis, only the start and end matter
+    pub center: Option<Point>,
+    pub plane: Plane,
+    /// The tool in the spindle, if known
+    pub tool: Option<u32>,
+}
+
+/// A straight or circular piece of a toolpath, small enough to be swept in
+/// one go
+enum Piece {
+    Line(Point, Point),
+    /// An arc in the xy plane, from `angle` through `sweep` radians
+    /// (counter-clockwise if positive), with radius and height interpolated
+    /// linearly from the start to the end
+    Arc {
+        center: Point,
+        angle: f64,
+        sweep: f64,
+        radius: (f64, f64),
+        z: (f64, f64),
+    },
+}
+
+impl Piece {
+    fn arc_point(center: &Point, angle: f64, radius: f64, z: f64) -> Point {
+        Point::new(
+            center.x + radius * angle.cos(),
+            center.y + radius * angle.sin(),
+            z,
+        )
+    }
+
+    fn ends(&self) -> (Point, Point) {
+        match self {
+            Piece::Line(a, b) => (*a, *b),
+            Piece::Arc {
+                center,
+                angle,
+                sweep,
+                radius,
+                z,
+            } => (
+                Self::arc_point(center, *angle, radius.0, z.0),
+                Self::arc_point(center, angle + sweep, radius.1, z.1),
+            ),
+        }
+    }
+
+    /// Bounding box of the tool tip's positions along the piece
+    fn bounds(&self) -> (Point, Point) {
+        let (a, b) = self.ends();
+        let (mut lo, mut hi) = (a.inf(&b), a.sup(&b));
+        if let Piece::Arc {
+            center,
+            angle,
+            sweep,
+            radius,
+            ..
+        } = self
+        {
+            // the extremes of the circle that the arc passes through
+            let (from, to) = (angle.min(angle + sweep), angle.max(angle + sweep));
+            let r = radius.0.max(radius.1);
+            let mut k = (from / FRAC_PI_2).ceil();
+            while k * FRAC_PI_2 <= to {
+                let p = Self::arc_point(center, k * FRAC_PI_2, r, a.z);
+                (lo, hi) = (lo.inf(&p), hi.sup(&p));
+                k += 1.0;
+            }
+        }
+        (lo, hi)
+    }
+
+    /// The tool placed at the positions along the piece that reach furthest
+    /// towards the point being evaluated
+    fn sweep(&self, tool: &Tree) -> Tree {
+        let (x, y, z) = Tree::axes();
+        let place = |p: [Tree; 3]| {
+            let [px, py, pz] = p;
+            tool.remap_xyz(x.clone() - px, y.clone() - py, z.clone() - pz)
+        };
+        let (a, b) = self.ends();
+        let lower = if a.z <= b.z { a } else { b };
+        let mut out = match self {
+            Piece::Line(a, b) => {
+                let d = b - a;
+                let len2 = d.x * d.x + d.y * d.y;
+                if len2 <= 1e-18 {
+                    // a vertical move, or no move at all
+                    return place(lower.map(Tree::constant).into());
+                }
+                let h = (((x.clone() - a.x) * d.x + (y.clone() - a.y) * d.y) / len2)
+                    .max(0.0)
+                    .min(1.0);
+                place([0, 1, 2].map(|i| h.clone() * d[i] + a[i]))
+            }
+            Piece::Arc {
+                center,
+                angle,
+                sweep,
+                radius,
+                z: (z0, z1),
+            } => {
+                // the angle of the point, measured from the middle of the
+                // arc; clamping it picks the nearest point of the arc, since
+                // it's in (-π, π]
+                let mid = angle + sweep / 2.0;
+                let (u, v) = (x.clone() - center.x, y.clone() - center.y);
+                let (c, s) = (mid.cos(), mid.sin());
+                let offset = (v.clone() * c - u.clone() * s).atan2(u * c + v * s);
+                let half = sweep.abs() / 2.0;
+                let offset = offset.max(-half).min(half);
+                let t = offset.clone() / *sweep + 0.5;
+                let r = t.clone() * (radius.1 - radius.0) + radius.0;
+                let theta = offset + mid;
+                place([
+                    r.clone() * theta.cos() + center.x,
+                    r * theta.sin() + center.y,
+                    t * (z1 - z0) + *z0,
+                ])
+            }
+        };
+        if a.z != b.z {
+            out = out.min(place(lower.map(Tree::constant).into()));
+        }
+        out
+    }
+}
+
+/// Number of pieces needed to keep each one within `tolerance` of `span`
+fn pieces(span: f64, tolerance: f64) -> usize {
+    (span.abs() / tolerance).ceil().max(1.0) as usize
+}
+
+fn split(m: &Move, tolerance: f64) -> Result<Vec<Piece>, String> {
+    if !m.kind.is_arc() {
+        let (a, d) = (m.start, m.end - m.start);
+        // vertical moves are swept exactly in one piece
+        let n = if d.xy().norm() <= 1e-9 {
+            1
+        } else {
+            pieces(d.z, tolerance)
+        };
+        return Ok((0..n)
+            .map(|i| {
+                let t = |i: usize| a + d * (i as f64 / n as f64);
+                Piece::Line(t(i), t(i + 1))
+            })
+            .collect());
+    }
+    let Some(center) = m.center else {
+        return Err(format!("{} moves need a center", m.kind.name()));
+    };
+    // work in the plane's own coordinates, with its axis as z
+    let [iu, iv, iw] = m.plane.axes();
+    let local = |p: &Point| Point::new(p[iu], p[iv], p[iw]);
+    let global = |p: &Point| {
+        let mut out = Point::zeros();
+        (out[iu], out[iv], out[iw]) = (p.x, p.y, p.z);
+        out
+    };
+    let (s, e, c) = (local(&m.start), local(&m.end), local(&center));
+    let (r0, r1) = ((s - c).xy().norm(), (e - c).xy().norm());
+    if r0 <= 1e-12 || r1 <= 1e-12 {
+        return Err("arcs must not start or end at their center".to_owned());
+    }
+    let a0 = (s.y - c.y).atan2(s.x - c.x);
+    let a1 = (e.y - c.y).atan2(e.x - c.x);
+    let ccw = m.kind == Kind::CounterClockwise;
+    let mut sweep = if ccw { a1 - a0 } else { a0 - a1 }.rem_euclid(TAU);
+    // an arc that ends where it starts is a full circle
+    if sweep <= 1e-9 {
+        sweep = TAU;
+    }
+    if !ccw {
+        sweep = -sweep;
+    }
+    let at = |t: f64| {
+        Piece::arc_point(
+            &c,
+            a0 + sweep * t,
+            r0 + (r1 - r0) * t,
+            s.z + (e.z - s.z) * t,
+        )
+    };
+
+    if m.plane == Plane::XY {
+        let n = pieces(e.z - s.z, tolerance);
+        Ok((0..n)
+            .map(|i| {
+                let (t0, t1) = (i as f64 / n as f64, (i + 1) as f64 / n as f64);
+                Piece::Arc {
+                    center: c,
+                    angle: a0 + sweep * t0,
+                    sweep: sweep / n as f64,
+                    radius: (r0 + (r1 - r0) * t0, r0 + (r1 - r0) * t1),
+                    z: (s.z + (e.z - s.z) * t0, s.z + (e.z - s.z) * t1),
+                }
+            })
+            .collect())
+    } else {
+        // chords whose sagitta r (1 - cos(δ / 2)) is within the tolerance
+        let r = r0.max(r1);
+        let step = 2.0 * (1.0 - (tolerance / r).min(1.0)).acos();
+        let n = pieces(sweep, step);
+        let mut out = vec![];
+        for i in 0..n {
+            let (a, b) = (at(i as f64 / n as f64), at((i + 1) as f64 / n as f64));
+            let chord = Move {
+                kind: Kind::Linear,
+                start: global(&a),
+                end: global(&b),
+                center: None,
+                plane: m.plane,
+                tool: m.tool,
+            };
+            out.extend(split(&chord, tolerance)?);
+        }
+        Ok(out)
+    }
+}
+
+/// A lower bound on the distance to a box, which is its negated distance
+/// to the nearest face inside it; infinite sides are skipped
+fn box_bound(lo: &Point, hi: &Point) -> Option<Tree> {
+    let axes = [Tree::x(), Tree::y(), Tree::z()];
+    let mut out: Option<Tree> = None;
+    for i in 0..3 {
+        let faces = [
+            (lo[i].is_finite()).then(|| axes[i].neg() + lo[i]),
+            (hi[i].is_finite()).then(|| axes[i].clone() - hi[i]),
+        ];
+        for f in faces.into_iter().flatten() {
+            out = Some(match out {
+                Some(o) => o.max(f),
+                None => f,
+            });
+        }
+    }
+    out
+}
+
+/// Combines trees with `min`, as a balanced tree
+fn balanced_min(mut trees: Vec<Tree>) -> Tree {
+    while trees.len() > 1 {
+        let mut next = Vec::with_capacity(trees.len().div_ceil(2));
+        let mut iter = trees.into_iter();
+        while let Some(a) = iter.next() {
+            next.push(match iter.next() {
+                Some(b) => a.min(b),
+                None => a,
+            });
+        }
+        trees = next;
+    }
+    trees.pop().unwrap()
+}
+
+/// Sweeps `tool` (with the given bounds, relative to its tip, which must be
+/// unbounded above) along `moves`, returning the swept volume and its bounds
+pub fn swept_volume(
+    tool: &Tree,
+    bounds: Bounds,
+    moves: &[Move],
+    tolerance: f64,
+) -> Result<(Tree, Bounds), String> {
+    if !(tolerance > 0.0 && tolerance.is_finite()) {
+        return Err(format!(
+            "tolerance must be positive and finite, got {tolerance}"
+        ));
+    }
+    if moves.is_empty() {
+        return Err("a toolpath needs at least one move".to_owned());
+    }
+    if bounds.5 != f64::INFINITY {
+        return Err(format!(
+            "tools must extend upwards forever, got zmax = {}",
+            bounds.5
+        ));
+    }
+    let tool_lo = Point::new(bounds.0, bounds.2, bounds.4);
+    let tool_hi = Point::new(bounds.1, bounds.3, bounds.5);
+    let mut trees = vec![];
+    let (mut lo, mut hi) = (Point::repeat(f64::INFINITY), Point::repeat(-f64::INFINITY));
+    for m in moves {
+        for piece in split(m, tolerance)? {
+            let (a, b) = piece.bounds();
+            let (a, b) = (a + tool_lo, b + tool_hi);
+            (lo, hi) = (lo.inf(&a), hi.sup(&b));
+            let swept = piece.sweep(tool);
+            trees.push(match box_bound(&a, &b) {
+                Some(bound) => swept.max(bound),
+                None => swept,
+            });
+        }
+    }
+    Ok((balanced_min(trees), (lo.x, hi.x, lo.y, hi.y, lo.z, hi.z)))
+}
+
+/// A move of a toolpath: a line (`G0` or `G1`) or an arc (`G2` or `G3`)
+/// from `start` to `end`, with arcs turning around an absolute `center` in
+/// the given plane, made with the given tool
+#[pyclass(name = "Move", module = "fidgetpy._core", frozen)]
+pub struct PyMove {
+    pub _val: Move,
+}
+
+#[pymethods]
+impl PyMove {
+    #[new]
+    #[pyo3(signature = (kind, start, end, center=None, plane="xy", tool=None))]
+    fn new(
+        kind: &str,
+        start: (f64, f64, f64),
+        end: (f64, f64, f64),
+        center: Option<(f64, f64, f64)>,
+        plane: &str,
+        tool: Option<u32>,
+    ) -> PyResult<Self> {
+        let point = |p: (f64, f64, f64)| Point::new(p.0, p.1, p.2);
+        let m = Kind::parse(kind).and_then(|kind| {
+            let plane = Plane::parse(plane)?;
+            if kind.is_arc() != center.is_some() {
+                return Err(format!(
+                    "{} moves {} a center",
+                    kind.name(),
+                    if kind.is_arc() { "need" } else { "don't take" }
+                ));
+            }
+            Ok(Move {
+                kind,
+                start: point(start),
+                end: point(end),
+                center: center.map(point),
+                plane,
+                tool,
+            })
+        });
+        match m {
+            Ok(m) => Ok(PyMove { _val: m }),
+            Err(e) => Err(PyRuntimeError::new_err(e)),
+        }
+    }
+
+    #[getter]
+    fn kind(&self) -> &'static str {
+        self._val.kind.name()
+    }
+
+    #[getter]
+    fn start(&self) -> (f64, f64, f64) {
+        let p = self._val.start;
+        (p.x, p.y, p.z)
+    }
+
+    #[getter]
+    fn end(&self) -> (f64, f64, f64) {
+        let p = self._val.end;
+        (p.x, p.y, p.z)
+    }
+
+    #[getter]
+    fn center(&self) -> Option<(f64, f64, f64)> {
+        self._val.center.map(|p| (p.x, p.y, p.z))
+    }
+
+    #[getter]
+    fn plane(&self) -> &'static str {
+        self._val.plane.name()
+    }
+
+    #[getter]
+    fn tool(&self) -> Option<u32> {
+        self._val.tool
+    }
+
+    fn __repr__(&self) -> String {
+        let m = &self._val;
+        let center = match m.center {
+            Some(c) => format!(", center=({:?}, {:?}, {:?})", c.x, c.y, c.z),
+            None => String::new(),
+        };
+        let tool = match m.tool {
+            Some(t) => format!(", tool={t}"),
+            None => String::new(),
+        };
+        format!(
+            "Move('{}', ({:?}, {:?}, {:?}), ({:?}, {:?}, {:?}){center}, plane='{}'{tool})",
+            m.kind.name(),
+            m.start.x,
+            m.start.y,
+            m.start.z,
+            m.end.x,
+            m.end.y,
+            m.end.z,
+            m.plane.name()
+        )
+    }
+}
+
+#[pyfunction(name = "swept_volume")]
+#[pyo3(signature = (tool, bounds, moves, tolerance=0.01))]
+fn py_swept_volume(
+    tool: PyRef<PyTree>,
+    bounds: Bounds,
+    moves: Vec<PyRef<PyMove>>,
+    tolerance: f64,
+) -> PyResult<(PyTree, Bounds)> {
+    let moves: Vec<Move> = moves.iter().map(|m| m._val.clone()).collect();
+    match swept_volume(&tool._val, bounds, &moves, tolerance) {
+        Ok((tree, b)) => Ok((PyTree::from(tree), b)),
+        Err(e) => Err(PyRuntimeError::new_err(e)),
+    }
+}
+
+/// Adds the `toolpath` submodule to `m`
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let sub = PyModule::new(m.py(), "toolpath")?;
+    sub.add_class::<PyMove>()?;
+    sub.add_function(wrap_pyfunction!(py_swept_volume, &sub)?)?;
+    m.add_submodule(&sub)
+}
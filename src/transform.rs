@@ -0,0 +1,236 @@
+//! Affine transforms of trees, as 4×4 matrices.
+//!
+//! A matrix maps points of the original shape to points of the transformed
+//! one, so the tree is remapped with its inverse: the transformed field at
+//! `p` is the original field at `M⁻¹ p`. Only affine matrices are accepted
+//! (the bottom row must be `0 0 0 1`). Similarity transforms (rotations,
+//! translations, mirrors and uniform scaling) preserve exact distance
+//! fields: the field is multiplied by the scale factor, so distances scale
+//! along with the shape. Any other transform leaves a field that's neither
+//! exact nor a bound.
+use fidget::context::Tree;
+use nalgebra::{Matrix3, Matrix4, Rotation3, Unit, Vector3};
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+
+type Bounds = (f64, f64, f64, f64, f64, f64);
+
+/// Converts rows of a matrix from Python, checking that it's affine
+pub fn from_rows(rows: &[Vec<f64>]) -> Result<Matrix4<f64>, String> {
+    if rows.len() != 4 || rows.iter().any(|r| r.len() != 4) {
+        return Err("expected a 4x4 matrix".to_owned());
+    }
+    let m = Matrix4::from_fn(|i, j| rows[i][j]);
+    if m.iter().any(|v| !v.is_finite()) {
+        return Err("matrix entries must be finite".to_owned());
+    }
+    if m.row(3).iter().ne([0.0, 0.0, 0.0, 1.0].iter()) {
+        return Err("matrix must be affine, with a bottom row of 0 0 0 1".to_owned());
+    }
+    Ok(m)
+}
+
+/// Converts a matrix to rows, for returning to Python
+pub fn to_rows(m: &Matrix4<f64>) -> Vec<Vec<f64>> {
+    m.row_iter().map(|r| r.iter().copied().collect()).collect()
+}
+
+/// Applies a transform to a tree
+pub fn apply(tree: &Tree, m: &Matrix4<f64>) -> Result<Tree, String> {
+    let inv = m
+        .try_inverse()
+        .ok_or_else(|| "matrix is not invertible".to_owned())?;
+    let axes = [Tree::x(), Tree::y(), Tree::z()];
+    // builds one row of inv * (x, y, z, 1), skipping zero terms so that
+    // e.g. a rotation about z leaves z untouched
+    let row = |i: usize| {
+        let mut out: Option<Tree> = None;
+        for (j, axis) in axes.iter().enumerate() {
+            let term = match inv[(i, j)] {
+                0.0 => continue,
+                1.0 => axis.clone(),
+                c => axis.clone() * c,
+            };
+            out = Some(match out {
+                Some(t) => t + term,
+                None => term,
+            });
+        }
+        let offset = inv[(i, 3)];
+        match out {
+            Some(t) if offset == 0.0 => t,
+            Some(t) => t + offset,
+            None => Tree::constant(offset),
+        }
+    };
+    let out = tree.remap_xyz(row(0), row(1), row(2));
+    Ok(match similarity_scale(m) {
+        Some(s) if s != 1.0 => out * s,
+        _ => out,
+    })
+}
+
+/// Returns the scale factor of a similarity transform, or `None` if the
+/// transform distorts shapes
+///
+/// The linear part `L` of a similarity is a rotation (or reflection) times a
+/// uniform scale `s`, so `LᵀL = s²I`.
+pub fn similarity_scale(m: &Matrix4<f64>) -> Option<f64> {
+    let l = m.fixed_view::<3, 3>(0, 0);
+    let gram = l.transpose() * l;
+    let s2 = gram.trace() / 3.0;
+    let tol = 1e-9 * s2;
+    let identity = Matrix3::<f64>::identity();
+    if gram
+        .iter()
+        .zip(identity.iter())
+        .all(|(g, i)| (g - s2 * i).abs() <= tol)
+    {
+        Some(s2.sqrt())
+    } else {
+        None
+    }
+}
+
+/// Returns the axis-aligned bounds of a transformed box
+///
+/// Each output coordinate is a sum of scaled input ranges, so this is done
+/// with interval arithmetic; terms with a zero coefficient are skipped, which
+/// keeps infinite bounds (e.g. of 2D shapes) from producing NaN.
+pub fn bounds(m: &Matrix4<f64>, b: Bounds) -> Bounds {
+    let ranges = [(b.0, b.1), (b.2, b.3), (b.4, b.5)];
+    let mut out = [(0.0, 0.0); 3];
+    for (i, o) in out.iter_mut().enumerate() {
+        let (mut lo, mut hi) = (m[(i, 3)], m[(i, 3)]);
+        for (j, &(a, b)) in ranges.iter().enumerate() {
+            let c = m[(i, j)];
+            if c == 0.0 {
+                continue;
+            }
+            let (p, q) = (c * a, c * b);
+            lo += p.min(q);
+            hi += p.max(q);
+        }
+        *o = (lo, hi);
+    }
+    (out[0].0, out[0].1, out[1].0, out[1].1, out[2].0, out[2].1)
+}
+
+/// Parses an axis: "x", "y", "z", or a vector
+fn parse_axis(v: &Bound<PyAny>) -> PyResult<Vector3<f64>> {
+    let out = if let Ok(name) = v.extract::<String>() {
+        match name.as_str() {
+            "x" => Vector3::x(),
+            "y" => Vector3::y(),
+            "z" => Vector3::z(),
+            _ => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "unknown axis '{name}', expected 'x', 'y', 'z' or a vector"
+                )))
+            }
+        }
+    } else {
+        let (x, y, z): (f64, f64, f64) = v.extract()?;
+        Vector3::new(x, y, z)
+    };
+    if out.norm() == 0.0 || !out.norm().is_finite() {
+        return Err(PyRuntimeError::new_err("axis must be a non-zero vector"));
+    }
+    Ok(out)
+}
+
+/// Rotation taking the z axis to `axis`, for building shapes along it
+///
+/// Named axes cycle the coordinates, so that along "x" the original x and y
+/// axes lie along y and z; other vectors use the shortest rotation.
+pub fn frame(axis: &Bound<PyAny>) -> PyResult<Matrix4<f64>> {
+    let (x, y, z) = (Vector3::x(), Vector3::y(), Vector3::z());
+    let columns = match axis.extract::<String>().as_deref() {
+        Ok("x") => [y, z, x],
+        Ok("y") => [z, x, y],
+        Ok("z") => [x, y, z],
+        _ => {
+            let a = parse_axis(axis)?.normalize();
+            let r = Rotation3::rotation_between(&z, &a).unwrap_or_else(|| {
+                // antiparallel, so any half turn about a perpendicular axis
+                Rotation3::from_axis_angle(&Vector3::x_axis(), std::f64::consts::PI)
+            });
+            return Ok(r.to_homogeneous());
+        }
+    };
+    Ok(Matrix3::from_columns(&columns).to_homogeneous())
+}
+
+/// Rotation by `angle` radians about `axis` (through the origin), following
+/// the right-hand rule
+pub fn rotation(axis: &Bound<PyAny>, angle: f64) -> PyResult<Matrix4<f64>> {
+    let axis = Unit::new_normalize(parse_axis(axis)?);
+    Ok(Rotation3::from_axis_angle(&axis, angle).to_homogeneous())
+}
+
+/// Scaling about the origin; `sy` and `sz` default to `sx`
+pub fn scaling(sx: f64, sy: Option<f64>, sz: Option<f64>) -> Matrix4<f64> {
+    let sy = sy.unwrap_or(sx);
+    let sz = sz.unwrap_or(sx);
+    Matrix4::new_nonuniform_scaling(&Vector3::new(sx, sy, sz))
+}
+
+/// Reflection across a plane through the origin, given as "xy", "yz", "xz"
+/// or its normal vector
+pub fn mirroring(plane: &Bound<PyAny>) -> PyResult<Matrix4<f64>> {
+    let normal = match plane.extract::<String>() {
+        Ok(name) => match name.as_str() {
+            "yz" | "zy" => Vector3::x(),
+            "xz" | "zx" => Vector3::y(),
+            "xy" | "yx" => Vector3::z(),
+            _ => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "unknown plane '{name}', expected 'xy', 'yz', 'xz' or a normal vector"
+                )))
+            }
+        },
+        Err(_) => parse_axis(plane)?,
+    };
+    let n = normal.normalize();
+    let householder = Matrix3::identity() - 2.0 * n * n.transpose();
+    Ok(householder.to_homogeneous())
+}
+
+#[pyfunction(name = "rotation")]
+fn py_rotation(axis: &Bound<PyAny>, angle: f64) -> PyResult<Vec<Vec<f64>>> {
+    Ok(to_rows(&rotation(axis, angle)?))
+}
+
+#[pyfunction(name = "scaling")]
+#[pyo3(signature = (sx, sy=None, sz=None))]
+fn py_scaling(sx: f64, sy: Option<f64>, sz: Option<f64>) -> Vec<Vec<f64>> {
+    to_rows(&scaling(sx, sy, sz))
+}
+
+#[pyfunction(name = "mirroring")]
+fn py_mirroring(plane: &Bound<PyAny>) -> PyResult<Vec<Vec<f64>>> {
+    Ok(to_rows(&mirroring(plane)?))
+}
+
+#[pyfunction(name = "translation")]
+fn py_translation(dx: f64, dy: f64, dz: f64) -> Vec<Vec<f64>> {
+    to_rows(&Matrix4::new_translation(&Vector3::new(dx, dy, dz)))
+}
+
+#[pyfunction(name = "transform_bounds")]
+fn py_transform_bounds(matrix: Vec<Vec<f64>>, bounds: Bounds) -> PyResult<Bounds> {
+    match from_rows(&matrix) {
+        Ok(m) => Ok(self::bounds(&m, bounds)),
+        Err(e) => Err(PyRuntimeError::new_err(e)),
+    }
+}
+
+/// Adds the `transforms` submodule to `m`
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let sub = PyModule::new(m.py(), "transforms")?;
+    sub.add_function(wrap_pyfunction!(py_rotation, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(py_scaling, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(py_mirroring, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(py_translation, &sub)?)?;
+    sub.add_function(wrap_pyfunction!(py_transform_bounds, &sub)?)?;
+    m.add_submodule(&sub)
+}
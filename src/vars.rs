@@ -0,0 +1,127 @@
+//! Process-wide names for variables.
+//!
+//! `Var::new()` gives each variable a random index that has no meaning
+//! outside of this process, so users and exports refer to variables by name
+//! instead. A name is attached to a variable when the variable is created by
+//! name, or the first time an anonymous variable is exported, and it stays
+//! attached for the life of the process. This lets a tree be exported,
+//! re-imported and then evaluated with the variables that it was originally
+//! built from.
+use fidget::var::Var;
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+#[derive(Default)]
+struct Registry {
+    by_name: HashMap<String, Var>,
+    by_var: HashMap<Var, String>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Default::default);
+
+/// Checks whether `name` can be used as a variable name
+///
+/// Names are written as single tokens in the text format, so they must be
+/// non-empty and free of whitespace.
+pub fn is_valid(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(char::is_whitespace)
+}
+
+/// Returns the name of a variable, assigning one if it doesn't have a name yet
+pub fn name(var: Var) -> String {
+    if let Some(n) = axis_name(var) {
+        return n.to_owned();
+    }
+    let mut reg = REGISTRY.lock().unwrap();
+    if let Some(n) = reg.by_var.get(&var) {
+        return n.clone();
+    }
+    let name = reg.fresh_name(var);
+    reg.by_name.insert(name.clone(), var);
+    reg.by_var.insert(var, name.clone());
+    name
+}
+
+/// Returns the name of a variable without assigning one
+///
+/// Anonymous variables are described by the name they would be given, which
+/// may change if another variable takes that name before they're exported.
+/// This is for read-only paths (introspection and error messages), which
+/// shouldn't grow the registry.
+pub fn describe(var: Var) -> String {
+    if let Some(n) = axis_name(var) {
+        return n.to_owned();
+    }
+    let reg = REGISTRY.lock().unwrap();
+    match reg.by_var.get(&var) {
+        Some(n) => n.clone(),
+        None => reg.fresh_name(var),
+    }
+}
+
+/// Returns the variable with the given name, creating a new one if needed
+pub fn lookup(name: &str) -> Var {
+    if let Some(v) = axis(name) {
+        return v;
+    }
+    let mut reg = REGISTRY.lock().unwrap();
+    if let Some(v) = reg.by_name.get(name) {
+        return *v;
+    }
+    let var = Var::new();
+    reg.by_name.insert(name.to_owned(), var);
+    reg.by_var.insert(var, name.to_owned());
+    var
+}
+
+/// Returns the variable with the given name, if there is one
+///
+/// Unlike [`lookup`], this never creates a variable; a name that isn't
+/// registered can't appear in any tree.
+pub fn find(name: &str) -> Option<Var> {
+    axis(name).or_else(|| REGISTRY.lock().unwrap().by_name.get(name).copied())
+}
+
+/// Checks whether `name` is reserved for one of the axes
+pub fn is_reserved(name: &str) -> bool {
+    axis(name).is_some()
+}
+
+fn axis(name: &str) -> Option<Var> {
+    match name {
+        "x" => Some(Var::X),
+        "y" => Some(Var::Y),
+        "z" => Some(Var::Z),
+        _ => None,
+    }
+}
+
+fn axis_name(var: Var) -> Option<&'static str> {
+    match var {
+        Var::X => Some("x"),
+        Var::Y => Some("y"),
+        Var::Z => Some("z"),
+        Var::V(..) => None,
+    }
+}
+
+impl Registry {
+    /// Picks an unused name for an anonymous variable
+    fn fresh_name(&self, var: Var) -> String {
+        // `Var` prints as `V(1a2b...)`, which isn't a single token
+        let base: String = var
+            .to_string()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let mut name = base.clone();
+        let mut i = 1;
+        while self.by_name.contains_key(&name) || is_reserved(&name) {
+            name = format!("{base}_{i}");
+            i += 1;
+        }
+        name
+    }
+}
@@ -0,0 +1,515 @@
+//! Export of narrow-band level sets in the OpenVDB file format.
+//!
+//! Only the subset of the format needed to store a single uncompressed
+//! `FloatGrid` (a `Tree_float_5_4_3`) is implemented. Tiles that are far from
+//! the surface are culled with interval arithmetic before any voxel is sampled,
+//! so only leaf nodes that intersect the narrow band are evaluated densely.
+use fidget::{
+    eval::Function,
+    shape::{EzShape, Shape},
+    types::Interval,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{BuildHasher, Hasher};
+
+const MAGIC: i64 = 0x56444220;
+/// File format version 222 (node mask compression), which is still read by
+/// every current OpenVDB release and doesn't require blosc or zlib.
+const FILE_VERSION: u32 = 222;
+const LIBRARY_VERSION: (u32, u32) = (3, 0);
+/// Written before every value buffer, meaning "all values follow, no mask"
+const NO_MASK_AND_ALL_VALS: u8 = 6;
+
+const LEAF_LOG2: u32 = 3;
+const INTERNAL1_LOG2: u32 = 4;
+const INTERNAL2_LOG2: u32 = 5;
+/// Width (in voxels) of the blocks covered by each tree level
+const LEAF_DIM: i32 = 1 << LEAF_LOG2;
+const INTERNAL1_DIM: i32 = 1 << (LEAF_LOG2 + INTERNAL1_LOG2);
+const INTERNAL2_DIM: i32 = 1 << (LEAF_LOG2 + INTERNAL1_LOG2 + INTERNAL2_LOG2);
+/// Half-width (in voxels) of the cube around the origin that's searched for
+/// the surface when no bounds are given, which is one root-level node
+const SEARCH_DIM: i32 = INTERNAL2_DIM;
+
+type Coord = [i32; 3];
+
+struct Leaf {
+    values: Vec<f32>,
+    mask: Vec<u64>,
+}
+
+/// An internal node, storing children by table index
+///
+/// Table entries without a child are inactive tiles: `inside` holds the ones
+/// that lie entirely within the surface, everything else is background.
+struct Internal<C> {
+    children: BTreeMap<u32, C>,
+    inside: BTreeSet<u32>,
+}
+
+impl<C> Default for Internal<C> {
+    fn default() -> Self {
+        Internal {
+            children: BTreeMap::new(),
+            inside: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Root {
+    children: BTreeMap<Coord, Internal<Internal<Leaf>>>,
+    inside: BTreeSet<Coord>,
+}
+
+/// Table index of `p` within a node at `origin`, for a node with `2^log2dim`
+/// entries per axis whose children span `2^child_log2` voxels.
+fn table_index(p: Coord, origin: Coord, log2dim: u32, child_log2: u32) -> u32 {
+    let d = |i: usize| ((p[i] - origin[i]) >> child_log2) as u32;
+    (d(0) << (2 * log2dim)) | (d(1) << log2dim) | d(2)
+}
+
+fn align(p: Coord, dim: i32) -> Coord {
+    [
+        p[0].div_euclid(dim) * dim,
+        p[1].div_euclid(dim) * dim,
+        p[2].div_euclid(dim) * dim,
+    ]
+}
+
+/// Narrow-band level set sampler
+pub struct LevelSet {
+    voxel_size: f32,
+    background: f32,
+    lower: Coord,
+    upper: Coord,
+    root: Root,
+}
+
+impl LevelSet {
+    /// Samples `shape` on a grid with the given voxel size, storing active
+    /// voxels within `half_width` voxels of the surface.
+    ///
+    /// `bounds` is `(xmin, xmax, ymin, ymax, zmin, zmax)` in world units. If
+    /// it's `None`, the shape's extent is found by interval culling alone,
+    /// within [`SEARCH_DIM`] voxels of the origin.
+    pub fn build<F: Function>(
+        shape: &Shape<F>,
+        voxel_size: f32,
+        half_width: f32,
+        bounds: Option<(f32, f32, f32, f32, f32, f32)>,
+    ) -> Result<Self, fidget::Error> {
+        let pad = half_width.ceil() as i32 + 1;
+        let lo = |v: f32| (v / voxel_size).floor() as i32 - pad;
+        let hi = |v: f32| (v / voxel_size).ceil() as i32 + pad;
+        let (lower, upper) = match bounds {
+            Some(b) => ([lo(b.0), lo(b.2), lo(b.4)], [hi(b.1), hi(b.3), hi(b.5)]),
+            None => ([-SEARCH_DIM; 3], [SEARCH_DIM - 1; 3]),
+        };
+        let mut out = LevelSet {
+            voxel_size,
+            background: half_width * voxel_size,
+            lower,
+            upper,
+            root: Root::default(),
+        };
+        let mut sampler = Sampler::new(shape, &out);
+        let start = align(out.lower, INTERNAL2_DIM);
+        let mut x = start[0];
+        while x <= out.upper[0] {
+            let mut y = start[1];
+            while y <= out.upper[1] {
+                let mut z = start[2];
+                while z <= out.upper[2] {
+                    let origin = [x, y, z];
+                    match sampler.classify(origin, INTERNAL2_DIM)? {
+                        Region::Outside => (),
+                        Region::Inside => {
+                            out.root.inside.insert(origin);
+                        }
+                        Region::Surface => {
+                            let node = sampler.internal2(origin)?;
+                            if !node.children.is_empty() || !node.inside.is_empty() {
+                                out.root.children.insert(origin, node);
+                            }
+                        }
+                    }
+                    z += INTERNAL2_DIM;
+                }
+                y += INTERNAL2_DIM;
+            }
+            x += INTERNAL2_DIM;
+        }
+        Ok(out)
+    }
+
+    /// Number of active voxels in the narrow band
+    pub fn active_voxel_count(&self) -> usize {
+        self.leaves()
+            .map(|l| {
+                l.mask
+                    .iter()
+                    .map(|m| m.count_ones() as usize)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    fn leaves(&self) -> impl Iterator<Item = &Leaf> {
+        self.root
+            .children
+            .values()
+            .flat_map(|n| n.children.values())
+            .flat_map(|n| n.children.values())
+    }
+
+    /// Serializes the grid as a complete `.vdb` file
+    pub fn to_bytes(&self, grid_name: &str) -> Vec<u8> {
+        let mut w = Writer::default();
+        // file header
+        w.i64(MAGIC);
+        w.u32(FILE_VERSION);
+        w.u32(LIBRARY_VERSION.0);
+        w.u32(LIBRARY_VERSION.1);
+        w.u8(1); // has grid offsets
+        w.raw(uuid().as_bytes());
+        w.u32(0); // file-level metadata
+        w.u32(1); // grid count
+
+        // grid descriptor
+        w.string(grid_name);
+        w.string("Tree_float_5_4_3");
+        w.string(""); // instance parent
+        let offsets = w.out.len();
+        w.i64(0);
+        w.i64(0);
+        w.i64(0);
+        let grid_pos = w.out.len();
+
+        w.u32(0); // no compression
+                  // grid metadata
+        w.u32(2);
+        w.string("class");
+        w.string("string");
+        w.u32("level set".len() as u32);
+        w.raw(b"level set");
+        w.string("is_saved_as_half_float");
+        w.string("bool");
+        w.u32(1);
+        w.u8(0);
+        // transform
+        w.string("UniformScaleMap");
+        let s = self.voxel_size as f64;
+        for v in [s, s, 1.0 / s, 1.0 / (s * s), 0.5 / s] {
+            w.vec3d(v);
+        }
+
+        self.write_topology(&mut w);
+        let block_pos = w.out.len();
+        self.write_buffers(&mut w);
+        let end_pos = w.out.len();
+
+        for (i, p) in [grid_pos, block_pos, end_pos].into_iter().enumerate() {
+            let at = offsets + i * 8;
+            w.out[at..at + 8].copy_from_slice(&(p as i64).to_le_bytes());
+        }
+        w.out
+    }
+
+    fn tile_value(&self, inside: bool) -> f32 {
+        if inside {
+            -self.background
+        } else {
+            self.background
+        }
+    }
+
+    fn write_topology(&self, w: &mut Writer) {
+        w.u32(1); // buffer count
+        w.f32(self.background);
+        w.u32(self.root.inside.len() as u32);
+        w.u32(self.root.children.len() as u32);
+        for origin in &self.root.inside {
+            w.coord(*origin);
+            w.f32(-self.background);
+            w.u8(0); // inactive
+        }
+        for (origin, node) in &self.root.children {
+            w.coord(*origin);
+            self.write_internal(w, node, INTERNAL2_LOG2, |w, n| {
+                self.write_internal(w, n, INTERNAL1_LOG2, |w, leaf| {
+                    w.words(&leaf.mask);
+                })
+            });
+        }
+    }
+
+    fn write_internal<C>(
+        &self,
+        w: &mut Writer,
+        node: &Internal<C>,
+        log2dim: u32,
+        child: impl Fn(&mut Writer, &C),
+    ) {
+        let size = 1usize << (3 * log2dim);
+        let mut child_mask = vec![0u64; size / 64];
+        for i in node.children.keys() {
+            child_mask[*i as usize / 64] |= 1 << (i % 64);
+        }
+        w.words(&child_mask);
+        w.words(&vec![0u64; size / 64]); // value mask: all tiles are inactive
+        w.u8(NO_MASK_AND_ALL_VALS);
+        for i in 0..size as u32 {
+            if node.children.contains_key(&i) {
+                w.f32(0.0);
+            } else {
+                w.f32(self.tile_value(node.inside.contains(&i)));
+            }
+        }
+        for c in node.children.values() {
+            child(w, c);
+        }
+    }
+
+    fn write_buffers(&self, w: &mut Writer) {
+        for leaf in self.leaves() {
+            w.words(&leaf.mask);
+            w.u8(NO_MASK_AND_ALL_VALS);
+            for v in &leaf.values {
+                w.f32(*v);
+            }
+        }
+    }
+}
+
+enum Region {
+    Outside,
+    Inside,
+    Surface,
+}
+
+struct Sampler<F: Function> {
+    interval: fidget::shape::ShapeTracingEval<F::IntervalEval>,
+    interval_tape:
+        fidget::shape::ShapeTape<<F::IntervalEval as fidget::eval::TracingEvaluator>::Tape>,
+    float: fidget::shape::ShapeBulkEval<F::FloatSliceEval>,
+    float_tape: fidget::shape::ShapeTape<<F::FloatSliceEval as fidget::eval::BulkEvaluator>::Tape>,
+    voxel_size: f32,
+    background: f32,
+    lower: Coord,
+    upper: Coord,
+}
+
+impl<F: Function> Sampler<F> {
+    fn new(shape: &Shape<F>, ls: &LevelSet) -> Self {
+        Sampler {
+            interval: Shape::<F>::new_interval_eval(),
+            interval_tape: shape.ez_interval_tape(),
+            float: Shape::<F>::new_float_slice_eval(),
+            float_tape: shape.ez_float_slice_tape(),
+            voxel_size: ls.voxel_size,
+            background: ls.background,
+            lower: ls.lower,
+            upper: ls.upper,
+        }
+    }
+
+    /// Checks whether the block is clipped away by the sampling bounds
+    fn clipped(&self, origin: Coord, dim: i32) -> bool {
+        (0..3).any(|i| origin[i] > self.upper[i] || origin[i] + dim <= self.lower[i])
+    }
+
+    /// Uses interval arithmetic to decide whether a block of voxels is
+    /// entirely outside of the narrow band
+    fn classify(&mut self, origin: Coord, dim: i32) -> Result<Region, fidget::Error> {
+        if self.clipped(origin, dim) {
+            return Ok(Region::Outside);
+        }
+        let range = |i: usize| {
+            Interval::new(
+                origin[i] as f32 * self.voxel_size,
+                (origin[i] + dim - 1) as f32 * self.voxel_size,
+            )
+        };
+        let (v, _trace) = self
+            .interval
+            .eval(&self.interval_tape, range(0), range(1), range(2))?;
+        Ok(if v.lower() >= self.background {
+            Region::Outside
+        } else if v.upper() <= -self.background {
+            Region::Inside
+        } else {
+            Region::Surface
+        })
+    }
+
+    fn children(origin: Coord, dim: i32, child_dim: i32) -> impl Iterator<Item = Coord> {
+        let n = dim / child_dim;
+        (0..n).flat_map(move |i| {
+            (0..n).flat_map(move |j| {
+                (0..n).map(move |k| {
+                    [
+                        origin[0] + i * child_dim,
+                        origin[1] + j * child_dim,
+                        origin[2] + k * child_dim,
+                    ]
+                })
+            })
+        })
+    }
+
+    fn internal2(&mut self, origin: Coord) -> Result<Internal<Internal<Leaf>>, fidget::Error> {
+        let mut node = Internal::default();
+        for c in Self::children(origin, INTERNAL2_DIM, INTERNAL1_DIM) {
+            let i = table_index(c, origin, INTERNAL2_LOG2, LEAF_LOG2 + INTERNAL1_LOG2);
+            match self.classify(c, INTERNAL1_DIM)? {
+                Region::Outside => (),
+                Region::Inside => {
+                    node.inside.insert(i);
+                }
+                Region::Surface => {
+                    let child = self.internal1(c)?;
+                    if !child.children.is_empty() || !child.inside.is_empty() {
+                        node.children.insert(i, child);
+                    }
+                }
+            }
+        }
+        Ok(node)
+    }
+
+    fn internal1(&mut self, origin: Coord) -> Result<Internal<Leaf>, fidget::Error> {
+        let mut node = Internal::default();
+        for c in Self::children(origin, INTERNAL1_DIM, LEAF_DIM) {
+            let i = table_index(c, origin, INTERNAL1_LOG2, LEAF_LOG2);
+            match self.classify(c, LEAF_DIM)? {
+                Region::Outside => (),
+                Region::Inside => {
+                    node.inside.insert(i);
+                }
+                Region::Surface => match self.leaf(c)? {
+                    Some(leaf) => {
+                        node.children.insert(i, leaf);
+                    }
+                    None => {
+                        // No active voxels, so the whole leaf has one sign
+                        if self.sample(c)? < 0.0 {
+                            node.inside.insert(i);
+                        }
+                    }
+                },
+            }
+        }
+        Ok(node)
+    }
+
+    fn sample(&mut self, p: Coord) -> Result<f32, fidget::Error> {
+        let s = self.voxel_size;
+        let out = self.float.eval(
+            &self.float_tape,
+            &[p[0] as f32 * s],
+            &[p[1] as f32 * s],
+            &[p[2] as f32 * s],
+        )?;
+        Ok(out[0])
+    }
+
+    fn leaf(&mut self, origin: Coord) -> Result<Option<Leaf>, fidget::Error> {
+        let n = (LEAF_DIM * LEAF_DIM * LEAF_DIM) as usize;
+        let (mut xs, mut ys, mut zs) = (
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+        );
+        // voxel offset within a leaf is (x << 6) | (y << 3) | z
+        for c in Self::children(origin, LEAF_DIM, 1) {
+            xs.push(c[0] as f32 * self.voxel_size);
+            ys.push(c[1] as f32 * self.voxel_size);
+            zs.push(c[2] as f32 * self.voxel_size);
+        }
+        let out = self.float.eval(&self.float_tape, &xs, &ys, &zs)?;
+        let mut leaf = Leaf {
+            values: Vec::with_capacity(n),
+            mask: vec![0u64; n / 64],
+        };
+        for (i, v) in out.iter().enumerate() {
+            if v.abs() < self.background {
+                leaf.mask[i / 64] |= 1 << (i % 64);
+                leaf.values.push(*v);
+            } else if *v < 0.0 {
+                leaf.values.push(-self.background);
+            } else {
+                leaf.values.push(self.background);
+            }
+        }
+        if leaf.mask.iter().all(|m| *m == 0) {
+            Ok(None)
+        } else {
+            Ok(Some(leaf))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn raw(&mut self, b: &[u8]) {
+        self.out.extend(b);
+    }
+    fn u8(&mut self, v: u8) {
+        self.out.push(v);
+    }
+    fn u32(&mut self, v: u32) {
+        self.raw(&v.to_le_bytes());
+    }
+    fn i64(&mut self, v: i64) {
+        self.raw(&v.to_le_bytes());
+    }
+    fn f32(&mut self, v: f32) {
+        self.raw(&v.to_le_bytes());
+    }
+    fn vec3d(&mut self, v: f64) {
+        for _ in 0..3 {
+            self.raw(&v.to_le_bytes());
+        }
+    }
+    fn coord(&mut self, c: Coord) {
+        for v in c {
+            self.raw(&v.to_le_bytes());
+        }
+    }
+    fn words(&mut self, w: &[u64]) {
+        for v in w {
+            self.raw(&v.to_le_bytes());
+        }
+    }
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.raw(s.as_bytes());
+    }
+}
+
+/// Generates a random version 4 UUID string, without pulling in a dependency
+fn uuid() -> String {
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        let r = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        chunk.copy_from_slice(&r.to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
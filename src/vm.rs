@@ -0,0 +1,375 @@
+//! Conversion between SSA tapes and the text format of `Context::from_text`.
+//!
+//! Besides the opcodes understood by `Context::from_text`, the format here
+//! allows `var-<name>` lines for variables other than x, y and z; names are
+//! resolved through the [`vars`](crate::vars) registry.
+use crate::{fold, vars};
+use fidget::{
+    compiler::{SsaOp, SsaTape},
+    context::{BinaryOpcode, Context, Node, UnaryOpcode},
+};
+use std::collections::HashMap;
+
+/// Accumulates lines of the text format, one node per line
+///
+/// Every line gets a fresh `$n` address. SSA registers and immediates are
+/// mapped onto those addresses as they're emitted; immediates are
+/// deduplicated so that each constant value is written once.
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    next: u32,
+    regs: HashMap<u32, u32>,
+    consts: HashMap<u32, u32>,
+    /// Full-precision values for the tape's 32-bit immediates, by bit pattern
+    precise: HashMap<u32, f64>,
+}
+
+impl TextWriter {
+    fn line(&mut self, op: &str, args: &[u32]) -> u32 {
+        let addr = self.next;
+        self.out.push_str(&format!("${addr} {op}"));
+        for a in args {
+            self.out.push_str(&format!(" ${a}"));
+        }
+        self.out.push('\n');
+        self.next += 1;
+        addr
+    }
+    fn constant(&mut self, imm: f32) -> u32 {
+        if let Some(addr) = self.consts.get(&imm.to_bits()) {
+            return *addr;
+        }
+        let addr = self.next;
+        match self.precise.get(&imm.to_bits()) {
+            Some(v) => self.out.push_str(&format!("${addr} const {v}\n")),
+            None => self.out.push_str(&format!("${addr} const {imm}\n")),
+        }
+        self.next += 1;
+        self.consts.insert(imm.to_bits(), addr);
+        addr
+    }
+    fn reg(&self, r: u32) -> Result<u32, String> {
+        match self.regs.get(&r) {
+            Some(a) => Ok(*a),
+            None => Err(format!("register {r} is used before it is written")),
+        }
+    }
+    /// Writes an operation on a register and an immediate, optionally with the
+    /// immediate as the left-hand argument
+    fn reg_imm(
+        &mut self,
+        op: &str,
+        out: u32,
+        arg: u32,
+        imm: f32,
+        swap: bool,
+    ) -> Result<(), String> {
+        let arg = self.reg(arg)?;
+        let imm = self.constant(imm);
+        let addr = if swap {
+            self.line(op, &[imm, arg])
+        } else {
+            self.line(op, &[arg, imm])
+        };
+        self.regs.insert(out, addr);
+        Ok(())
+    }
+}
+
+/// Converts a single-output SSA tape to text
+///
+/// `inputs` maps from input index (as used by [`SsaOp::Input`]) to the
+/// opcode used to declare that variable, e.g. `var-x`.
+///
+/// Tapes store immediates as `f32`; the original `f64` values of `constants`
+/// are written instead wherever they unambiguously match an immediate.
+pub fn tape_to_text(
+    tape: &SsaTape,
+    inputs: &HashMap<u32, String>,
+    constants: &[f64],
+) -> Result<String, String> {
+    let mut w = TextWriter::default();
+    let mut ambiguous = std::collections::HashSet::new();
+    for c in constants {
+        let bits = (*c as f32).to_bits();
+        match w.precise.insert(bits, *c) {
+            Some(prev) if prev.to_bits() != c.to_bits() => {
+                ambiguous.insert(bits);
+            }
+            _ => (),
+        }
+    }
+    for bits in ambiguous {
+        w.precise.remove(&bits);
+    }
+    let mut output = None;
+    // tapes are stored in reverse evaluation order
+    for &op in tape.tape.iter().rev() {
+        match op {
+            SsaOp::Output(arg, _i) => output = Some(arg),
+            SsaOp::Input(out, i) => {
+                let name = match inputs.get(&i) {
+                    Some(n) => n,
+                    None => return Err(format!("no name for input variable {i}")),
+                };
+                let addr = w.line(name, &[]);
+                w.regs.insert(out, addr);
+            }
+            SsaOp::CopyImm(out, imm) => {
+                let addr = w.constant(imm);
+                w.regs.insert(out, addr);
+            }
+            SsaOp::CopyReg(out, arg) => {
+                // the text format has no copy, so alias the source instead
+                let addr = w.reg(arg)?;
+                w.regs.insert(out, addr);
+            }
+            SsaOp::RecipReg(out, arg) => {
+                // nor does it have a reciprocal opcode
+                w.reg_imm("div", out, arg, 1.0, true)?;
+            }
+            SsaOp::NegReg(out, arg)
+            | SsaOp::AbsReg(out, arg)
+            | SsaOp::SqrtReg(out, arg)
+            | SsaOp::SquareReg(out, arg)
+            | SsaOp::FloorReg(out, arg)
+            | SsaOp::CeilReg(out, arg)
+            | SsaOp::RoundReg(out, arg)
+            | SsaOp::SinReg(out, arg)
+            | SsaOp::CosReg(out, arg)
+            | SsaOp::TanReg(out, arg)
+            | SsaOp::AsinReg(out, arg)
+            | SsaOp::AcosReg(out, arg)
+            | SsaOp::AtanReg(out, arg)
+            | SsaOp::ExpReg(out, arg)
+            | SsaOp::LnReg(out, arg)
+            | SsaOp::NotReg(out, arg) => {
+                let name = match op {
+                    SsaOp::NegReg(..) => "neg",
+                    SsaOp::AbsReg(..) => "abs",
+                    SsaOp::SqrtReg(..) => "sqrt",
+                    SsaOp::SquareReg(..) => "square",
+                    SsaOp::FloorReg(..) => "floor",
+                    SsaOp::CeilReg(..) => "ceil",
+                    SsaOp::RoundReg(..) => "round",
+                    SsaOp::SinReg(..) => "sin",
+                    SsaOp::CosReg(..) => "cos",
+                    SsaOp::TanReg(..) => "tan",
+                    SsaOp::AsinReg(..) => "asin",
+                    SsaOp::AcosReg(..) => "acos",
+                    SsaOp::AtanReg(..) => "atan",
+                    SsaOp::ExpReg(..) => "exp",
+                    SsaOp::LnReg(..) => "ln",
+                    SsaOp::NotReg(..) => "not",
+                    _ => unreachable!(),
+                };
+                let arg = w.reg(arg)?;
+                let addr = w.line(name, &[arg]);
+                w.regs.insert(out, addr);
+            }
+            SsaOp::AddRegReg(out, lhs, rhs)
+            | SsaOp::SubRegReg(out, lhs, rhs)
+            | SsaOp::MulRegReg(out, lhs, rhs)
+            | SsaOp::DivRegReg(out, lhs, rhs)
+            | SsaOp::AtanRegReg(out, lhs, rhs)
+            | SsaOp::MinRegReg(out, lhs, rhs)
+            | SsaOp::MaxRegReg(out, lhs, rhs)
+            | SsaOp::CompareRegReg(out, lhs, rhs)
+            | SsaOp::ModRegReg(out, lhs, rhs)
+            | SsaOp::AndRegReg(out, lhs, rhs)
+            | SsaOp::OrRegReg(out, lhs, rhs) => {
+                let name = match op {
+                    SsaOp::AddRegReg(..) => "add",
+                    SsaOp::SubRegReg(..) => "sub",
+                    SsaOp::MulRegReg(..) => "mul",
+                    SsaOp::DivRegReg(..) => "div",
+                    SsaOp::AtanRegReg(..) => "atan2",
+                    SsaOp::MinRegReg(..) => "min",
+                    SsaOp::MaxRegReg(..) => "max",
+                    SsaOp::CompareRegReg(..) => "compare",
+                    SsaOp::ModRegReg(..) => "mod",
+                    SsaOp::AndRegReg(..) => "and",
+                    SsaOp::OrRegReg(..) => "or",
+                    _ => unreachable!(),
+                };
+                let lhs = w.reg(lhs)?;
+                let rhs = w.reg(rhs)?;
+                let addr = w.line(name, &[lhs, rhs]);
+                w.regs.insert(out, addr);
+            }
+            SsaOp::AddRegImm(out, arg, imm)
+            | SsaOp::SubRegImm(out, arg, imm)
+            | SsaOp::SubImmReg(out, arg, imm)
+            | SsaOp::MulRegImm(out, arg, imm)
+            | SsaOp::DivRegImm(out, arg, imm)
+            | SsaOp::DivImmReg(out, arg, imm)
+            | SsaOp::AtanRegImm(out, arg, imm)
+            | SsaOp::AtanImmReg(out, arg, imm)
+            | SsaOp::MinRegImm(out, arg, imm)
+            | SsaOp::MaxRegImm(out, arg, imm)
+            | SsaOp::CompareRegImm(out, arg, imm)
+            | SsaOp::CompareImmReg(out, arg, imm)
+            | SsaOp::ModRegImm(out, arg, imm)
+            | SsaOp::ModImmReg(out, arg, imm)
+            | SsaOp::AndRegImm(out, arg, imm)
+            | SsaOp::OrRegImm(out, arg, imm) => {
+                let (name, swap) = match op {
+                    SsaOp::AddRegImm(..) => ("add", false),
+                    SsaOp::SubRegImm(..) => ("sub", false),
+                    SsaOp::SubImmReg(..) => ("sub", true),
+                    SsaOp::MulRegImm(..) => ("mul", false),
+                    SsaOp::DivRegImm(..) => ("div", false),
+                    SsaOp::DivImmReg(..) => ("div", true),
+                    SsaOp::AtanRegImm(..) => ("atan2", false),
+                    SsaOp::AtanImmReg(..) => ("atan2", true),
+                    SsaOp::MinRegImm(..) => ("min", false),
+                    SsaOp::MaxRegImm(..) => ("max", false),
+                    SsaOp::CompareRegImm(..) => ("compare", false),
+                    SsaOp::CompareImmReg(..) => ("compare", true),
+                    SsaOp::ModRegImm(..) => ("mod", false),
+                    SsaOp::ModImmReg(..) => ("mod", true),
+                    SsaOp::AndRegImm(..) => ("and", false),
+                    SsaOp::OrRegImm(..) => ("or", false),
+                    _ => unreachable!(),
+                };
+                w.reg_imm(name, out, arg, imm, swap)?;
+            }
+        }
+    }
+    // The parser treats the last line as the root, which may not be the case
+    // if the output was aliased or deduplicated; adding zero is folded away
+    // on import, so it makes a harmless explicit root.
+    let root = match output {
+        Some(r) => w.reg(r)?,
+        None => return Err("tape has no output".to_owned()),
+    };
+    if root + 1 != w.next {
+        let zero = w.constant(0.0);
+        w.line("add", &[root, zero]);
+    }
+    Ok(w.out)
+}
+
+/// Parses the text format into a new context, returning it and the root node
+///
+/// Each non-empty line that isn't a comment is `<address> <opcode> [args]`,
+/// where arguments refer to the addresses of earlier lines. The last line is
+/// the root.
+pub fn text_to_context(src: &str) -> Result<(Context, Node), String> {
+    let mut ctx = Context::new();
+    let mut seen: HashMap<&str, Node> = HashMap::new();
+    let mut last = None;
+    for (n, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {msg}", n + 1);
+        let mut iter = line.split_whitespace();
+        let addr = iter.next().unwrap();
+        let opcode = match iter.next() {
+            Some(op) => op,
+            None => return Err(err("missing opcode".to_owned())),
+        };
+        let mut args = vec![];
+        let node = if opcode == "const" {
+            let txt = iter.next().unwrap_or("");
+            match txt.parse::<f64>() {
+                Ok(v) => ctx.constant(v),
+                Err(..) => return Err(err(format!("invalid constant '{txt}'"))),
+            }
+        } else if let Some(name) = opcode.strip_prefix("var-") {
+            ctx.var(vars::lookup(name))
+        } else {
+            for txt in iter.by_ref() {
+                match seen.get(txt) {
+                    Some(node) => args.push(*node),
+                    None => return Err(err(format!("unknown address '{txt}'"))),
+                }
+            }
+            let built = match (unary_opcode(opcode), binary_opcode(opcode), &args[..]) {
+                (Some(op), _, &[a]) => fold::unary(&mut ctx, op, a),
+                (_, Some(op), &[a, b]) => fold::binary(&mut ctx, op, a, b),
+                (None, None, _) => return Err(err(format!("unknown opcode '{opcode}'"))),
+                _ => return Err(err(format!("wrong number of arguments for '{opcode}'"))),
+            };
+            match built {
+                Ok(node) => node,
+                Err(e) => return Err(err(e.to_string())),
+            }
+        };
+        if iter.next().is_some() {
+            return Err(err(format!("too many arguments for '{opcode}'")));
+        }
+        seen.insert(addr, node);
+        last = Some(node);
+    }
+    match last {
+        Some(node) => Ok((ctx, node)),
+        None => Err("file is empty".to_owned()),
+    }
+}
+
+/// Unary opcodes and their names in the text format
+///
+/// `recip` isn't understood by `Context::from_text`, so it's never written;
+/// it's accepted on import for completeness.
+const UNARY_NAMES: [(UnaryOpcode, &str); 17] = [
+    (UnaryOpcode::Neg, "neg"),
+    (UnaryOpcode::Abs, "abs"),
+    (UnaryOpcode::Recip, "recip"),
+    (UnaryOpcode::Sqrt, "sqrt"),
+    (UnaryOpcode::Square, "square"),
+    (UnaryOpcode::Floor, "floor"),
+    (UnaryOpcode::Ceil, "ceil"),
+    (UnaryOpcode::Round, "round"),
+    (UnaryOpcode::Sin, "sin"),
+    (UnaryOpcode::Cos, "cos"),
+    (UnaryOpcode::Tan, "tan"),
+    (UnaryOpcode::Asin, "asin"),
+    (UnaryOpcode::Acos, "acos"),
+    (UnaryOpcode::Atan, "atan"),
+    (UnaryOpcode::Exp, "exp"),
+    (UnaryOpcode::Ln, "ln"),
+    (UnaryOpcode::Not, "not"),
+];
+
+/// Binary opcodes and their names in the text format
+const BINARY_NAMES: [(BinaryOpcode, &str); 11] = [
+    (BinaryOpcode::Add, "add"),
+    (BinaryOpcode::Sub, "sub"),
+    (BinaryOpcode::Mul, "mul"),
+    (BinaryOpcode::Div, "div"),
+    (BinaryOpcode::Atan, "atan2"),
+    (BinaryOpcode::Min, "min"),
+    (BinaryOpcode::Max, "max"),
+    (BinaryOpcode::Compare, "compare"),
+    (BinaryOpcode::Mod, "mod"),
+    (BinaryOpcode::And, "and"),
+    (BinaryOpcode::Or, "or"),
+];
+
+fn unary_opcode(name: &str) -> Option<UnaryOpcode> {
+    UNARY_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(op, _)| *op)
+}
+
+fn binary_opcode(name: &str) -> Option<BinaryOpcode> {
+    BINARY_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(op, _)| *op)
+}
+
+/// Returns the text format name of a unary opcode
+pub fn unary_name(op: UnaryOpcode) -> &'static str {
+    UNARY_NAMES.iter().find(|(o, _)| *o == op).unwrap().1
+}
+
+/// Returns the text format name of a binary opcode
+pub fn binary_name(op: BinaryOpcode) -> &'static str {
+    BINARY_NAMES.iter().find(|(o, _)| *o == op).unwrap().1
+}
@@ -0,0 +1,135 @@
+//! Dense sampling of scalar fields, and export as raw / NRRD volumes.
+use fidget::{
+    eval::Function,
+    shape::{EzShape, Shape},
+};
+use rayon::prelude::*;
+
+/// A regular grid of samples, stored with x varying fastest
+pub struct Volume {
+    pub sizes: [usize; 3],
+    pub origin: [f32; 3],
+    pub spacing: [f32; 3],
+    pub data: Vec<f32>,
+}
+
+impl Volume {
+    /// Checks that `bounds` and `resolution` describe a usable grid: at least
+    /// one sample along each axis, and finite bounds that aren't inverted
+    /// (or empty, if there's more than one sample along that axis)
+    pub fn check(
+        bounds: (f32, f32, f32, f32, f32, f32),
+        resolution: (usize, usize, usize),
+    ) -> Result<(), String> {
+        let axes = [
+            ('x', bounds.0, bounds.1, resolution.0),
+            ('y', bounds.2, bounds.3, resolution.1),
+            ('z', bounds.4, bounds.5, resolution.2),
+        ];
+        for (axis, lo, hi, n) in axes {
+            if n == 0 {
+                return Err(format!("resolution along {axis} must be at least 1"));
+            }
+            if !lo.is_finite() || !hi.is_finite() {
+                return Err(format!(
+                    "bounds along {axis} must be finite, got {lo}..{hi}"
+                ));
+            }
+            if lo > hi || (lo == hi && n > 1) {
+                return Err(format!(
+                    "bounds along {axis} are empty or inverted, got {lo}..{hi} with {n} samples"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Samples `shape` at `resolution` points along each axis, with the first
+    /// and last samples lying on the faces of `bounds`.
+    ///
+    /// `bounds` is `(xmin, xmax, ymin, ymax, zmin, zmax)` in world units.
+    pub fn sample<F: Function>(
+        shape: &Shape<F>,
+        bounds: (f32, f32, f32, f32, f32, f32),
+        resolution: (usize, usize, usize),
+    ) -> Result<Self, fidget::Error> {
+        let sizes = [resolution.0, resolution.1, resolution.2];
+        let lower = [bounds.0, bounds.2, bounds.4];
+        let upper = [bounds.1, bounds.3, bounds.5];
+        let mut spacing = [0.0; 3];
+        for i in 0..3 {
+            spacing[i] = if sizes[i] > 1 {
+                (upper[i] - lower[i]) / (sizes[i] - 1) as f32
+            } else {
+                0.0
+            };
+        }
+        let (nx, ny) = (sizes[0], sizes[1]);
+        let mut xs = Vec::with_capacity(nx * ny);
+        let mut ys = Vec::with_capacity(nx * ny);
+        for j in 0..ny {
+            for i in 0..nx {
+                xs.push(lower[0] + i as f32 * spacing[0]);
+                ys.push(lower[1] + j as f32 * spacing[1]);
+            }
+        }
+        // evaluate one z slice at a time, spread across threads
+        let slices = (0..sizes[2])
+            .into_par_iter()
+            .map_init(
+                || {
+                    (
+                        Shape::<F>::new_float_slice_eval(),
+                        shape.ez_float_slice_tape(),
+                    )
+                },
+                |(eval, tape), k| {
+                    let zs = vec![lower[2] + k as f32 * spacing[2]; nx * ny];
+                    eval.eval(tape, &xs, &ys, &zs).map(|out| out.to_vec())
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Volume {
+            sizes,
+            origin: lower,
+            spacing,
+            data: slices.concat(),
+        })
+    }
+
+    /// Sample data as little-endian 32-bit floats
+    pub fn raw_bytes(&self) -> Vec<u8> {
+        self.data.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// Builds a NRRD header describing this volume
+    ///
+    /// If `data_file` is given, the header is a detached header (`.nhdr`)
+    /// pointing at that raw file; otherwise the raw data must be appended
+    /// directly after the header.
+    pub fn nrrd_header(&self, data_file: Option<&str>) -> String {
+        let [nx, ny, nz] = self.sizes;
+        let [ox, oy, oz] = self.origin;
+        let [dx, dy, dz] = self.spacing;
+        let mut out = String::from("NRRD0004\n");
+        out.push_str("# Complete NRRD file format specification at:\n");
+        out.push_str("# http://teem.sourceforge.net/nrrd/format.html\n");
+        out.push_str("type: float\n");
+        out.push_str("dimension: 3\n");
+        out.push_str("space dimension: 3\n");
+        out.push_str(&format!("sizes: {nx} {ny} {nz}\n"));
+        out.push_str(&format!(
+            "space directions: ({dx},0,0) (0,{dy},0) (0,0,{dz})\n"
+        ));
+        out.push_str("kinds: domain domain domain\n");
+        out.push_str("endian: little\n");
+        out.push_str("encoding: raw\n");
+        out.push_str(&format!("space origin: ({ox},{oy},{oz})\n"));
+        if let Some(f) = data_file {
+            out.push_str(&format!("data file: {f}\n"));
+        }
+        // a blank line separates the header from attached data
+        out.push('\n');
+        out
+    }
+}
@@ -1,5 +1,17 @@
+import io
+import json
 import math
+import pickle
+import random
+import struct
+import zipfile
+from collections import Counter
+import pytest
 from fidgetpy import shapes
+from fidgetpy.types import BoundBox, Shape, Tree
+from fidgetpy._core import BACKENDS, mesh_multi as _mesh_multi
+from fidgetpy._core import transforms as _transforms
+from fidgetpy.errors import ShapeBoundsWarning
 
 eps = 1e-10
 
@@ -50,3 +62,547 @@ def test_extrude():
     m = s.mesh(5)
     assert len(m.vertices) > 1000
     assert len(m.triangles) > 1000
+
+
+def test_vdb_export(tmp_path):
+    path = tmp_path / "sphere.vdb"
+    s = shapes.sphere(1.0)
+    active = s.to_vdb(path, 0.1, 3.0)
+    data = path.read_bytes()
+    # magic number and file format version
+    assert data[:8] == b" BDV\x00\x00\x00\x00"
+    assert int.from_bytes(data[8:12], "little") == 222
+    # roughly one shell of 6 voxels around the surface area of the sphere
+    assert 0.5 * 6 * 4 * math.pi / 0.1**2 < active < 2 * 6 * 4 * math.pi / 0.1**2
+
+    # without bounds, the band is found by interval culling alone
+    assert s.tree.to_vdb(str(tmp_path / "tree.vdb"), 0.1, 3.0) == active
+
+
+def test_mesh_attributes():
+    z = Tree.z()
+    s = shapes.sphere(1.0)
+    m = s.mesh(4, attributes={"height": z, "dist": s.tree + 1.0})
+    n = len(m.vertices)
+    assert list(m.attributes) == ["height", "dist"]
+    assert all(abs(h - v[2]) < 1e-6 for h, v in zip(m.attributes["height"], m.vertices))
+
+    header, body = m.to_ply(color="height").split(b"end_header\n", 1)
+    assert f"element vertex {n}".encode() in header
+    assert b"property float height\nproperty float dist\n" in header
+    assert b"property uchar red" in header
+    # xyz + 2 attributes as floats, then rgb, then faces as count + 3 indices
+    assert len(body) == n * (5 * 4 + 3) + len(m.triangles) * 13
+    x, y, z0, h, d = struct.unpack_from("<5f", body)
+    assert (x, y, z0) == m.vertices[0] and h == m.attributes["height"][0]
+
+    obj = m.to_obj(color="dist").splitlines()
+    assert sum(line.startswith("v ") for line in obj) == n
+    assert all(len(line.split()) == 7 for line in obj if line.startswith("v "))
+    assert m.to_obj().splitlines()[1].count(" ") == 3
+
+    glb = m.to_glb(color="height")
+    assert glb[:4] == b"glTF" and len(glb) == struct.unpack_from("<I", glb, 8)[0]
+    json_len, kind = struct.unpack_from("<I4s", glb, 12)
+    assert kind == b"JSON"
+    gltf = json.loads(glb[20 : 20 + json_len])
+    attrs = gltf["meshes"][0]["primitives"][0]["attributes"]
+    assert set(attrs) == {"POSITION", "_HEIGHT", "_DIST", "COLOR_0"}
+    assert gltf["accessors"][attrs["_DIST"]]["count"] == n
+
+    with pytest.raises(RuntimeError):
+        m.to_ply(color="missing")
+    with pytest.raises(RuntimeError):
+        s.mesh(3, attributes={"bad name": z})
+
+
+def test_mesh_multi():
+    ball = shapes.sphere(0.8)
+    x = Tree.x()
+    left = Shape(ball.tree.max(x), ball.bounds)
+    right = Shape(ball.tree.max(-x), ball.bounds)
+    m = shapes.mesh_multi([left, right], 5)
+    assert len(m.materials) == len(m.triangles)
+    assert set(m.materials) == {0, 1}
+
+    parts = m.split_materials()
+    assert len(parts) == 2
+    for part in parts:
+        # closed: every directed edge is matched by one going the other way
+        edges = Counter()
+        for t in part.triangles:
+            for i in range(3):
+                edges[t[i], t[(i + 1) % 3]] += 1
+        assert all(edges[b, a] == n for (a, b), n in edges.items())
+
+    # the halves share the interface at x = 0, with opposite winding
+    def faces(part, flip=False):
+        out = set()
+        vertices = part.vertices
+        for t in part.triangles:
+            v = [vertices[i] for i in (reversed(t) if flip else t)]
+            i = v.index(min(v))
+            out.add(tuple(v[i:] + v[:i]))
+        return out
+
+    shared = faces(parts[0]) & faces(parts[1], flip=True)
+    assert len(shared) > 100
+    assert not faces(parts[0]) & faces(parts[1])
+    assert all(abs(v[0]) < 1e-6 for f in shared for v in f)
+
+    archive = zipfile.ZipFile(io.BytesIO(m.to_3mf(["left", "right"])))
+    assert archive.testzip() is None
+    model = archive.read("3D/3dmodel.model").decode()
+    assert model.count("<object ") == 2 and 'name="right"' in model
+    with pytest.raises(RuntimeError):
+        m.to_3mf(["only one"])
+
+    # materials that extend past the region are closed off at its faces
+    big = Shape(shapes.sphere(3).tree.max(x), ball.bounds)
+    m = _mesh_multi([big.tree, right.tree], 4, 0, 0, 0, 1)
+    assert set(m.materials) == {0, 1}
+    for part in m.split_materials():
+        edges = Counter()
+        for t in part.triangles:
+            for i in range(3):
+                edges[t[i], t[(i + 1) % 3]] += 1
+        assert all(edges[b, a] == n for (a, b), n in edges.items())
+        assert all(max(abs(c) for c in v) < 1 + 1e-6 for v in part.vertices)
+
+    with pytest.raises(ShapeBoundsWarning):
+        shapes.mesh_multi([left, shapes.circle(1)], 4)
+    with pytest.raises(ValueError):
+        shapes.mesh_multi([], 4)
+
+
+def test_native_primitives():
+    # concave: (1, 1.5) is in the notch
+    notched = shapes.polygon([(0, 0), (2, 0), (2, 2), (1, 1), (0, 2)])
+    checks = [
+        # shape, point, expected distance
+        (shapes.rectangle(2, 4), (0, 3, 9), 1.0),
+        (shapes.cone(1, 2), (0, 0, 3), 1.0),
+        (shapes.cone(1, 2), (0, 0, 1), -1 / math.sqrt(5)),
+        (shapes.cone(1, 2, 0.5), (0, 0, 2.5), 0.5),
+        (shapes.capsule((0, 0, 0), (0, 0, 2), 0.5), (1, 0, 1), 0.5),
+        (shapes.ellipsoid(1, 2, 3), (0, 0, 0), -1.0),
+        (shapes.ellipsoid(2, 2, 2), (3, 0, 0), 1.0),
+        (shapes.hexagonal_prism(1, 1), (0, 2, 0), 1.0),
+        (shapes.hexagonal_prism(1, 1), (2 / math.sqrt(3), 0, 0), 0.0),
+        (shapes.wedge(2, 2, 2), (0.9, 0, 0.9), 1.8 / math.sqrt(2)),
+        (shapes.wedge(2, 2, 2), (-0.9, 0, -0.9), -0.1),
+        (shapes.rounded_box(2, 2, 2, 0.5), (2, 2, 2), 1.5 * 3**0.5 - 0.5),
+        (notched, (1, 1.5, 0), 0.5**1.5),
+        (notched, (1, 0.5, 0), -0.5),
+    ]
+    for shape, p, expected in checks:
+        assert abs(shape.eval(*p) - expected) < 1e-5, (shape, p)
+
+    # the field is negative only inside the bounds
+    for shape in [
+        shapes.box(1, 2, 3),
+        shapes.cone(1, 2),
+        shapes.capsule((0, 0, 0), (1, 2, 3), 0.5),
+        shapes.ellipsoid(1, 2, 3),
+        shapes.hexagonal_prism(1, 2),
+        shapes.wedge(1, 2, 3),
+        shapes.rounded_box(1, 2, 3, 0.2),
+    ]:
+        b = shape.bounds
+        for i in range(-4, 5):
+            for j in range(-4, 5):
+                for k in range(-4, 5):
+                    x, y, z = i * 0.5, j * 0.5, k * 0.5
+                    if shape.eval(x, y, z) < 0:
+                        assert b.xmin <= x <= b.xmax
+                        assert b.ymin <= y <= b.ymax
+                        assert b.zmin <= z <= b.zmax
+        assert len(shape.mesh(4).triangles) > 0
+
+    with pytest.raises(RuntimeError):
+        shapes.polygon([(0, 0), (1, 0)])
+    with pytest.raises(RuntimeError):
+        shapes.sphere(-1)
+
+
+def test_transforms():
+    b = shapes.box(2, 4, 6)
+    r = shapes.rotate(b, "z", math.pi / 2)
+    # the long y side now lies along x
+    assert abs(r.eval(3, 0, 0) - 1.0) < 1e-5
+    assert abs(r.bounds.xmax - 2) < 1e-9 and abs(r.bounds.ymax - 1) < 1e-9
+
+    # rotating about an arbitrary axis preserves distances
+    r = shapes.rotate(shapes.move(b, 1, 0, 0), (1, 1, 1), 0.7)
+    m = _transforms.rotation((1, 1, 1), 0.7)
+    p = (0.3, -0.2, 2.5)
+    q = [sum(m[i][j] * p[j] for j in range(3)) + m[i][3] for i in range(3)]
+    assert abs(r.eval(*q) - b.eval(p[0] - 1, p[1], p[2])) < 1e-5
+
+    s = shapes.scale(shapes.sphere(1), 2, 1, 1)
+    assert abs(s.eval(2, 0, 0)) < 1e-6 and abs(s.eval(0, 1, 0)) < 1e-6
+    assert (s.bounds.xmin, s.bounds.xmax) == (-2, 2)
+
+    # uniform scaling scales the field too, so distances stay exact
+    s = shapes.scale(shapes.sphere(1), 2)
+    assert abs(s.eval(0, 0, 0) + 2) < 1e-6
+    assert abs(s.eval(3, 0, 0) - 1) < 1e-6
+    r = shapes.scale(shapes.sphere(1), -0.5)
+    assert abs(r.eval(0, 0, 0) + 0.5) < 1e-6
+
+    w = shapes.mirror(shapes.wedge(2, 2, 2), "yz")
+    assert w.eval(0.9, 0, -0.9) < 0 and w.eval(-0.9, 0, 0.9) > 0
+    # mirroring twice is the identity
+    w2 = shapes.mirror(w, (1, 0, 0))
+    p = (0.3, 0.2, 0.1)
+    assert abs(w2.eval(*p) - shapes.wedge(2, 2, 2).eval(*p)) < 1e-9
+
+    # 2D shapes keep their infinite extent along z
+    c = shapes.transform(shapes.circle(1), _transforms.translation(1, 2, 3))
+    assert c.bounds.zmin == -math.inf and c.bounds.xmax == 2
+    assert abs(c.eval(1, 2, 100) + 1) < 1e-9
+
+    with pytest.raises(RuntimeError):
+        b.tree.transform(_transforms.scaling(0))
+    with pytest.raises(RuntimeError):
+        b.tree.transform([[1, 0, 0, 0]] * 4)
+
+
+@pytest.mark.parametrize(
+    "op",
+    [
+        lambda a, b: shapes.smooth_union(a, b, 0.5),
+        lambda a, b: shapes.smooth_union(a, b, 0.5, "exponential"),
+        lambda a, b: shapes.smooth_intersection(a, b, 0.5),
+        lambda a, b: shapes.smooth_intersection(a, b, 0.5, "exponential"),
+        lambda a, b: shapes.smooth_difference(a, b, 0.5),
+        lambda a, b: shapes.smooth_difference(a, b, 0.5, "exponential"),
+        lambda a, b: shapes.chamfer_union(a, b, 0.5),
+        lambda a, b: shapes.chamfer_intersection(a, b, 0.5),
+        lambda a, b: shapes.chamfer_difference(a, b, 0.5),
+    ],
+)
+def test_blend_lipschitz(op):
+    rng = random.Random(0)
+    a = shapes.box(2, 2, 2)
+    b = shapes.move(shapes.sphere(1), 0.8, 0.6, 0.4)
+    s = op(a, b)
+    for _ in range(200):
+        p = [rng.uniform(-2, 2) for _ in range(3)]
+        # mostly short steps, which measure the local gradient
+        step = rng.choice([1e-3, 1e-2, 1.0])
+        q = [c + rng.uniform(-step, step) for c in p]
+        assert abs(s.eval(*p) - s.eval(*q)) <= math.dist(p, q) * (1 + 1e-6)
+
+    m = s.mesh(4)
+    assert len(m.triangles) > 100
+    assert all(math.isfinite(c) for v in m.vertices for c in v)
+
+
+def test_blends():
+    a = shapes.box(2, 2, 2)
+    b = shapes.move(shapes.box(2, 2, 2), 1, 1, 0)
+    k = 0.4
+    u = shapes.union(a, b)
+    su = shapes.smooth_union(a, b, k)
+    se = shapes.smooth_union(a, b, k, "exponential")
+    rng = random.Random(1)
+    for _ in range(200):
+        p = [rng.uniform(-2, 3) for _ in range(3)]
+        d, da, db = u.eval(*p), a.eval(*p), b.eval(*p)
+        assert d - k / 4 - 1e-9 <= su.eval(*p) <= d + 1e-9
+        assert d - k * math.log(2) - 1e-9 <= se.eval(*p) <= d + 1e-9
+        if abs(da - db) >= k:
+            assert abs(su.eval(*p) - d) < 1e-9
+
+    # the inside corner at (1, 0), between the walls x = 1 and y = 0, is
+    # filled in
+    assert u.eval(1.05, -0.05, 0) > 0
+    assert su.eval(1.05, -0.05, 0) < 0
+    # and the chamfer cuts it off flat, along the plane x - y = 1 + k
+    c = shapes.chamfer_union(a, b, k)
+    assert abs(c.eval(1.2, -0.2, 0)) < 1e-5
+    assert c.eval(1.15, -0.15, 0) < 0 < c.eval(1.25, -0.25, 0)
+
+    # intersections and differences round and bevel the outside edges
+    si = shapes.smooth_intersection(a, b, k)
+    assert a.tree.max(b.tree).eval(0.95, 0.05, 0) < 0
+    assert si.eval(0.95, 0.05, 0) > 0
+    cd = shapes.chamfer_difference(a, b, k)
+    assert abs(cd.eval(0.8, -0.2, 0)) < 1e-5
+    assert shapes.difference(a, b).eval(0.8, -0.2, 0) < 0
+
+    # the Tree methods are the same operations
+    t = a.tree.smooth_union(b.tree, k)
+    assert t.eval(1.05, -0.05, 0) == su.eval(1.05, -0.05, 0)
+
+    with pytest.raises(RuntimeError):
+        a.tree.smooth_union(b.tree, 0)
+    with pytest.raises(RuntimeError):
+        a.tree.chamfer_union(b.tree, -1)
+    with pytest.raises(RuntimeError):
+        a.tree.smooth_union(b.tree, k, "cubic")
+
+
+def test_repeat():
+    rng = random.Random(2)
+    # the sphere sticks out of its cell, into the next one along x
+    s = shapes.move(shapes.sphere(0.4), 0.3, 0.1, 0)
+    r = shapes.repeat(s, (1, 1.5, 0), (4, 2, 1))
+    m = shapes.mirror_repeat(s, (1, 1.5, 0))
+    for _ in range(200):
+        x, y, z = (rng.uniform(-2, 5) for _ in range(3))
+        # brute force over the copies
+        copies = [s.eval(x - i, y - 1.5 * j, z) for i in range(4) for j in range(2)]
+        assert abs(r.eval(x, y, z) - min(copies)) < 1e-9
+        mirrored = [
+            s.eval((x - i) * (-1) ** i, (y - 1.5 * j) * (-1) ** j, z)
+            for i in range(-3, 7)
+            for j in range(-2, 5)
+        ]
+        assert abs(m.eval(x, y, z) - min(mirrored)) < 1e-9
+    assert abs(r.bounds.xmin + 0.1) < 1e-9 and abs(r.bounds.xmax - 3.7) < 1e-9
+    assert abs(r.bounds.ymax - 2.0) < 1e-9 and r.bounds.zmax == s.bounds.zmax
+    assert m.bounds.xmax == math.inf and m.bounds.zmin == s.bounds.zmin
+
+    mb = shapes.mirror_repeat(s, (1, 0, 0), (2, 1, 1))
+    # the second copy is mirrored about x = 1, so it's centered at 0.7 and
+    # spans 0.3 .. 1.1
+    assert abs(mb.bounds.xmax - 1.1) < 1e-9 and abs(mb.eval(0.7, 0.1, 0) + 0.4) < 1e-9
+
+    # a bolt circle of holes
+    hole = shapes.move(shapes.cylinder(0.2, 1), 1.5, 0, 0)
+    bolts = shapes.polar_repeat(hole, 5)
+    for _ in range(200):
+        x, y, z = (rng.uniform(-2, 2) for _ in range(3))
+        copies = []
+        for i in range(5):
+            a = -i * math.tau / 5
+            u = x * math.cos(a) - y * math.sin(a)
+            v = x * math.sin(a) + y * math.cos(a)
+            copies.append(hole.eval(u, v, z))
+        assert abs(bolts.eval(x, y, z) - min(copies)) < 1e-6
+    assert abs(bolts.bounds.xmax - math.hypot(1.7, 0.2)) < 1e-9
+    assert bolts.bounds.zmin == -1 and bolts.bounds.zmax == 1
+    # around another axis, the axis itself is left alone
+    px = shapes.polar_repeat(hole, 3, "x")
+    assert (px.bounds.xmin, px.bounds.xmax) == (hole.bounds.xmin, hole.bounds.xmax)
+    assert len(px.mesh(5).triangles) > 100
+
+    with pytest.raises(RuntimeError):
+        s.tree.repeat((1, -1, 0))
+    with pytest.raises(RuntimeError):
+        s.tree.repeat((1, 1, 0), (0, 1, 1))
+    with pytest.raises(RuntimeError):
+        s.tree.polar_repeat(0)
+    with pytest.raises(RuntimeError):
+        s.tree.polar_repeat(3, "w")
+
+
+def test_offsets():
+    s = shapes.sphere(1)
+    b = shapes.box(2, 2, 2)
+    assert s.exact and b.exact and not shapes.ellipsoid(1, 2, 3).exact
+    # similarity transforms keep the flag, and repetition clears it
+    assert shapes.move(s, 1, 2, 3).exact
+    assert shapes.rotate(b, (1, 2, 3), 0.4).exact
+    assert shapes.mirror(b, "xy").exact
+    assert not shapes.polar_repeat(shapes.move(s, 3, 0, 0), 4).exact
+    assert not shapes.repeat(s, (3, 0, 0)).exact
+    big = shapes.scale(s, 2)
+    assert big.exact and abs(big.eval(0, 0, 0) + 2) < 1e-9
+    assert not shapes.scale(s, 2, 1, 1).exact
+    # booleans and arithmetic clear it
+    u = shapes.union(s, shapes.move(b, 1, 0, 0))
+    assert not u.exact
+    assert not (s.tree * 1.0).exact
+
+    grown = shapes.offset(b, 0.5)
+    assert grown.exact and abs(grown.eval(2, 2, 0) - (math.sqrt(2) - 0.5)) < 1e-5
+    assert grown.bounds.xmax == 1.5
+    shrunk = shapes.offset(b, -0.5)
+    assert not shrunk.exact and abs(shrunk.eval(0.5, 0, 0)) < 1e-5
+    assert not shapes.expand(u, 0.1).exact
+
+    # re-distancing fixes a stretched sphere near its surface, and is the
+    # default when growing an inexact field
+    e = shapes.scale(s, 2, 1, 1)
+    assert abs(shapes.offset(e, 0.5).eval(2.5, 0, 0)) < 1e-4
+    with pytest.raises(ShapeBoundsWarning):
+        shapes.offset(e, 0.5, redistance=False)
+    with pytest.raises(ShapeBoundsWarning):
+        shapes.thicken_surface(e, 0.4, redistance=False)
+    assert shapes.offset(e, -0.5, redistance=False).bounds == e.bounds
+    # without it, the surface of a grown bound can leave its bounding box
+    # and be clipped by the mesher
+    o = shapes.offset(shapes.scale(s, 1, 1, 4), 1)
+    assert o.eval(0, 0, 6) > 0 and o.bounds.zmax == 5
+    long = shapes.scale(s, 1, 1, 10)
+    clipped = Shape(long.tree.offset(2), shapes.offset(long, 2).bounds)
+    for shape, closed in [(shapes.offset(long, 2), True), (clipped, False)]:
+        edges = Counter()
+        for t in shape.mesh(6).triangles:
+            for i in range(3):
+                edges[t[i], t[(i + 1) % 3]] += 1
+        assert all(edges[b, a] == n for (a, b), n in edges.items()) == closed
+    fixed = shapes.offset(e, 0.5, redistance=True)
+    assert abs(fixed.eval(2.5, 0, 0)) < 1e-4
+    assert abs(fixed.eval(0, 1.5, 0)) < 1e-4
+    assert math.isfinite(fixed.eval(0, 0, 0))
+    # and leaves exact fields alone
+    p = (0.3, 0.2, 0.1)
+    assert abs(shapes.offset(s, 0.5, True).eval(*p) - (s.eval(*p) - 0.5)) < 1e-4
+    assert not shapes.offset(s, 0.5, True).exact
+    assert not shapes.thicken_surface(s, 0.4, True).exact
+    assert len(fixed.mesh(4).triangles) > 100
+    # including booleans, whose gradients are discontinuous
+    for backend in BACKENDS:
+        m = shapes.offset(u, 0.1, redistance=True).mesh(4, backend=backend)
+        assert len(m.triangles) > 100
+
+    h = shapes.shell(s, 0.2)
+    assert not h.exact
+    assert h.eval(0.9, 0, 0) < 0 < h.eval(0.5, 0, 0) and h.eval(1.1, 0, 0) > 0
+    m = h.mesh(5)
+    # two nested spheres
+    radii = [math.hypot(*v) for v in m.vertices]
+    assert min(radii) < 0.85 and max(radii) > 0.95
+
+    t = shapes.thicken_surface(s, 0.4)
+    assert t.exact and abs(t.eval(0, 0, 0) - 0.8) < 1e-9
+    assert abs(t.eval(1.2, 0, 0)) < 1e-9 and t.bounds.zmax == 1.2
+
+    # the flag survives pickling
+    assert pickle.loads(pickle.dumps(s.tree)).exact
+    assert not pickle.loads(pickle.dumps(u.tree)).exact
+
+    with pytest.raises(RuntimeError):
+        s.tree.shell(0)
+    with pytest.raises(RuntimeError):
+        s.tree.offset(math.inf)
+
+
+def test_extrude_general():
+    # a straight, centered extrusion of a circle is a cylinder
+    c = shapes.extrude(shapes.circle(1), 2, center=True)
+    cyl = shapes.cylinder(1, 1)
+    assert c.exact and c.bounds == cyl.bounds
+    for p in [(0, 0, 0), (2, 0, 0), (0, 0, 3), (2, 0, 3), (0.5, 0.2, 0.9)]:
+        assert abs(c.eval(*p) - cyl.eval(*p)) < 1e-9
+
+    # along other axes, the profile's axes are cycled
+    r = shapes.rectangle(2, 0.5)
+    along_x = shapes.extrude(r, 3, axis="x")
+    assert (along_x.bounds.xmin, along_x.bounds.xmax) == (0, 3)
+    assert along_x.bounds.zmax == 0.25 and along_x.eval(1.5, 0.9, 0) < 0
+    diagonal = shapes.extrude(shapes.circle(0.5), 2, axis=(1, 1, 0))
+    d = 2 / math.sqrt(2)
+    assert diagonal.eval(d - 0.1, d - 0.1, 0) < 0 < diagonal.eval(d, d, 0.6)
+    assert diagonal.bounds.xmax >= d + 0.5 / math.sqrt(2)
+
+    # a quarter turn swaps the rectangle's sides between bottom and top
+    t = shapes.extrude(r, 1, twist=90)
+    assert not t.exact
+    assert t.eval(0.9, 0, 0.05) < 0 < t.eval(0, 0.9, 0.05)
+    assert t.eval(0, 0.9, 0.95) < 0 < t.eval(0.9, 0, 0.95)
+    # a drafted cylinder, half as wide at the top
+    k = shapes.extrude(shapes.circle(1), 1, taper=0.5)
+    assert k.eval(0.7, 0, 0.5) < 0 < k.eval(0.8, 0, 0.5)
+    assert k.eval(0.45, 0, 0.99) < 0 < k.eval(0.55, 0, 0.99)
+
+    # twisted and tapered fields stay bounds: never more than the distance
+    # to the nearest surface vertex
+    rng = random.Random(3)
+    for s in [t, k, shapes.extrude(r, 1, twist=720, taper=1.5)]:
+        verts = s.mesh(5).vertices
+        for _ in range(30):
+            p = [rng.uniform(-1.5, 1.5), rng.uniform(-1.5, 1.5), rng.uniform(-0.5, 1.5)]
+            nearest = min(math.dist(p, v) for v in verts)
+            assert abs(s.eval(*p)) <= nearest + 0.02
+
+    with pytest.raises(RuntimeError):
+        shapes.extrude(r, 0)
+    with pytest.raises(RuntimeError):
+        shapes.extrude(r, 1, taper=0)
+    with pytest.raises(RuntimeError):
+        half_plane = BoundBox(-math.inf, 0, -1, 1, -math.inf, math.inf)
+        shapes.extrude(Shape(Tree.x(), half_plane), 1, twist=10)
+
+
+def test_sweep():
+    # a straight sweep along z is an extrusion, cut flat at the ends
+    c = shapes.circle(0.5)
+    s = shapes.sweep(c, [(0, 0, 0), (0, 0, 2)])
+    assert not s.exact
+    assert s.eval(0, 0, 1) == -0.5 and s.eval(1, 0, 1) == 0.5
+    assert s.eval(0, 0, 3) == 1 and s.eval(0, 0, -1) == 1
+
+    # an L-shaped path is mitred at its corner, with no seam inside
+    s = shapes.sweep(c, [(0, 0, 0), (2, 0, 0), (2, 2, 0)])
+    assert s.eval(2, 0, 0) < 0 and s.eval(1.9, 0, 0) < 0
+    assert s.eval(2.4, -0.4, 0) < 0 < s.eval(2.6, -0.6, 0)
+    for p in [(2.5, -0.5, 0), (1, 0, 0.5), (2, 1, 0.5), (2.5, 1, 0)]:
+        assert abs(s.eval(*p)) < 1e-9
+    assert s.bounds.xmax >= 2.5 and s.bounds.ymin <= -0.5
+
+    # a closed polygonal ring is close to a torus
+    n = 64
+    ring = [
+        (2 * math.cos(math.tau * i / n), 2 * math.sin(math.tau * i / n), 0)
+        for i in range(n)
+    ]
+    s = shapes.sweep(c, ring, closed=True)
+    t = shapes.torus(2, 0.5)
+    rng = random.Random(4)
+    for _ in range(200):
+        p = [rng.uniform(-3, 3), rng.uniform(-3, 3), rng.uniform(-1, 1)]
+        if abs(t.eval(*p)) > 0.02:
+            assert (s.eval(*p) < 0) == (t.eval(*p) < 0)
+
+    # on a closed loop that isn't planar, rotation-minimizing frames come
+    # back twisted; the twist is spread along the loop, so the profile
+    # still lines up across the closing joint
+    n = 12
+    loop = [
+        (
+            2 * math.cos(math.tau * i / n),
+            2 * math.sin(math.tau * i / n),
+            1.2 * math.sin(math.tau * i / n) + 0.3 * math.cos(3 * math.tau * i / n),
+        )
+        for i in range(n)
+    ]
+    s = shapes.sweep(shapes.rectangle(0.8, 0.3), loop, closed=True)
+    t0 = [b - a for a, b in zip(loop[0], loop[1])]
+    t1 = [b - a for a, b in zip(loop[-1], loop[0])]
+    m = [a / math.hypot(*t0) + b / math.hypot(*t1) for a, b in zip(t0, t1)]
+    m = [a / math.hypot(*m) for a in m]
+    for _ in range(100):
+        p = [a + rng.uniform(-0.5, 0.5) for a in loop[0]]
+        ahead = s.eval(*[a + 1e-3 * b for a, b in zip(p, m)])
+        behind = s.eval(*[a - 1e-3 * b for a, b in zip(p, m)])
+        assert abs(ahead - behind) <= 2e-3 + 1e-6
+
+    # splines pass through (Catmull-Rom) or end at (Bézier) their points,
+    # and every sweep is Lipschitz-1
+    for s in [
+        shapes.sweep(c, loop, closed=True, curve="catmull-rom"),
+        shapes.sweep(c, loop[:10], curve="bezier", samples=4),
+        shapes.sweep(c, loop, closed=True, frame="fixed"),
+    ]:
+        assert s.eval(*loop[3]) < 0 and s.eval(*loop[6]) < 0
+        f = s.tree.compile()
+        for _ in range(200):
+            p = [rng.uniform(-3, 3), rng.uniform(-3, 3), rng.uniform(-2, 2)]
+            q = [a + rng.uniform(-0.05, 0.05) for a in p]
+            assert abs(f.eval(*p) - f.eval(*q)) <= math.dist(p, q) + 1e-6
+
+    with pytest.raises(RuntimeError):
+        shapes.sweep(c, [(0, 0, 0)])
+    with pytest.raises(RuntimeError):
+        shapes.sweep(c, loop[:5], curve="bezier")
+    with pytest.raises(RuntimeError):
+        shapes.sweep(c, loop, curve="nurbs")
+    with pytest.raises(RuntimeError):
+        shapes.sweep(c, loop, frame="frenet")
+    with pytest.raises(RuntimeError):
+        shapes.sweep(c, [(0, 0, 0), (1, 0, 0), (0, 0, 0)])
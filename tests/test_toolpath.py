@@ -0,0 +1,260 @@
+import math
+import pathlib
+import random
+import pytest
+from fidgetpy import shapes, toolpath
+from fidgetpy.toolpath import Move
+from fidgetpy.types import BoundBox, Shape, Tree
+
+R = 0.25
+
+
+def square_endmill():
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    field = ((x.square() + y.square()).sqrt() - R).max(-z)
+    return Shape(field, BoundBox(-R, R, -R, R, 0, math.inf))
+
+
+def ball_endmill():
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    field = (x.square() + y.square() + (z - R).min(0).square()).sqrt() - R
+    return Shape(field, BoundBox(-R, R, -R, R, 0, math.inf))
+
+
+def square_field(q):
+    return max(math.hypot(q[0], q[1]) - R, -q[2])
+
+
+def ball_field(q):
+    return math.hypot(q[0], q[1], min(q[2] - R, 0)) - R
+
+
+def brute_force(field, positions, p):
+    return min(field([a - b for a, b in zip(p, c)]) for c in positions)
+
+
+def test_move():
+    m = Move("G2", (0, 0, 0), (1, 0, 0), center=(0.5, 0, 0))
+    assert m.kind == "G2" and m.plane == "xy"
+    assert m.start == (0, 0, 0) and m.end == (1, 0, 0)
+    assert m.center == (0.5, 0, 0)
+    assert repr(m) == (
+        "Move('G2', (0.0, 0.0, 0.0), (1.0, 0.0, 0.0), "
+        "center=(0.5, 0.0, 0.0), plane='xy')"
+    )
+    assert Move("G0", (0, 0, 0), (0, 0, 1)).center is None
+
+    with pytest.raises(RuntimeError):
+        Move("G4", (0, 0, 0), (1, 0, 0))
+    with pytest.raises(RuntimeError):
+        Move("G2", (0, 0, 0), (1, 0, 0))
+    with pytest.raises(RuntimeError):
+        Move("G1", (0, 0, 0), (1, 0, 0), center=(0, 0, 0))
+    with pytest.raises(RuntimeError):
+        Move("G3", (0, 0, 0), (1, 0, 0), center=(0.5, 0, 0), plane="xz")
+    with pytest.raises(RuntimeError):
+        toolpath.swept_volume(square_endmill(), [])
+    with pytest.raises(RuntimeError):
+        toolpath.swept_volume(
+            square_endmill(), [Move("G1", (0, 0, 0), (1, 0, 0))], tolerance=0
+        )
+    # a tool of finite length would be misplaced on vertical moves
+    short = Shape(square_endmill().tree, BoundBox(-R, R, -R, R, 0, 1))
+    with pytest.raises(RuntimeError):
+        toolpath.swept_volume(short, [Move("G1", (0, 0, 0), (0, 0, 5))])
+
+
+def test_swept_lines():
+    # a plunge, a slot, and a ramp back out
+    moves = [
+        Move("G0", (0, 0, 1), (0, 0, -0.5)),
+        Move("G1", (0, 0, -0.5), (2, 0, -0.5)),
+        Move("G1", (2, 0, -0.5), (2, 2, 0)),
+    ]
+    # the moves are under 3 long, so samples are under 0.0075 apart
+    positions = [
+        [a + (b - a) * i / 400 for a, b in zip(m.start, m.end)]
+        for m in moves
+        for i in range(401)
+    ]
+    rng = random.Random(5)
+    for tool, field in [(square_endmill(), square_field), (ball_endmill(), ball_field)]:
+        s = toolpath.swept_volume(tool, moves, tolerance=0.01)
+        assert not s.exact
+        assert s.bounds == BoundBox(-R, 2 + R, -R, 2 + R, -0.5, math.inf)
+        # the plunge and the slot are exact; the ramp may fall short by the
+        # tolerance, but never removes material that the tool doesn't reach
+        for p in [(0, 0, 0), (1, 0.2, -0.4), (1, 0.3, -0.4), (1, 0, -0.6)]:
+            assert abs(s.eval(*p) - brute_force(field, positions, p)) < 1e-9
+        for _ in range(300):
+            p = [rng.uniform(-0.5, 2.5), rng.uniform(-0.5, 2.5), rng.uniform(-1, 0.5)]
+            d, truth = s.eval(*p), brute_force(field, positions, p)
+            # sampled positions can miss the nearest one by half the spacing
+            assert d >= truth - 0.004
+            if truth < -0.02:
+                assert d < 0
+
+
+def test_swept_arcs():
+    rng = random.Random(6)
+    cases = [
+        # a flat quarter turn, counter-clockwise around (1, 0)
+        (Move("G3", (2, 0, 0), (1, 1, 0), center=(1, 0, 0)), 1e-9),
+        # a clockwise half turn
+        (Move("G2", (0, 0, 0), (2, 0, 0), center=(1, 0, 0)), 1e-9),
+        # a helical full turn, dropping by 0.5
+        (Move("G3", (2, 0, 0), (2, 0, -0.5), center=(1, 0, 0)), 0.02),
+        # a half turn below the x axis, in the zx plane
+        (Move("G2", (0, 0, 0), (2, 0, 0), center=(1, 0, 0), plane="zx"), 0.02),
+    ]
+    for m, error in cases:
+        s = toolpath.swept_volume(square_endmill(), [m], tolerance=0.01)
+        # sample the arc with the same angles as the move
+        [iu, iv, iw] = {"xy": [0, 1, 2], "zx": [2, 0, 1]}[m.plane]
+        a0 = math.atan2(m.start[iv] - m.center[iv], m.start[iu] - m.center[iu])
+        a1 = math.atan2(m.end[iv] - m.center[iv], m.end[iu] - m.center[iu])
+        sweep = (a1 - a0 if m.kind == "G3" else a0 - a1) % math.tau or math.tau
+        sweep = sweep if m.kind == "G3" else -sweep
+        positions = []
+        for i in range(1001):
+            t = i / 1000
+            p = [0.0] * 3
+            p[iu] = m.center[iu] + math.cos(a0 + sweep * t)
+            p[iv] = m.center[iv] + math.sin(a0 + sweep * t)
+            p[iw] = m.start[iw] + (m.end[iw] - m.start[iw]) * t
+            positions.append(p)
+        for p in positions[::50]:
+            assert s.bounds.xmin <= p[0] <= s.bounds.xmax
+            assert s.bounds.ymin <= p[1] <= s.bounds.ymax
+            assert s.bounds.zmin <= p[2] <= s.bounds.zmax
+        for _ in range(300):
+            p = [rng.uniform(-0.5, 2.5), rng.uniform(-1.5, 1.5), rng.uniform(-1.2, 0.5)]
+            d, truth = s.eval(*p), brute_force(square_field, positions, p)
+            # samples are under 0.0065 apart, and can miss the nearest
+            # position by half that
+            assert d >= truth - 0.0035
+            if truth < -error:
+                assert d < 0
+            if error < 1e-3:
+                assert abs(d - truth) < 0.0035
+
+
+def test_stock_removal():
+    # a pocket milled into a block of stock, as in examples/gcode.py
+    stock = shapes.move(shapes.box(3, 3, 1), 1, 1, -0.5)
+    moves = [
+        Move("G0", (0, 0, 0.5), (0, 0, -0.25)),
+        Move("G1", (0, 0, -0.25), (1.5, 0, -0.25)),
+        Move("G3", (1.5, 0, -0.25), (1.5, 1, -0.25), center=(1.5, 0.5, -0.25)),
+        Move("G1", (1.5, 1, -0.25), (0, 1, -0.25)),
+    ]
+    removed = toolpath.swept_volume(square_endmill(), moves)
+    part = shapes.difference(stock, removed)
+    assert part.eval(0.75, 0, -0.2) > 0 and part.eval(0.75, 0, -0.3) < 0
+    assert part.eval(2.1, 0.5, -0.2) > 0 and part.eval(0.75, 0.5, -0.2) < 0
+    mesh = part.mesh(5)
+    assert len(mesh.vertices) > 0
+
+
+def test_parse_gcode():
+    program = """%
+(a test program)
+G21 G17 G90
+T4 P4 Z0.0 D6.35 ; 1/4" spot drill
+(T19 P19 Z1.5 D9.525 ; 3/8" square endmill)
+T19 M6
+G0 X10 Y0 Z5
+G1 Z-1 F100
+G2 X0 Y10 R10
+G3 X10 Y0 R-10
+G2 X10 Y0 I-5 J0
+G91 G1 X1 Y1
+X1
+G90 G18 G2 X20 Z-1 I4.5 K0
+G17 M6 T4
+G98 G81 X0 Y0 Z-2 R1
+X5
+G80
+M30
+G1 X100
+"""
+    moves, tools = toolpath.parse_gcode(program)
+    assert tools == {
+        4: {"diameter": 6.35, "length": 0.0, "comment": '1/4" spot drill'},
+        19: {"diameter": 9.525, "length": 1.5, "comment": '3/8" square endmill'},
+    }
+    assert [m.kind for m in moves] == (
+        ["G0", "G1", "G2", "G3", "G2", "G1", "G1", "G2"]
+        + ["G0", "G0", "G1", "G0"]
+        + ["G0", "G1", "G0"]
+    )
+    assert all(m.tool == 19 for m in moves[:8])
+    assert all(m.tool == 4 for m in moves[8:])
+
+    # each move starts where the last one ended
+    assert moves[0].start == (0, 0, 0)
+    for a, b in zip(moves, moves[1:]):
+        assert a.end == b.start
+
+    # R picks the center on the side that gives the short way round for a
+    # positive radius, and the long way for a negative one
+    assert moves[2].center == pytest.approx((10, 10, -1))
+    assert moves[3].center == pytest.approx((0, 0, -1), abs=1e-9)
+    # a full circle, with I and J relative to the start
+    assert moves[4].start == moves[4].end and moves[4].center == (5, 0, -1)
+    # incremental moves, and an arc in the zx plane
+    assert moves[5].end == (11, 1, -1) and moves[6].end == (12, 1, -1)
+    assert moves[7].center == (16.5, 1, -1) and moves[7].plane == "zx"
+
+    # a drilling cycle climbs to the R plane, moves over the hole, feeds down,
+    # and retracts to where it started (G98) or the R plane, if higher
+    assert [m.end for m in moves[8:]] == [
+        (20, 1, 1),
+        (0, 0, 1),
+        (0, 0, -2),
+        (0, 0, 1),
+        (5, 0, 1),
+        (5, 0, -2),
+        (5, 0, 1),
+    ]
+
+    # units are converted, to those that the program selects by default
+    inches, _ = toolpath.parse_gcode("G20\nG1 X1\nG21 G1 Y25.4")
+    assert [m.end for m in inches] == [(1, 0, 0), pytest.approx((1, 1, 0))]
+    mm, _ = toolpath.parse_gcode("G20\nG1 X1\nG21 G1 Y25.4", units="mm")
+    assert [m.end for m in mm] == [(25.4, 0, 0), pytest.approx((25.4, 25.4, 0))]
+
+    # tool tables are in the program's units, even before it selects them
+    table = "(T1 P1 Z1 D0.25)\nG20\nT2 P2 D0.5\nG21 G1 X1"
+    _, tools = toolpath.parse_gcode(table)
+    assert tools[1]["diameter"] == 0.25 and tools[2]["diameter"] == 0.5
+    _, tools = toolpath.parse_gcode(table, units="mm")
+    assert tools[1]["diameter"] == pytest.approx(6.35)
+    assert tools[1]["length"] == pytest.approx(25.4)
+    assert tools[2]["diameter"] == pytest.approx(12.7)
+    # and so are moves before the first units code
+    moves, tools = toolpath.parse_gcode("(T1 P1 D0.25)\nG1 X1\nG20\nG1 X2", units="mm")
+    assert tools[1]["diameter"] == pytest.approx(6.35)
+    assert [m.end for m in moves] == [
+        pytest.approx((25.4, 0, 0)),
+        pytest.approx((50.8, 0, 0)),
+    ]
+
+    for bad in ["G1 X", "G2 X1 Y1", "G2 X10 R1", "G81 X1 Z-1", "G1 X1 $"]:
+        with pytest.raises(RuntimeError):
+            toolpath.parse_gcode(bad)
+    # parameters, expressions and checksums are rejected, not skipped
+    for bad in ["#1 = 2", "G1 X[1 + 2]", "G1 X1 *42"]:
+        with pytest.raises(RuntimeError, match="aren't supported"):
+            toolpath.parse_gcode(bad)
+    with pytest.raises(RuntimeError):
+        toolpath.parse_gcode("G1 X1", units="ft")
+
+    # the moves of a real program can be swept, one tool at a time
+    path = pathlib.Path(__file__).parent.parent / "examples"
+    moves, _ = toolpath.parse_gcode((path / "simple_3axis_machining.nc").read_text())
+    assert {m.tool for m in moves} == {4, 5, 19, 121}
+    drilled = [m for m in moves if m.tool == 121 and m.kind == "G1"]
+    assert len(drilled) == 7 and all(m.end[2] == -0.6241 for m in drilled)
+    s = toolpath.swept_volume(square_endmill(), drilled)
+    assert s.eval(0, -0.31, -0.6) < 0 < s.eval(0.5, -0.31, -0.6)
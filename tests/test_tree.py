@@ -1,6 +1,11 @@
+import math
+import pickle
+import random
+import struct
 import pytest
 from fidgetpy.types import Tree
 from fidgetpy.errors import FidgetError
+from fidgetpy._core import BACKENDS
 
 txt = """# This is a comment!
 0x600000b90000 var-x
@@ -19,3 +24,475 @@ def test_vm_import():
     incorrect = """addmul 1 2"""
     with pytest.raises(FidgetError):
         _ = Tree.from_vm(incorrect)
+
+
+def test_raw_and_nrrd_export(tmp_path):
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    t = x + 2 * y + 3 * z
+    bounds = (0.0, 1.0, 0.0, 1.0, 0.0, 1.0)
+    t.to_nrrd(str(tmp_path / "field.nrrd"), bounds, (3, 4, 5))
+    header, data = (tmp_path / "field.nrrd").read_bytes().split(b"\n\n", 1)
+    assert header.startswith(b"NRRD0004")
+    assert b"sizes: 3 4 5" in header
+    assert b"space origin: (0,0,0)" in header
+    values = struct.unpack(f"<{3 * 4 * 5}f", data)
+    # x varies fastest, so the second sample is one step along x
+    assert values[0] == 0.0
+    assert abs(values[1] - 0.5) < 1e-6
+    assert abs(values[-1] - 6.0) < 1e-6
+
+    nhdr = t.to_raw(str(tmp_path / "field.raw"), bounds, (3, 4, 5))
+    assert nhdr.endswith("field.nhdr")
+    assert b"data file: field.raw" in (tmp_path / "field.nhdr").read_bytes()
+    assert (tmp_path / "field.raw").read_bytes() == data
+
+    # the header can't replace the samples
+    with pytest.raises(RuntimeError):
+        t.to_raw(str(tmp_path / "field2.nhdr"), bounds, (3, 4, 5))
+    assert not (tmp_path / "field2.nhdr").exists()
+    for bad_bounds, resolution in [
+        (bounds, (3, 0, 5)),
+        ((1, 0, 0, 1, 0, 1), (3, 4, 5)),
+        ((0, math.nan, 0, 1, 0, 1), (3, 4, 5)),
+        ((0, 1, 0, math.inf, 0, 1), (3, 4, 5)),
+    ]:
+        with pytest.raises(RuntimeError):
+            t.to_nrrd(str(tmp_path / "bad.nrrd"), bad_bounds, resolution)
+
+
+def positive(t):
+    return abs(t) + 0.5
+
+
+# The text format is parsed through the Context builders, which fold e.g.
+# 0 * x and 0 / x to 0 even when x is NaN, and may swap the arguments of
+# min(-0.0, 0.0). Operations are wrapped to keep every intermediate value
+# finite, and away from places where the sign of zero matters.
+UNARY_OPS = [
+    lambda a: a.square(),
+    lambda a: a.floor(),
+    lambda a: positive(a).recip(),
+    lambda a: a.ceil(),
+    lambda a: a.round(),
+    lambda a: positive(a).sqrt(),
+    lambda a: a.neg(),
+    lambda a: a.sin(),
+    lambda a: a.cos(),
+    lambda a: a.sin().tan(),
+    lambda a: a.sin().asin(),
+    lambda a: a.cos().acos(),
+    lambda a: a.atan(),
+    lambda a: a.sin().exp(),
+    lambda a: positive(a).ln(),
+    lambda a: a.not_(),
+    lambda a: a.abs(),
+]
+BINARY_OPS = [
+    lambda a, b: a.add(b),
+    lambda a, b: a.sub(b),
+    lambda a, b: a.mul(b),
+    lambda a, b: a.div(positive(b)),
+    lambda a, b: a.max(b),
+    lambda a, b: a.min(b),
+    lambda a, b: a.compare(b),
+    lambda a, b: a.modulo(positive(b)),
+    lambda a, b: a.and_(b),
+    lambda a, b: a.or_(b),
+    lambda a, b: a.atan2(positive(b)),
+]
+
+
+def random_tree(rng, depth):
+    # every subtree contains an axis, so that nothing is constant-folded into
+    # values which can't be represented by the tape's 32-bit immediates
+    if depth == 0:
+        return rng.choice([Tree.x, Tree.y, Tree.z])()
+    if rng.random() < 0.3:
+        return rng.choice(UNARY_OPS)(random_tree(rng, depth - 1))
+    a = random_tree(rng, depth - 1)
+    op = rng.choice(BINARY_OPS)
+    if rng.random() < 0.5:
+        return op(a, random_tree(rng, depth - 1))
+    # zero is avoided, since contexts don't distinguish between 0.0 and -0.0
+    c = Tree.constant(rng.choice([-1, 1]) * rng.randint(1, 8) / 4)
+    return op(c, a) if rng.random() < 0.5 else op(a, c)
+
+
+def same_value(a, b):
+    if math.isnan(a) or math.isnan(b):
+        return math.isnan(a) and math.isnan(b)
+    return a == b or abs(a - b) <= 1e-9 * max(abs(a), abs(b))
+
+
+def test_vm_round_trip():
+    rng = random.Random(1234)
+    for _ in range(300):
+        t = random_tree(rng, rng.randint(1, 6))
+        t2 = Tree.from_vm(t.to_vm())
+        for _ in range(5):
+            p = [rng.uniform(-3.0, 3.0) for _ in range(3)]
+            assert same_value(t.eval(*p), t2.eval(*p)), t.to_vm()
+
+
+def test_vm_round_trip_regressions():
+    x, y = Tree.x(), Tree.y()
+    # mod used to be exported as max, and atan2 as the unary atan
+    cases = [
+        x % y,
+        x % 1.5,
+        2.5 % x,
+        x.atan2(y),
+        x.atan2(0.5),
+        Tree.constant(0.5).atan2(x),
+        Tree.constant(0.5).and_(x),
+    ]
+    for t in cases:
+        t2 = Tree.from_vm(t.to_vm())
+        assert same_value(t.eval(0.7, -1.3, 0.0), t2.eval(0.7, -1.3, 0.0))
+    # constant roots
+    assert Tree.from_vm(Tree.constant(3.0).to_vm()).eval(0, 0, 0) == 3.0
+
+
+def test_vm_anonymous_vars():
+    x, a, b = Tree.x(), Tree.var(), Tree.var()
+    t = x * a + b
+    src = t.to_vm()
+    assert src.count("var-") == 3
+    t2 = Tree.from_vm(src)
+    # variables keep their identity when re-imported in the same process
+    values = {x: 2.0, a: 3.0, b: 0.5}
+    assert t2.eval_map(values) == t.eval_map(values) == 6.5
+
+    def var_names(src):
+        return sorted(w for w in src.split() if w.startswith("var-"))
+
+    assert var_names(t2.to_vm()) == var_names(src)
+
+    # names that haven't been seen before become new variables
+    t3 = Tree.from_vm("0 var-x\n1 var-radius\n2 sub 0 1\n")
+    with pytest.raises(FidgetError):
+        t3.eval(1.0, 0.0, 0.0)
+    assert "var-radius" in t3.to_vm()
+
+
+def test_bytes_round_trip():
+    rng = random.Random(99)
+    for _ in range(100):
+        t = random_tree(rng, rng.randint(1, 6))
+        t2 = Tree.from_bytes(t.to_bytes())
+        for _ in range(3):
+            p = [rng.uniform(-3.0, 3.0) for _ in range(3)]
+            assert same_value(t.eval(*p), t2.eval(*p))
+
+    # shared subtrees are written once
+    x, y = Tree.x(), Tree.y()
+    s = (x * x + y * y).sqrt()
+    for _ in range(20):
+        s = s * s
+    data = s.to_bytes()
+    assert len(data) < 500
+    assert Tree.from_bytes(data).to_bytes() == data
+
+    # remapped trees and exact constants survive
+    r = (x + Tree.constant(0.1)).remap_xyz(y, x, Tree.z())
+    assert Tree.from_bytes(r.to_bytes()).eval(0, 2, 0) == 2.1
+
+    with pytest.raises(FidgetError):
+        Tree.from_bytes(b"not a tree")
+    with pytest.raises(FidgetError):
+        Tree.from_bytes(data[:-3])
+
+
+def test_pickle():
+    a = Tree.var()
+    t = (Tree.x() - a).abs()
+    t2 = pickle.loads(pickle.dumps(t))
+    assert isinstance(t2, Tree)
+    assert t2.eval_map({Tree.x(): 1.0, a: 3.5}) == 2.5
+
+
+def test_named_vars(tmp_path):
+    x = Tree.x()
+    r = Tree.var("radius")
+    assert Tree.var("radius").eval_map({"radius": 2.0}) == 2.0
+    t = x * x + Tree.y().square() - r * r
+    assert t.variables() == ["radius", "x", "y"]
+    assert t.eval_map({"x": 3.0, "y": 0.0, "radius": 2.0}) == 5.0
+    # trees and names can be mixed
+    assert t.eval_map({x: 3.0, "y": 0.0, r: 2.0}) == 5.0
+    assert "var-radius" in t.to_vm()
+    assert Tree.from_vm(t.to_vm()).variables() == t.variables()
+
+    with pytest.raises(RuntimeError):
+        Tree.var("wall thickness")
+    with pytest.raises(RuntimeError):
+        Tree.var("x")
+
+    # reading names doesn't register them, so an anonymous variable's name
+    # can't be used to look it up before it's exported
+    a = Tree.var()
+    (name,) = a.variables()
+    assert a.op == f"var-{name}"
+    assert (a + r).eval_map({name: 5.0, "radius": 1.0, a: 2.0}) == 3.0
+
+    # free variables are bound before exporting
+    mesh = t.mesh(5, 0.0, 0.0, 0.0, 3.0, {"radius": 1.0})
+    assert len(mesh.triangles) > 0
+    path = tmp_path / "circle.nrrd"
+    t.to_nrrd(str(path), (-2, 2, -2, 2, 0, 0), (5, 5, 1), {"radius": 1.0})
+    data = path.read_bytes()
+    assert struct.unpack("<f", data[-13 * 4 : -12 * 4])[0] == -1.0
+
+
+def test_bind():
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    r = Tree.var("bind_radius")
+    sphere = (x * x + y * y + z * z).sqrt() - r
+    disk = sphere.bind({z: 0.5, "bind_radius": 2.0})
+    assert disk.variables() == ["x", "y"]
+    assert len(disk.to_vm().splitlines()) < len(sphere.to_vm().splitlines())
+    for p in [(0.0, 0.0), (1.0, -2.0), (0.3, 0.4)]:
+        expected = sphere.eval_map({x: p[0], y: p[1], z: 0.5, r: 2.0})
+        assert disk.eval(p[0], p[1], 123.0) == expected
+
+    # binding everything leaves a single constant
+    c = sphere.bind({"x": 0.0, "y": 3.0, "z": 4.0, r: 1.0})
+    assert c.to_vm().strip() == "$0 const 4"
+    # unbound variables and the original tree are untouched
+    assert sphere.bind({}).variables() == sphere.variables()
+
+    # the result is simplified after folding
+    s = Tree.var("bind_sign")
+    assert (-(x * s)).bind({s: -1.0}).op == "var-x"
+    # binding a parameter keeps an exact field exact
+    exact = Tree.from_bytes(sphere.to_bytes(), exact=True)
+    assert exact.bind({r: 1.0}).exact
+    assert not sphere.bind({r: 1.0}).exact
+
+
+def test_substitute():
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    w = Tree.var("sub_width")
+    slab = (x.abs() - w / 2).max(z.abs() - 1.0)
+
+    # a variable becomes an expression of other variables
+    h = Tree.var("sub_height")
+    t = slab.substitute({w: h * 2, "z": y})
+    assert t.variables() == ["sub_height", "x", "y"]
+    assert t.eval_map({x: 0.5, y: 0.0, z: 99.0, h: 1.0}) == -0.5
+    # swapping is simultaneous, not sequential
+    swapped = (x - y).substitute({x: y, y: x})
+    assert swapped.eval(1.0, 3.0, 0.0) == 2.0
+
+    # subtrees can be replaced too, and numbers become constants
+    r2 = x * x + y * y
+    t = (r2 + z).sqrt().substitute({r2: 9.0})
+    assert t.variables() == ["z"]
+    assert t.eval(0.0, 0.0, 7.0) == 4.0
+
+    with pytest.raises(RuntimeError):
+        x.substitute({1.0: y})
+
+
+def test_simplify():
+    x, y = Tree.x(), Tree.y()
+    # the same expression built twice, plus identities and double negation
+    t = (-(-(x * 1.0 + 0.0))).abs().min((-x).abs()) + (y * y) * (y * y)
+    t2 = t.simplify()
+    before, after = t.simplify_report()
+    assert after < before
+    assert after == len(list(t2.walk()))
+    assert t2.to_vm().count("abs") == 1
+    assert "neg" not in t2.to_vm()
+    assert isinstance(t.simplify(), Tree)
+    assert Tree.from_bytes(t.to_bytes(), exact=True).simplify().exact
+
+    rng = random.Random(4321)
+    for _ in range(200):
+        t = random_tree(rng, rng.randint(1, 6))
+        t2 = t.simplify()
+        for _ in range(5):
+            p = [rng.uniform(-3.0, 3.0) for _ in range(3)]
+            assert same_value(t.eval(*p), t2.eval(*p)), t.to_vm()
+
+
+def test_structural_equality():
+    x, y = Tree.x(), Tree.y()
+    a = (x * x + y).sqrt()
+    b = (Tree.x() * Tree.x() + Tree.y()).sqrt()
+    assert a is not b
+    assert a == b and a.structurally_equal(b)
+    assert hash(a) == hash(b)
+    assert a != (y + x * x).sqrt()
+    assert a != (x * x + y).sqrt() + 0.0
+    assert Tree.constant(0.0) != Tree.constant(-0.0)
+    assert Tree.var() != Tree.var()
+    assert Tree.var("eq_named") == Tree.var("eq_named")
+    # comparisons with other types don't build trees
+    assert (x == 1.0) is False
+    assert x != "x"
+
+    cache = {a: "sphere-ish"}
+    assert cache[b] == "sphere-ish"
+    assert len({a, b, Tree.x()}) == 2
+    assert x.eval_map({Tree.x(): 2.0}) == 2.0
+
+
+def test_introspection():
+    x, y = Tree.x(), Tree.y()
+    t = (x * 2.0 + y).atan2(Tree.var("walk_angle"))
+    assert t.op == "atan2"
+    lhs, rhs = t.children
+    assert rhs.op == "var-walk_angle"
+    assert lhs.op == "add"
+    assert [c.op for c in lhs.children] == ["mul", "var-y"]
+    c = lhs.children[0].children[1]
+    assert c.op == "const" and c.constant_value == 2.0
+    assert x.constant_value is None and x.children == []
+
+    s = x * x + x
+    nodes = list(s.walk())
+    assert [n.op for n in nodes] == ["var-x", "mul", "add"]
+    assert nodes[-1] == s
+    # every node comes after its children
+    for i, n in enumerate(nodes):
+        for child in n.children:
+            assert child in nodes[:i]
+
+
+def test_stats():
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    t = (x * x + y * y).sqrt().min(z.abs() - 1.0).max(-z)
+    # len() and repr() don't need the full statistics, but agree with them
+    n = len(t)
+    assert repr(t) == f"<Tree, {n} nodes>"
+    s = t.stats()
+    assert len(t) == n == s["nodes"]
+    assert s["ops"]["var-x"] == 1
+    assert s["ops"]["mul"] == 2
+    assert s["choices"] == 2
+    assert s["nodes"] == sum(s["ops"].values())
+    # max <- min <- sqrt <- add <- mul <- x
+    assert s["depth"] == 6
+    assert 0 < s["tape_len"] <= s["reg_tape_len"]
+    assert s["registers"] > 0 and s["memory_slots"] == 0
+    assert s["jit_bytes"] > 0
+    # cached, and returned as a fresh dict each time
+    s["nodes"] = -1
+    assert t.stats()["nodes"] == sum(s["ops"].values())
+
+    # many simultaneously live values spill out of registers
+    waves = [(x * float(i)).sin() for i in range(1, 31)]
+    big = sum(waves[1:], waves[0])
+    for w in waves:
+        big = big * w
+    assert big.stats()["memory_slots"] > 0
+    # len() also counts the unused y and z axes
+    assert len(big) == big.stats()["nodes"] + 2
+
+
+@pytest.mark.parametrize("backend", BACKENDS)
+def test_compiled_shape(backend):
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    r = Tree.var("compiled_radius")
+    sphere = (x * x + y * y + z * z).sqrt() - r
+    c = sphere.compile(backend, vars={r: 0.5})
+    assert c.backend == backend
+    assert c.eval(1.0, 0.0, 0.0) == 0.5
+    assert c.eval_many([0.0, 1.0], [0.0, 0.0], [0.0, 0.0]) == [-0.5, 0.5]
+    lo, hi = c.eval_interval((0.0, 1.0), (0.0, 1.0), (0.0, 1.0))
+    assert lo <= -0.5 and hi >= 3**0.5 - 0.5 - 1e-6
+    with pytest.raises(FidgetError):
+        c.eval_many([0.0], [0.0, 1.0], [0.0])
+
+    mesh = c.mesh(5, 0.0, 0.0, 0.0, 1.0)
+    assert len(mesh.triangles) > 0
+    image = c.render_2d(32)
+    assert len(image) == 32 * 32
+    # the disk at z = 0 covers about pi / 16 of the image
+    assert abs(image.count(255) / len(image) - math.pi / 16) < 0.05
+    heights = c.render_3d(16)
+    assert max(heights) == 12 and min(heights) == 0
+
+    with pytest.raises(FidgetError):
+        sphere.compile(backend)
+    with pytest.raises(RuntimeError):
+        sphere.compile("gpu", vars={r: 0.5})
+
+
+def assert_meshes_close(a, b, tol=1e-5):
+    # meshing is multithreaded, so vertex and triangle order can vary between
+    # runs, and backends may round differently; every triangle of b must
+    # match one of a (in some rotation, keeping its winding) within tol
+    assert len(a.triangles) == len(b.triangles)
+
+    def triangles(mesh):
+        vertices = mesh.vertices
+        return [[vertices[i] for i in t] for t in mesh.triangles]
+
+    def key(t, cell=1e-3):
+        return tuple(round(sum(v[i] for v in t) / 3 / cell) for i in range(3))
+
+    def close(p, q):
+        return all(abs(u - v) <= tol for u, v in zip(p, q))
+
+    buckets = {}
+    for t in triangles(a):
+        buckets.setdefault(key(t), []).append(t)
+    for t in triangles(b):
+        kx, ky, kz = key(t)
+        nearby = [
+            s
+            for dx in (-1, 0, 1)
+            for dy in (-1, 0, 1)
+            for dz in (-1, 0, 1)
+            for s in buckets.get((kx + dx, ky + dy, kz + dz), [])
+        ]
+        assert any(
+            all(close(s[(k + r) % 3], t[k]) for k in range(3))
+            for s in nearby
+            for r in range(3)
+        ), t
+
+
+def test_backends(tmp_path):
+    assert BACKENDS[-1] == "vm"
+    x, y, z = Tree.x(), Tree.y(), Tree.z()
+    box = (x.abs() - 0.6).max(y.abs() - 0.4).max(z.abs() - 0.5)
+    shape = box.max(0.7 - (x * x + y * y + z * z).sqrt())
+
+    # the backends should agree up to rounding
+    meshes = [shape.mesh(5, 0.0, 0.0, 0.0, 1.0, backend=b) for b in BACKENDS]
+    assert len(meshes[0].triangles) > 0
+    for m in meshes[1:]:
+        assert_meshes_close(meshes[0], m)
+    bounds = (-1.0, 1.0, -1.0, 1.0, -1.0, 1.0)
+    volumes = []
+    for b in BACKENDS:
+        path = tmp_path / f"{b}.nrrd"
+        shape.to_nrrd(str(path), bounds, (9, 9, 9), backend=b)
+        _, data = path.read_bytes().split(b"\n\n", 1)
+        volumes.append(struct.unpack(f"<{9**3}f", data))
+    for v in volumes[1:]:
+        assert all(abs(p - q) < 1e-6 for p, q in zip(v, volumes[0]))
+
+    # point evaluation can be checked against the slow evaluator too
+    r = Tree.var("backend_radius")
+    ball = (x.square() + y.square() + z.square()).sqrt() - r
+    p = (0.3, -0.2, 0.9)
+    exact = ball.eval_map({"x": p[0], "y": p[1], "z": p[2], r: 0.5})
+    for b in BACKENDS:
+        assert abs(shape.eval(*p, backend=b) - shape.eval(*p)) < 1e-6
+        values = {"x": p[0], "y": p[1], "z": p[2], r: 0.5}
+        assert abs(ball.eval_map(values, backend=b) - exact) < 1e-6
+        # every variable needs a value, as without a backend
+        with pytest.raises(FidgetError):
+            ball.eval_map({"x": p[0], "y": p[1], r: 0.5}, backend=b)
+        with pytest.raises(FidgetError):
+            ball.eval(*p, backend=b)
+
+    assert shape.compile().backend == BACKENDS[0]
+    with pytest.raises(RuntimeError):
+        shape.mesh(3, 0.0, 0.0, 0.0, 1.0, backend="gpu")
+    with pytest.raises(RuntimeError):
+        shape.eval(*p, backend="gpu")